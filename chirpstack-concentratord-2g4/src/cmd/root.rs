@@ -5,7 +5,7 @@ use std::thread;
 
 use libconcentratord::signals;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, events, jitqueue, reset};
+use libconcentratord::{capture, commands, events, filters, jitqueue, reset, simulator, testtx};
 use libloragw_2g4::hal;
 
 use super::super::{concentrator, config, handler, wrapper};
@@ -21,14 +21,8 @@ pub fn run(
         "https://www.chirpstack.io/concentratord/"
     );
 
-    // reset concentrator
-    reset::reset().expect("concentrator reset failed");
-
-    // setup concentrator
-    concentrator::board_setconf(&config)?;
-    concentrator::rx_setconf(&config)?;
-    concentrator::tx_setconf(&config)?;
-    concentrator::start()?;
+    let simulator = config.gateway.model == "simulator";
+    setup_concentrator(&config)?;
 
     // setup static location
     handler::gps::set_static_gps_coords(
@@ -38,7 +32,7 @@ pub fn run(
     );
 
     // get concentrator eui
-    let gateway_id = concentrator::get_eui().unwrap();
+    let gateway_id = concentrator::get_eui(&config)?;
 
     info!(
         "Gateway ID retrieved, gateway_id: {:x?}",
@@ -46,7 +40,8 @@ pub fn run(
     );
 
     // setup jit queue
-    let queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::new(32);
+    let jit_config = config.gateway.model_config.jit.merge(&config.gateway.jit);
+    let queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::from_config(&jit_config);
     let queue = Arc::new(Mutex::new(queue));
 
     // setup zeromq
@@ -54,6 +49,18 @@ pub fn run(
     let rep_sock = commands::get_socket(&config.concentratord.api.command_bind)
         .expect("bind command socket error");
 
+    // setup uplink filters
+    filters::setup(&config.concentratord.filters)?;
+
+    // setup packet capture
+    capture::setup(
+        &config.concentratord.capture,
+        match config.gateway.lorawan_public {
+            true => 0x21,
+            false => 0x12,
+        },
+    )?;
+
     // setup threads
     let mut signal_pool = signals::SignalPool::new();
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
@@ -70,18 +77,21 @@ pub fn run(
 
     // jit thread
     threads.push(thread::spawn({
+        let gateway_id = gateway_id.clone();
         let queue = Arc::clone(&queue);
         let antenna_gain = config.gateway.antenna_gain;
+        let calibrate = jit_config.calibrate;
         let stop_receive = signal_pool.new_receiver();
 
         move || {
-            handler::jit::jit_loop(queue, antenna_gain, stop_receive);
+            handler::jit::jit_loop(&gateway_id, queue, antenna_gain, calibrate, stop_receive);
         }
     }));
 
     // command thread
     threads.push(thread::spawn({
         let vendor_config = config.gateway.model_config.clone();
+        let tx_limits = config.gateway.tx_limits.clone();
         let gateway_id = gateway_id.clone();
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
@@ -91,6 +101,7 @@ pub fn run(
             handler::command::handle_loop(
                 lorawan_public,
                 &vendor_config,
+                &tx_limits,
                 &gateway_id,
                 queue,
                 rep_sock,
@@ -129,7 +140,32 @@ pub fn run(
         t.join().unwrap();
     }
 
-    concentrator::stop()?;
+    if testtx::is_active() {
+        testtx::stop()?;
+    }
+
+    if !simulator {
+        concentrator::stop()?;
+    }
 
     return Ok(stop_signal);
 }
+
+/// Reset and start the (simulated) concentrator.
+pub fn setup_concentrator(config: &config::Configuration) -> Result<(), String> {
+    if config.gateway.model == "simulator" {
+        // setup simulated concentrator
+        simulator::start(&config.gateway.simulator.uplink_script)?;
+    } else {
+        // reset concentrator
+        reset::reset().expect("concentrator reset failed");
+
+        // setup concentrator
+        concentrator::board_setconf(&config)?;
+        concentrator::rx_setconf(&config)?;
+        concentrator::tx_setconf(&config)?;
+        concentrator::start()?;
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,62 @@
+use super::super::config;
+
+/// Validate the given configuration files without accessing the concentrator
+/// hardware. Every problem is printed with its location. The returned value
+/// is the process exit code.
+pub fn run(filenames: &[String]) -> i32 {
+    let config = match config::try_get(filenames) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("{}", err);
+            return 1;
+        }
+    };
+
+    let errors = validate(&config);
+    if errors.is_empty() {
+        println!("Configuration is valid");
+        return 0;
+    }
+
+    for (location, err) in &errors {
+        println!("{}: {}", location, err);
+    }
+
+    return 1;
+}
+
+fn validate(config: &config::Configuration) -> Vec<(String, String)> {
+    let mut errors: Vec<(String, String)> = Vec::new();
+
+    for (i, channel) in config.gateway.concentrator.channels.iter().enumerate() {
+        if channel.frequency == 0 {
+            continue;
+        }
+
+        if channel.frequency < 2400000000 || channel.frequency > 2500000000 {
+            errors.push((
+                format!("gateway.concentrator.channels[{}].frequency", i),
+                format!(
+                    "frequency {} is not within the 2.4GHz band",
+                    channel.frequency
+                ),
+            ));
+        }
+
+        if ![203000, 406000, 812000, 1625000].contains(&channel.bandwidth) {
+            errors.push((
+                format!("gateway.concentrator.channels[{}].bandwidth", i),
+                format!("invalid bandwidth: {}", channel.bandwidth),
+            ));
+        }
+
+        if channel.spreading_factor < 5 || channel.spreading_factor > 12 {
+            errors.push((
+                format!("gateway.concentrator.channels[{}].spreading_factor", i),
+                format!("invalid spreading_factor: {}", channel.spreading_factor),
+            ));
+        }
+    }
+
+    errors
+}
@@ -1,3 +1,4 @@
+use libconcentratord::simulator;
 use libloragw_2g4::hal;
 
 use super::config::Configuration;
@@ -73,7 +74,88 @@ pub fn stop() -> Result<(), String> {
     return Ok(());
 }
 
-pub fn get_eui() -> Result<[u8; 8], String> {
+pub fn get_eui(config: &Configuration) -> Result<[u8; 8], String> {
     debug!("Getting the gateway EUI");
+    if simulator::enabled() {
+        let mut eui: [u8; 8] = [0; 8];
+        let b = hex::decode(&config.gateway.simulator.gateway_id).map_err(|e| e.to_string())?;
+        if b.len() != eui.len() {
+            return Err("simulator gateway_id must be exactly 8 bytes".to_string());
+        }
+        eui.copy_from_slice(&b);
+        return Ok(eui);
+    }
+
     return hal::get_eui();
 }
+
+pub fn receive() -> Result<Vec<hal::RxPacket>, String> {
+    if !simulator::enabled() {
+        return hal::receive();
+    }
+
+    let mut packets: Vec<hal::RxPacket> = Vec::new();
+    for uplink in simulator::receive()? {
+        let mut payload: [u8; 256] = [0; 256];
+        let size = uplink.phy_payload.len().min(payload.len());
+        payload[..size].copy_from_slice(&uplink.phy_payload[..size]);
+
+        packets.push(hal::RxPacket {
+            freq_hz: uplink.frequency,
+            channel: 0,
+            status: hal::CRC::CRCOk,
+            count_us: uplink.count_us,
+            freq_offset_hz: 0,
+            modulation: hal::Modulation::LoRa,
+            bandwidth: uplink.bandwidth,
+            datarate: match uplink.spreading_factor {
+                5 => hal::DataRate::SF5,
+                6 => hal::DataRate::SF6,
+                7 => hal::DataRate::SF7,
+                8 => hal::DataRate::SF8,
+                9 => hal::DataRate::SF9,
+                10 => hal::DataRate::SF10,
+                11 => hal::DataRate::SF11,
+                12 => hal::DataRate::SF12,
+                _ => return Err("invalid simulated spreading_factor".to_string()),
+            },
+            coderate: hal::CodeRate::LoRa4_5,
+            rssi: uplink.rssi as f32,
+            snr: uplink.snr,
+            size: size as u16,
+            payload: payload,
+        });
+    }
+
+    return Ok(packets);
+}
+
+pub fn send(tx_packet: &hal::TxPacket) -> Result<(), String> {
+    if simulator::enabled() {
+        return simulator::send(tx_packet.count_us, tx_packet.freq_hz, tx_packet.size);
+    }
+
+    return hal::send(tx_packet);
+}
+
+pub fn get_instcnt() -> Result<u32, String> {
+    if simulator::enabled() {
+        return simulator::get_count_us();
+    }
+
+    return hal::get_instcnt();
+}
+
+pub fn get_tx_status() -> Result<hal::TxStatus, String> {
+    if simulator::enabled() {
+        return match simulator::is_emitting()? {
+            true => Ok(hal::TxStatus::Emitting),
+            false => Ok(hal::TxStatus::Scheduled),
+        };
+    }
+
+    match hal::status(hal::StatusSelect::Tx)? {
+        hal::StatusReturn::Tx(v) => Ok(v),
+        _ => Err("unexpected status type".to_string()),
+    }
+}
@@ -1,8 +1,11 @@
-use std::fs;
+use std::process;
 
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use libconcentratord::config::Error;
+use libconcentratord::{capture, filters, jitqueue};
+
 pub mod vendor;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -22,12 +25,29 @@ pub struct Concentratord {
     #[serde(with = "humantime_serde")]
     pub stats_interval: Duration,
     pub api: API,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub state_file: String,
+    #[serde(default)]
+    pub filters: filters::Configuration,
+    #[serde(default)]
+    pub capture: capture::Configuration,
 }
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct API {
     pub event_bind: String,
     pub command_bind: String,
+    #[serde(default)]
+    pub curve_secret_key: String,
+    #[serde(default)]
+    pub curve_allowed_clients: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Metrics {
+    pub bind: String,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -39,9 +59,17 @@ pub struct Gateway {
     pub model: String,
     #[serde(default)]
     pub model_flags: Vec<String>,
+    #[serde(default)]
+    pub model_dir: String,
     pub concentrator: Concentrator,
     #[serde(default)]
     pub location: Location,
+    #[serde(default)]
+    pub tx_limits: TxLimits,
+    #[serde(default)]
+    pub simulator: Simulator,
+    #[serde(default)]
+    pub jit: jitqueue::Configuration,
     #[serde(skip)]
     pub model_config: vendor::Configuration,
     #[serde(skip)]
@@ -69,6 +97,30 @@ pub struct Location {
     pub altitude: i16,
 }
 
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct TxLimits {
+    #[serde(default)]
+    pub max_eirp: i8,
+    #[serde(default)]
+    pub clamp_eirp: bool,
+    #[serde(default)]
+    pub dwell_time: Vec<DwellTime>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DwellTime {
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    #[serde(with = "humantime_serde")]
+    pub max_dwell_time: Duration,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Simulator {
+    pub gateway_id: String,
+    pub uplink_script: String,
+}
+
 fn example_configuration() -> Configuration {
     Configuration {
         concentratord: Concentratord {
@@ -111,21 +163,78 @@ fn example_configuration() -> Configuration {
 }
 
 pub fn get(filenames: Vec<String>) -> Configuration {
-    let mut content: String = String::new();
+    match try_get(&filenames) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Load configuration error, error: {}", err);
+            process::exit(1);
+        }
+    }
+}
 
-    for file_name in &filenames {
-        content.push_str(&fs::read_to_string(&file_name).expect("Error reading config file"));
+/// Read and validate the given configuration files.
+pub fn try_get(filenames: &[String]) -> Result<Configuration, Error> {
+    let mut config: Configuration = libconcentratord::config::parse(filenames)?;
+
+    // validate CURVE keys
+    let api = &config.concentratord.api;
+    if !api.curve_secret_key.is_empty() {
+        libconcentratord::curve::decode_key(&api.curve_secret_key).map_err(|e| Error::Value {
+            location: "concentratord.api.curve_secret_key".to_string(),
+            error: e,
+        })?;
+    }
+    for (i, key) in api.curve_allowed_clients.iter().enumerate() {
+        libconcentratord::curve::decode_key(key).map_err(|e| Error::Value {
+            location: format!("concentratord.api.curve_allowed_clients[{}]", i),
+            error: e,
+        })?;
     }
 
-    let mut config: Configuration = toml::from_str(&content).expect("Error parsing config file");
+    // validate uplink filters
+    filters::Filters::new(&config.concentratord.filters).map_err(|e| Error::Value {
+        location: "concentratord.filters".to_string(),
+        error: e,
+    })?;
 
     // get model configuration
-    config.gateway.model_config = match config.gateway.model.as_ref() {
-        "semtech_sx1280z3dsfgw1" => vendor::semtech::sx1280z3dsfgw1::new(&config),
-        _ => panic!("unexpected gateway model: {}", config.gateway.model),
+    let models = vendor::get_models(&config.gateway.model_dir).map_err(|e| Error::Value {
+        location: "gateway.model_dir".to_string(),
+        error: e,
+    })?;
+    let model = match models.iter().find(|m| m.name == config.gateway.model) {
+        Some(v) => v,
+        None => {
+            return Err(Error::Value {
+                location: "gateway.model".to_string(),
+                error: format!(
+                    "unknown gateway model: {}, see the models subcommand for the supported models",
+                    config.gateway.model
+                ),
+            })
+        }
     };
 
+    for flag in &config.gateway.model_flags {
+        if !model.flags.contains(flag) {
+            return Err(Error::Value {
+                location: "gateway.model_flags".to_string(),
+                error: format!(
+                    "unknown flag: {}, accepted flags for {}: [{}]",
+                    flag,
+                    model.name,
+                    model.flags.join(", ")
+                ),
+            });
+        }
+    }
+
+    config.gateway.model_config = vendor::new(&config, model).map_err(|e| Error::Value {
+        location: "gateway.model".to_string(),
+        error: e,
+    })?;
+
     debug!("Antenna gain {} dBi", config.gateway.antenna_gain);
 
-    return config;
+    return Ok(config);
 }
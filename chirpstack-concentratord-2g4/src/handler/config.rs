@@ -26,6 +26,18 @@ pub fn update_configuration(
                     );
                 }
 
+                // validated here, as this would otherwise fail the concentrator setup
+                if v.spreading_factors[0] < 5 || v.spreading_factors[0] > 12 {
+                    return Err(format!(
+                        "invalid spreading_factor: {}",
+                        v.spreading_factors[0]
+                    ));
+                }
+
+                if ![203000, 406000, 812000, 1625000].contains(&v.bandwidth) {
+                    return Err(format!("invalid bandwidth: {}", v.bandwidth));
+                }
+
                 concentrator.channels[i] = Channel {
                     frequency: channel.frequency,
                     bandwidth: v.bandwidth,
@@ -3,4 +3,5 @@ pub mod config;
 pub mod gps;
 pub mod jit;
 pub mod stats;
+pub mod test_tx;
 pub mod uplink;
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use libconcentratord::{proto, testtx};
+use libloragw_2g4::hal;
+
+use super::super::config::vendor;
+use super::super::{concentrator, wrapper};
+
+struct Transmitter {
+    tx_packet: hal::TxPacket,
+}
+
+impl testtx::Transmitter for Transmitter {
+    fn cw_on(&mut self) -> Result<(), String> {
+        let mut tx_packet = self.tx_packet;
+        tx_packet.tx_mode = hal::TxMode::CWOn;
+        concentrator::send(&tx_packet)
+    }
+
+    fn cw_off(&mut self) -> Result<(), String> {
+        let mut tx_packet = self.tx_packet;
+        tx_packet.tx_mode = hal::TxMode::CWOff;
+        concentrator::send(&tx_packet)
+    }
+
+    fn send_packet(&mut self) -> Result<Duration, String> {
+        let time_on_air = hal::time_on_air(&self.tx_packet)?;
+        concentrator::send(&self.tx_packet)?;
+        return Ok(time_on_air);
+    }
+}
+
+/// Start the given test transmission. The concentrator must be started.
+pub fn start(
+    lorawan_public: bool,
+    vendor_config: &vendor::Configuration,
+    pl: &proto::TestTx,
+) -> Result<(), String> {
+    let params = testtx::Parameters::from_proto(pl)?;
+
+    let tx_packet = match pl.cw {
+        // The bandwidth is not used by the continuous wave, but it must be
+        // valid.
+        true => hal::TxPacket {
+            freq_hz: pl.frequency,
+            tx_mode: hal::TxMode::Immediate,
+            rf_power: pl.power as i8,
+            bandwidth: 812000,
+            ..Default::default()
+        },
+        false => wrapper::downlink_from_proto(lorawan_public, &testtx::get_downlink_item(pl))?,
+    };
+
+    let freqs = vendor_config.min_max_tx_freq;
+    if tx_packet.freq_hz < freqs.0 || tx_packet.freq_hz > freqs.1 {
+        return Err(format!(
+            "frequency is not within min/max gateway frequency, min_freq: {}, max_freq: {}",
+            freqs.0, freqs.1
+        ));
+    }
+
+    info!(
+        "Configuring test transmission, freq: {}, power: {}, cw: {}, dr: {:?}",
+        tx_packet.freq_hz, tx_packet.rf_power, pl.cw, tx_packet.datarate
+    );
+
+    testtx::start(
+        Transmitter {
+            tx_packet: tx_packet,
+        },
+        params,
+    )
+}
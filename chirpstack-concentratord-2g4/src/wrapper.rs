@@ -1,20 +1,24 @@
 use std::time::Duration;
 
-use libconcentratord::jitqueue;
+use libconcentratord::{capture, jitqueue};
 use libloragw_2g4::hal;
 use uuid::Uuid;
 
 #[derive(Copy, Clone)]
-pub struct TxPacket(hal::TxPacket, Uuid);
+pub struct TxPacket(hal::TxPacket, Uuid, jitqueue::Priority);
 
 impl TxPacket {
-    pub fn new(id: Uuid, tx_packet: hal::TxPacket) -> TxPacket {
-        TxPacket(tx_packet, id)
+    pub fn new(id: Uuid, tx_packet: hal::TxPacket, priority: jitqueue::Priority) -> TxPacket {
+        TxPacket(tx_packet, id, priority)
     }
 
     pub fn tx_packet(&self) -> hal::TxPacket {
         self.0
     }
+
+    pub fn id(&self) -> Uuid {
+        self.1
+    }
 }
 
 impl jitqueue::TxPacket for TxPacket {
@@ -26,9 +30,11 @@ impl jitqueue::TxPacket for TxPacket {
         match self.0.tx_mode {
             hal::TxMode::Timestamped => jitqueue::TxMode::Timestamped,
             hal::TxMode::OnGPS => jitqueue::TxMode::OnGPS,
-            hal::TxMode::Immediate => jitqueue::TxMode::Immediate,
-            hal::TxMode::CWOn => panic!("CWOn is not supported in queue"),
-            hal::TxMode::CWOff => panic!("CWOff is not supported in queue"),
+            // CW packets are only sent by the test transmission and never
+            // enqueued, they are sent immediately.
+            hal::TxMode::Immediate | hal::TxMode::CWOn | hal::TxMode::CWOff => {
+                jitqueue::TxMode::Immediate
+            }
         }
     }
 
@@ -51,6 +57,14 @@ impl jitqueue::TxPacket for TxPacket {
     fn get_id(&self) -> String {
         self.1.to_string()
     }
+
+    fn get_priority(&self) -> jitqueue::Priority {
+        self.2
+    }
+
+    fn get_frequency(&self) -> u32 {
+        self.0.freq_hz
+    }
 }
 
 pub fn uplink_to_proto(
@@ -223,3 +237,39 @@ pub fn downlink_from_proto(
 
     return Ok(packet);
 }
+
+pub fn downlink_to_capture(gateway_id: &[u8], packet: &hal::TxPacket) -> capture::Packet {
+    capture::Packet {
+        gateway_id: gateway_id.to_vec(),
+        count_us: packet.count_us,
+        frequency: packet.freq_hz,
+        bandwidth: packet.bandwidth,
+        spreading_factor: match packet.datarate {
+            hal::DataRate::SF5 => 5,
+            hal::DataRate::SF6 => 6,
+            hal::DataRate::SF7 => 7,
+            hal::DataRate::SF8 => 8,
+            hal::DataRate::SF9 => 9,
+            hal::DataRate::SF10 => 10,
+            hal::DataRate::SF11 => 11,
+            hal::DataRate::SF12 => 12,
+        },
+        code_rate: match packet.coderate {
+            hal::CodeRate::LoRa4_5 => "4/5",
+            hal::CodeRate::LoRa4_6 => "4/6",
+            hal::CodeRate::LoRa4_7 => "4/7",
+            hal::CodeRate::LoRa4_8 => "4/8",
+            hal::CodeRate::LoRaLi4_5 => "4/5LI",
+            hal::CodeRate::LoRaLi4_6 => "4/6LI",
+            hal::CodeRate::LoRaLi4_8 => "4/8LI",
+        }
+        .to_string(),
+        crc_status: match packet.no_crc {
+            true => chirpstack_api::gw::CrcStatus::NoCrc,
+            false => chirpstack_api::gw::CrcStatus::CrcOk,
+        },
+        iq_inverted: packet.invert_pol,
+        phy_payload: packet.payload[..packet.size as usize].to_vec(),
+        ..Default::default()
+    }
+}
@@ -4,6 +4,13 @@ use super::super::config;
 
 pub fn run(config: &config::Configuration) {
     let template = r#"
+# This configuration can be split over multiple files (e.g. a base file and
+# a conf.d directory with region specific files), which are merged in the
+# order given by the -c / --config flags. Any value can be overridden by a
+# CONCENTRATORD_<SECTION>__<KEY> environment variable, e.g.
+# CONCENTRATORD_GATEWAY__MODEL for the gateway model. The --debug-config
+# flag prints which file or environment variable set each value.
+
 # Concentratord configuration.
 [concentratord]
   # Log level.
@@ -25,6 +32,13 @@ pub fn run(config: &config::Configuration) {
   # Statistics interval.
   stats_interval="{{ concentratord.stats_interval }}"
 
+  # State file.
+  #
+  # When set, the gateway configuration received through the config command
+  # is written to this file and restored on (re)start of the Concentratord.
+  # When left blank, the received gateway configuration is not persisted.
+  state_file="{{ concentratord.state_file }}"
+
   # Configuration for the (ZeroMQ based) API.
   [concentratord.api]
     # Event PUB socket bind.
@@ -33,6 +47,106 @@ pub fn run(config: &config::Configuration) {
     # Command REP socket bind.
     command_bind="{{ concentratord.api.command_bind }}"
 
+    # CURVE secret key (Z85 encoded).
+    #
+    # When set, the event and command sockets use CURVE authentication and
+    # encryption. Clients must be configured with the matching server public
+    # key. A keypair can be generated with the keygen subcommand.
+    curve_secret_key="{{ concentratord.api.curve_secret_key }}"
+
+    # Allowed CURVE client public keys (Z85 encoded).
+    #
+    # When empty, any client knowing the server public key is allowed to
+    # connect. Else only the clients with one of the given public keys.
+    curve_allowed_clients=[{{#each concentratord.api.curve_allowed_clients}}"{{ this }}",{{/each}}]
+
+  # Prometheus metrics configuration.
+  [concentratord.metrics]
+    # Bind address of the Prometheus metrics HTTP endpoint, e.g. 0.0.0.0:9100.
+    #
+    # When left blank, the metrics endpoint is disabled.
+    bind="{{ concentratord.metrics.bind }}"
+
+  # Uplink filters.
+  #
+  # Uplinks which are rejected by these filters are not published on the
+  # event socket. The number of dropped uplinks per reason is reported in
+  # the gateway stats meta-data (rx_packets_dropped_<reason>) and metrics.
+  [concentratord.filters]
+    # Forward uplinks with a valid CRC.
+    forward_crc_ok={{ concentratord.filters.forward_crc_ok }}
+
+    # Forward uplinks with an invalid CRC.
+    forward_crc_invalid={{ concentratord.filters.forward_crc_invalid }}
+
+    # Forward uplinks without CRC.
+    forward_crc_missing={{ concentratord.filters.forward_crc_missing }}
+
+    # Allowed and denied NetIDs.
+    #
+    # Data uplinks are filtered by the DevAddr prefix of the NetID,
+    # rejoin-requests (type 0 and 2) by the NetID. When the allow list is
+    # empty, all NetIDs which are not denied are allowed.
+    #
+    # Example:
+    # net_ids=["000013"]
+    net_ids=[{{#each concentratord.filters.net_ids}}"{{ this }}",{{/each}}]
+    deny_net_ids=[{{#each concentratord.filters.deny_net_ids}}"{{ this }}",{{/each}}]
+
+    # Allowed and denied DevAddr prefixes.
+    #
+    # Data uplinks are allowed when the DevAddr matches one of the allowed
+    # DevAddr prefixes or NetIDs, and none of the denied prefixes.
+    #
+    # Example:
+    # dev_addr_prefixes=["26000000/7"]
+    dev_addr_prefixes=[{{#each concentratord.filters.dev_addr_prefixes}}"{{ this }}",{{/each}}]
+    deny_dev_addr_prefixes=[{{#each concentratord.filters.deny_dev_addr_prefixes}}"{{ this }}",{{/each}}]
+
+    # Allowed and denied JoinEUI prefixes.
+    #
+    # These are applied to join-requests and rejoin-requests (type 1).
+    #
+    # Example:
+    # join_eui_prefixes=["70b3d57ed0000000/32"]
+    join_eui_prefixes=[{{#each concentratord.filters.join_eui_prefixes}}"{{ this }}",{{/each}}]
+    deny_join_eui_prefixes=[{{#each concentratord.filters.deny_join_eui_prefixes}}"{{ this }}",{{/each}}]
+
+    # Max. number of data uplinks per DevAddr within the rate-limit interval.
+    #
+    # Set to 0 to disable rate limiting.
+    rate_limit_max_frames={{ concentratord.filters.rate_limit_max_frames }}
+
+    # Rate-limit interval.
+    rate_limit_interval="{{ concentratord.filters.rate_limit_interval }}"
+
+  # Packet capture configuration.
+  #
+  # When enabled, every received uplink and every emitted downlink is written
+  # to a pcap file using the LoRaTap link-type, which can be opened by
+  # Wireshark.
+  [concentratord.capture]
+
+    # Capture file.
+    #
+    # Leave this empty to disable writing captures to a file.
+    file="{{ concentratord.capture.file }}"
+
+    # Max. capture file size (bytes).
+    #
+    # When the file exceeds this size, it is rotated to file.1, file.2, ...
+    max_file_size={{ concentratord.capture.max_file_size }}
+
+    # Max. number of rotated capture files to keep.
+    max_files={{ concentratord.capture.max_files }}
+
+    # UDP target (e.g. 127.0.0.1:5555).
+    #
+    # When set, each captured packet (LoRaTap header + PHYPayload) is also
+    # sent to this UDP target. This can be used for live captures using the
+    # Wireshark udpdump interface, with "loratap" as payload protocol.
+    udp_target="{{ concentratord.capture.udp_target }}"
+
 
 # LoRa gateway configuration.
 [gateway]
@@ -62,6 +176,13 @@ pub fn run(config: &config::Configuration) {
   #     AP2  - Module is in AP2 slot
   model_flags=[{{#each gateway.model_flags}}"{{ this }}",{{/each}}]
 
+  # Gateway model directory.
+  #
+  # Directory containing additional gateway model definitions (*.toml files).
+  # A model definition with the same name as a built-in model overrides the
+  # built-in model. Use the models subcommand to list the available models.
+  model_dir="{{ gateway.model_dir }}"
+
   # Gateway ID.
   gateway_id="{{ gateway.gateway_id }}"
 
@@ -93,6 +214,40 @@ pub fn run(config: &config::Configuration) {
       datarate={{ gateway.concentrator.fsk.datarate }}
 
 
+  # Concentrator boards.
+  #
+  # Gateways with multiple concentrator boards (e.g. a 16 channel gateway with
+  # two SX1301 boards) are configured by adding a [[gateway.board]] section
+  # per board. Each board runs in its own process, with its own channel plan
+  # and JIT queue. The index of the board (in order of definition) is set as
+  # board in the uplink meta-data and is used to route downlinks. When
+  # boards are configured, the model, model_flags, reset_pin, antenna_gain
+  # and concentrator settings above are not used. Only the first board sends
+  # beacons. The gateway configuration command is not supported. The JIT
+  # queue configuration of a board ([gateway.board.jit]) overrides the
+  # [gateway.jit] configuration. The stats of all boards are published as a
+  # single stats event.
+  #
+  # Example:
+  #
+  # [[gateway.board]]
+  #   model="multitech_mtac_lora_h_915_us915"
+  #   model_flags=["AP1"]
+  #
+  #   [gateway.board.concentrator]
+  #     multi_sf_channels=[902300000, 902500000, 902700000, 902900000, 903100000, 903300000, 903500000, 903700000]
+  #
+  # [[gateway.board]]
+  #   model="multitech_mtac_lora_h_915_us915"
+  #   model_flags=["AP2"]
+  #
+  #   [gateway.board.jit]
+  #     tx_jit_delay="40ms"
+  #
+  #   [gateway.board.concentrator]
+  #     multi_sf_channels=[903900000, 904100000, 904300000, 904500000, 904700000, 904900000, 905100000, 905300000]
+
+
   # Beacon configuration.
   #
   # This requires a gateway with GPS / GNSS.
@@ -119,6 +274,141 @@ pub fn run(config: &config::Configuration) {
     tx_power={{ gateway.beacon.tx_power }}
 
 
+  # Duty-cycle configuration.
+  #
+  # When enabled, the transmit time per sub-band is tracked over a sliding
+  # window and downlinks exceeding the max duty-cycle of the sub-band are
+  # rejected. The current usage (%) per sub-band is reported in the gateway
+  # stats meta-data as duty_cycle_NAME.
+  #
+  # Please note that the sub-bands are region dependent. Duty-cycle
+  # enforcement is disabled by default, also in the packaged EU868
+  # configuration files which contain the ETSI EN 300 220 sub-bands.
+  [gateway.duty_cycle]
+
+    # Enable duty-cycle enforcement.
+    enable={{ gateway.duty_cycle.enable }}
+
+    # Sliding window.
+    window="{{ gateway.duty_cycle.window }}"
+
+    # Sub-bands.
+    #
+    # The duty_cycle is the max duty-cycle of the sub-band, e.g. 0.01 for 1%.
+    # Frequencies outside the configured sub-bands are not restricted.
+    #
+    # Example:
+    # [[gateway.duty_cycle.bands]]
+    #   name="g1"
+    #   frequency_min=868000000
+    #   frequency_max=868600000
+    #   duty_cycle=0.01
+{{#each gateway.duty_cycle.bands}}
+    [[gateway.duty_cycle.bands]]
+      name="{{ this.name }}"
+      frequency_min={{ this.frequency_min }}
+      frequency_max={{ this.frequency_max }}
+      duty_cycle={{ this.duty_cycle }}
+{{/each}}
+
+
+  # Simulator configuration.
+  #
+  # This is only used when the gateway model is set to "simulator". The
+  # simulator does not require any concentrator hardware and provides a
+  # simulated GPS time.
+  [gateway.simulator]
+
+    # Uplink script.
+    #
+    # Path to a file containing the uplinks to simulate, one uplink per line:
+    #   <time_ms> <frequency> <bandwidth> <spreading_factor> <rssi> <snr> <phy_payload_hex>
+    #
+    # The time_ms is the time (ms) after start at which the uplink is received.
+    # Lines starting with # are ignored.
+    uplink_script="{{ gateway.simulator.uplink_script }}"
+
+
+  # JIT queue configuration.
+  #
+  # The timing values and capacity default to the values of the gateway
+  # model. These can be tuned when the gateway misses its RX windows, e.g.
+  # using the values measured in calibration mode.
+  [gateway.jit]
+
+    # TX start delay.
+    #
+    # Time between the scheduled TX time and the start of the emission.
+    {{#if gateway.jit.tx_start_delay}}tx_start_delay="{{ gateway.jit.tx_start_delay }}"{{else}}# tx_start_delay="1500us"{{/if}}
+
+    # TX margin delay.
+    #
+    # Minimum time between two transmissions.
+    {{#if gateway.jit.tx_margin_delay}}tx_margin_delay="{{ gateway.jit.tx_margin_delay }}"{{else}}# tx_margin_delay="1ms"{{/if}}
+
+    # TX JIT delay.
+    #
+    # Time in advance of the transmission at which a packet is sent to the
+    # concentrator. This must be larger than the time needed to send a packet
+    # to the concentrator.
+    {{#if gateway.jit.tx_jit_delay}}tx_jit_delay="{{ gateway.jit.tx_jit_delay }}"{{else}}# tx_jit_delay="30ms"{{/if}}
+
+    # TX max. advance delay.
+    #
+    # Maximum time in advance for which a packet can be enqueued.
+    {{#if gateway.jit.tx_max_advance_delay}}tx_max_advance_delay="{{ gateway.jit.tx_max_advance_delay }}"{{else}}# tx_max_advance_delay="512s"{{/if}}
+
+    # Queue capacity.
+    #
+    # Maximum number of queued packets.
+    {{#if gateway.jit.capacity}}capacity={{ gateway.jit.capacity }}{{else}}# capacity=32{{/if}}
+
+    # Calibration mode.
+    #
+    # When enabled, the concentrator TX status is polled after sending a
+    # packet to measure the send duration and the offset between the detected
+    # and scheduled start of the emission. The measurements are logged and
+    # exposed as Prometheus metrics. Packets sent while the previous packet is
+    # still being measured are not measured.
+    calibrate={{ gateway.jit.calibrate }}
+
+
+  # TX limits configuration.
+  #
+  # Please note that these limits are region dependent. The correct settings
+  # can be found in the LoRaWAN Regional Parameters specification.
+  [gateway.tx_limits]
+
+    # Max EIRP (dBm).
+    #
+    # The tx power requested by the network server is the EIRP, the antenna_gain
+    # is subtracted from this before transmission. Set to 0 to disable.
+    max_eirp={{ gateway.tx_limits.max_eirp }}
+
+    # Clamp the EIRP.
+    #
+    # When set to true, the tx power of a downlink exceeding the max EIRP is
+    # set to the max EIRP. When set to false, the downlink is rejected.
+    clamp_eirp={{ gateway.tx_limits.clamp_eirp }}
+
+    # Max dwell time per frequency range.
+    #
+    # Downlinks within the frequency range with a time on air exceeding the
+    # max dwell time are rejected with the TX_FREQ status.
+    #
+    # Example:
+    # [[gateway.tx_limits.dwell_time]]
+    #   frequency_min=923000000
+    #   frequency_max=923600000
+    #   max_dwell_time="400ms"
+{{#each gateway.tx_limits.dwell_time}}
+    [[gateway.tx_limits.dwell_time]]
+      frequency_min={{ this.frequency_min }}
+      frequency_max={{ this.frequency_max }}
+      max_dwell_time="{{ this.max_dwell_time }}"
+{{/each}}
+
+
   # Static gateway location.
   [gateway.location]
 
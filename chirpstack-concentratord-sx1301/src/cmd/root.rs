@@ -5,7 +5,9 @@ use std::thread;
 
 use libconcentratord::signals;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, events, jitqueue, reset};
+use libconcentratord::{
+    capture, commands, dutycycle, events, filters, jitqueue, reset, simulator, testtx,
+};
 use libloragw_sx1301::hal;
 
 use super::super::{concentrator, config, handler, wrapper};
@@ -21,16 +23,8 @@ pub fn run(
         "https://www.chirpstack.io/concentratord/"
     );
 
-    // reset concentrator
-    reset::reset().expect("concentrator reset failed");
-
-    // setup concentrator
-    concentrator::set_spidev_path(&config)?;
-    concentrator::board_setconf(&config)?;
-    concentrator::txgain_setconf(&config)?;
-    concentrator::rxrf_setconf(&config)?;
-    concentrator::rxif_setconf(&config)?;
-    concentrator::start(&config)?;
+    let simulator = config.gateway.model == "simulator";
+    setup_concentrator(&config)?;
 
     // setup static location
     handler::gps::set_static_gps_coords(
@@ -45,9 +39,39 @@ pub fn run(
         .expect("bind command socket error");
 
     // setup jit queue
-    let queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::new(32);
+    let jit_config = config.gateway.model_config.jit.merge(&config.gateway.jit);
+    let mut queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::from_config(&jit_config);
+    if config.gateway.duty_cycle.enable {
+        queue.set_duty_cycle(dutycycle::DutyCycle::new(
+            config.gateway.duty_cycle.window,
+            config
+                .gateway
+                .duty_cycle
+                .bands
+                .iter()
+                .map(|b| dutycycle::Band {
+                    name: b.name.clone(),
+                    frequency_min: b.frequency_min,
+                    frequency_max: b.frequency_max,
+                    duty_cycle: b.duty_cycle,
+                })
+                .collect(),
+        ));
+    }
     let queue = Arc::new(Mutex::new(queue));
 
+    // setup uplink filters
+    filters::setup(&config.concentratord.filters)?;
+
+    // setup packet capture
+    capture::setup(
+        &config.concentratord.capture,
+        match config.gateway.lorawan_public {
+            true => 0x34,
+            false => 0x12,
+        },
+    )?;
+
     // setup threads
     let mut signal_pool = signals::SignalPool::new();
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
@@ -56,9 +80,10 @@ pub fn run(
     threads.push(thread::spawn({
         let stop_receive = signal_pool.new_receiver();
         let gateway_id = config.gateway.gateway_id_bytes.clone();
+        let board = config.gateway.board_index;
 
         move || {
-            handler::uplink::handle_loop(&gateway_id, stop_receive);
+            handler::uplink::handle_loop(&gateway_id, board, stop_receive);
         }
     }));
 
@@ -73,19 +98,23 @@ pub fn run(
 
     // jit thread
     threads.push(thread::spawn({
+        let gateway_id = config.gateway.gateway_id_bytes.clone();
         let queue = Arc::clone(&queue);
         let stop_receive = signal_pool.new_receiver();
         let antenna_gain = config.gateway.antenna_gain;
+        let calibrate = jit_config.calibrate;
 
         move || {
-            handler::jit::jit_loop(queue, antenna_gain, stop_receive);
+            handler::jit::jit_loop(&gateway_id, queue, antenna_gain, calibrate, stop_receive);
         }
     }));
 
     // gateway command thread
     threads.push(thread::spawn({
         let vendor_config = config.gateway.model_config.clone();
+        let tx_limits = config.gateway.tx_limits.clone();
         let gateway_id = config.gateway.gateway_id_bytes.clone();
+        let board = config.gateway.board_index;
         let queue = Arc::clone(&queue);
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
@@ -93,7 +122,9 @@ pub fn run(
         move || {
             handler::command::handle_loop(
                 &vendor_config,
+                &tx_limits,
                 &gateway_id,
+                board,
                 queue,
                 rep_sock,
                 stop_receive,
@@ -106,6 +137,7 @@ pub fn run(
     threads.push(thread::spawn({
         let gateway_id = config.gateway.gateway_id_bytes.clone();
         let stats_interval = config.concentratord.stats_interval;
+        let queue = Arc::clone(&queue);
         let stop_receive = signal_pool.new_receiver();
         let mut metadata = HashMap::new();
         metadata.insert(
@@ -120,11 +152,20 @@ pub fn run(
         metadata.insert("hal_version".to_string(), hal::version_info());
 
         move || {
-            handler::stats::stats_loop(&gateway_id, &stats_interval, stop_receive, &metadata);
+            handler::stats::stats_loop(&gateway_id, &stats_interval, queue, stop_receive, metadata);
         }
     }));
 
-    if config.gateway.model_config.gps_tty_path.is_some() {
+    if simulator {
+        // gps simulator thread
+        threads.push(thread::spawn({
+            let stop_receive = signal_pool.new_receiver();
+
+            move || {
+                handler::gps::gps_simulator_loop(stop_receive);
+            }
+        }));
+    } else if config.gateway.model_config.gps_tty_path.is_some() {
         // gps thread
         threads.push(thread::spawn({
             let gps_tty_path = config
@@ -140,7 +181,9 @@ pub fn run(
                 handler::gps::gps_loop(&gps_tty_path, stop_receive);
             }
         }));
+    }
 
+    if simulator || config.gateway.model_config.gps_tty_path.is_some() {
         // gps validate thread
         threads.push(thread::spawn({
             let stop_receive = signal_pool.new_receiver();
@@ -172,7 +215,35 @@ pub fn run(
         t.join().unwrap();
     }
 
-    concentrator::stop(&config)?;
+    if testtx::is_active() {
+        testtx::stop()?;
+    }
+
+    if !simulator {
+        concentrator::stop(&config)?;
+    }
 
     return Ok(stop_signal);
 }
+
+/// Reset and start the (simulated) concentrator.
+pub fn setup_concentrator(config: &config::Configuration) -> Result<(), String> {
+    if config.gateway.model == "simulator" {
+        // setup simulated concentrator
+        simulator::start(&config.gateway.simulator.uplink_script)?;
+        concentrator::timestamp::start(&config);
+    } else {
+        // reset concentrator
+        reset::reset().expect("concentrator reset failed");
+
+        // setup concentrator
+        concentrator::set_spidev_path(&config)?;
+        concentrator::board_setconf(&config)?;
+        concentrator::txgain_setconf(&config)?;
+        concentrator::rxrf_setconf(&config)?;
+        concentrator::rxif_setconf(&config)?;
+        concentrator::start(&config)?;
+    }
+
+    return Ok(());
+}
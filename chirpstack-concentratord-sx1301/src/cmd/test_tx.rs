@@ -0,0 +1,98 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use clap::ArgMatches;
+use libconcentratord::{proto, testtx};
+use signal_hook::consts::signal::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use super::super::{concentrator, config, handler};
+use super::root;
+
+/// Run a test transmission with the parameters of the test-tx subcommand,
+/// until the duration has passed or until SIGINT / SIGTERM is received. The
+/// Concentratord must not be running. In case of multiple boards, the board
+/// must already be selected. The returned value is the process exit code.
+pub fn run(config: &config::Configuration, matches: &ArgMatches) -> i32 {
+    let pl = match get_test_tx(matches) {
+        Ok(v) => v,
+        Err(err) => {
+            error!("Invalid test transmission parameters, error: {}", err);
+            return 1;
+        }
+    };
+
+    if let Err(err) = test_tx(config, &pl) {
+        error!("Test transmission error, error: {}", err);
+        return 1;
+    }
+
+    return 0;
+}
+
+fn test_tx(config: &config::Configuration, pl: &proto::TestTx) -> Result<(), String> {
+    info!(
+        "Starting Concentratord SX1301 test transmission (version: {})",
+        config::VERSION
+    );
+
+    root::setup_concentrator(config)?;
+
+    let res = handler::test_tx::start(&config.gateway.model_config, pl);
+    if res.is_ok() {
+        let mut signals = Signals::new(&[SIGINT, SIGTERM]).map_err(|e| e.to_string())?;
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                warn!("Received stop signal, stopping test transmission");
+                if let Err(err) = testtx::stop() {
+                    error!("Stop test transmission error, error: {}", err);
+                }
+            }
+        });
+
+        while testtx::is_active() {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    if config.gateway.model != "simulator" {
+        concentrator::stop(&config)?;
+    }
+
+    return res;
+}
+
+fn get_test_tx(matches: &ArgMatches) -> Result<proto::TestTx, String> {
+    let duration: u64 = parse(matches, "duration")?;
+    let interval: u64 = parse(matches, "interval")?;
+
+    Ok(proto::TestTx {
+        frequency: parse(matches, "frequency")?,
+        power: parse(matches, "power")?,
+        duration: Some(prost_types::Duration {
+            seconds: duration as i64,
+            nanos: 0,
+        }),
+        cw: matches.is_present("cw"),
+        bandwidth: parse(matches, "bandwidth")?,
+        spreading_factor: parse(matches, "spreading-factor")?,
+        payload_size: parse(matches, "payload-size")?,
+        interval: Some(prost_types::Duration {
+            seconds: (interval / 1000) as i64,
+            nanos: ((interval % 1000) * 1_000_000) as i32,
+        }),
+        board: parse(matches, "board")?,
+    })
+}
+
+fn parse<T>(matches: &ArgMatches, name: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let v = matches.value_of(name).unwrap_or_default();
+    v.parse()
+        .map_err(|e| format!("invalid {}: {}, error: {}", name, v, e))
+}
@@ -0,0 +1,170 @@
+use super::super::config::{self, helpers};
+
+/// Validate the given configuration files without accessing the concentrator
+/// hardware. Every problem is printed with its location. The returned value
+/// is the process exit code.
+pub fn run(filenames: &[String]) -> i32 {
+    let config = match config::try_get(filenames) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("{}", err);
+            return 1;
+        }
+    };
+
+    let mut errors: Vec<(String, String)> = Vec::new();
+    if config.gateway.board.is_empty() {
+        errors = validate(&config);
+    } else {
+        // every board is validated as it would run in the board process
+        for board in 0..config.gateway.board.len() {
+            let mut board_config = match config::try_get(filenames) {
+                Ok(v) => v,
+                Err(err) => {
+                    println!("{}", err);
+                    return 1;
+                }
+            };
+            if let Err(err) = config::select_board(&mut board_config, board) {
+                println!("{}", err);
+                return 1;
+            }
+
+            let prefix = format!("gateway.board[{}].concentrator", board);
+            for (location, err) in validate(&board_config) {
+                errors.push((location.replacen("gateway.concentrator", &prefix, 1), err));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        println!("Configuration is valid");
+        return 0;
+    }
+
+    for (location, err) in &errors {
+        println!("{}: {}", location, err);
+    }
+
+    return 1;
+}
+
+fn validate(config: &config::Configuration) -> Vec<(String, String)> {
+    let mut errors: Vec<(String, String)> = Vec::new();
+    let gps =
+        config.gateway.model == "simulator" || config.gateway.model_config.gps_tty_path.is_some();
+    let concentrator = &config.gateway.concentrator;
+
+    // LoRa Std channel
+    let lora_std = &concentrator.lora_std;
+    if lora_std.frequency != 0 {
+        if ![125000, 250000, 500000].contains(&lora_std.bandwidth) {
+            errors.push((
+                "gateway.concentrator.lora_std.bandwidth".to_string(),
+                format!("invalid bandwidth: {}", lora_std.bandwidth),
+            ));
+        }
+
+        if lora_std.spreading_factor < 7 || lora_std.spreading_factor > 12 {
+            errors.push((
+                "gateway.concentrator.lora_std.spreading_factor".to_string(),
+                format!("invalid spreading_factor: {}", lora_std.spreading_factor),
+            ));
+        }
+    }
+
+    // FSK channel
+    if concentrator.fsk.frequency != 0 && concentrator.fsk.datarate == 0 {
+        errors.push((
+            "gateway.concentrator.fsk.datarate".to_string(),
+            "datarate must be set".to_string(),
+        ));
+    }
+
+    // radio frequencies and IF placement
+    match helpers::get_radio_frequencies(config) {
+        Ok(radio_freqs) => {
+            for (i, freq) in concentrator.multi_sf_channels.iter().enumerate() {
+                if *freq == 0 {
+                    continue;
+                }
+
+                if let Err(err) = helpers::get_radio_for_channel(
+                    &radio_freqs,
+                    *freq,
+                    config.gateway.model_config.lora_multi_sf_bandwidth,
+                ) {
+                    errors.push((
+                        format!("gateway.concentrator.multi_sf_channels[{}]", i),
+                        err,
+                    ));
+                }
+            }
+
+            if lora_std.frequency != 0 {
+                if let Err(err) = helpers::get_radio_for_channel(
+                    &radio_freqs,
+                    lora_std.frequency,
+                    lora_std.bandwidth,
+                ) {
+                    errors.push(("gateway.concentrator.lora_std.frequency".to_string(), err));
+                }
+            }
+
+            if concentrator.fsk.frequency != 0 {
+                if let Err(err) = helpers::get_radio_for_channel(
+                    &radio_freqs,
+                    concentrator.fsk.frequency,
+                    concentrator.fsk.bandwidth,
+                ) {
+                    errors.push(("gateway.concentrator.fsk.frequency".to_string(), err));
+                }
+            }
+        }
+        Err(err) => errors.push(("gateway.concentrator".to_string(), err)),
+    }
+
+    // beacon
+    let beacon = &config.gateway.beacon;
+    if !beacon.frequencies.is_empty() {
+        if !gps {
+            errors.push((
+                "gateway.beacon".to_string(),
+                "beaconing requires a gateway model with GPS".to_string(),
+            ));
+        }
+
+        if beacon.spreading_factor < 7 || beacon.spreading_factor > 12 {
+            errors.push((
+                "gateway.beacon.spreading_factor".to_string(),
+                format!("invalid spreading_factor: {}", beacon.spreading_factor),
+            ));
+        }
+
+        if ![125000, 250000, 500000].contains(&beacon.bandwidth) {
+            errors.push((
+                "gateway.beacon.bandwidth".to_string(),
+                format!("invalid bandwidth: {}", beacon.bandwidth),
+            ));
+        }
+
+        // beacons are always sent using the first radio
+        if let Some((tx_freq_min, tx_freq_max)) =
+            config.gateway.model_config.radio_min_max_tx_freq.first()
+        {
+            for (i, freq) in beacon.frequencies.iter().enumerate() {
+                if freq < tx_freq_min || freq > tx_freq_max {
+                    errors.push((
+                        format!("gateway.beacon.frequencies[{}]", i),
+                        format!(
+                            "frequency {} is not within min/max tx frequency ({} - {})",
+                            freq, tx_freq_min, tx_freq_max
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    errors
+}
@@ -1,3 +1,4 @@
+use libconcentratord::simulator;
 use libloragw_sx1301::{hal, spi};
 
 pub mod timestamp;
@@ -168,3 +169,67 @@ pub fn stop(_config: &Configuration) -> Result<(), String> {
 
     return Ok(());
 }
+
+pub fn receive() -> Result<Vec<hal::RxPacket>, String> {
+    if !simulator::enabled() {
+        return hal::receive();
+    }
+
+    let mut packets: Vec<hal::RxPacket> = Vec::new();
+    for uplink in simulator::receive()? {
+        let mut payload: [u8; 256] = [0; 256];
+        let size = uplink.phy_payload.len().min(payload.len());
+        payload[..size].copy_from_slice(&uplink.phy_payload[..size]);
+
+        packets.push(hal::RxPacket {
+            freq_hz: uplink.frequency,
+            if_chain: 0,
+            status: hal::CRC::CRCOk,
+            count_us: uplink.count_us,
+            rf_chain: 0,
+            modulation: hal::Modulation::LoRa,
+            bandwidth: uplink.bandwidth,
+            datarate: match uplink.spreading_factor {
+                7 => hal::DataRate::SF7,
+                8 => hal::DataRate::SF8,
+                9 => hal::DataRate::SF9,
+                10 => hal::DataRate::SF10,
+                11 => hal::DataRate::SF11,
+                12 => hal::DataRate::SF12,
+                _ => return Err("invalid simulated spreading_factor".to_string()),
+            },
+            coderate: hal::CodeRate::LoRa4_5,
+            rssi: uplink.rssi as f32,
+            snr: uplink.snr,
+            snr_min: uplink.snr,
+            snr_max: uplink.snr,
+            crc: 0,
+            size: size as u16,
+            payload: payload,
+        });
+    }
+
+    return Ok(packets);
+}
+
+pub fn send(tx_packet: &hal::TxPacket) -> Result<(), String> {
+    if simulator::enabled() {
+        return simulator::send(tx_packet.count_us, tx_packet.freq_hz, tx_packet.size);
+    }
+
+    return hal::send(tx_packet);
+}
+
+pub fn get_tx_status() -> Result<hal::TxStatus, String> {
+    if simulator::enabled() {
+        return match simulator::is_emitting()? {
+            true => Ok(hal::TxStatus::Emitting),
+            false => Ok(hal::TxStatus::Scheduled),
+        };
+    }
+
+    match hal::status(hal::StatusSelect::Tx)? {
+        hal::StatusReturn::Tx(v) => Ok(v),
+        _ => Err("unexpected status type".to_string()),
+    }
+}
@@ -1,8 +1,13 @@
-use std::fs;
+use std::process;
 
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use libconcentratord::boards;
+use libconcentratord::config::Error;
+use libconcentratord::{capture, filters, jitqueue};
+use libconcentratord::vendor::Model;
+
 pub mod timestamp;
 pub mod helpers;
 pub mod vendor;
@@ -43,6 +48,33 @@ pub struct Beacon {
     pub tx_power: u32,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DutyCycle {
+    pub enable: bool,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    #[serde(default)]
+    pub bands: Vec<DutyCycleBand>,
+}
+
+impl Default for DutyCycle {
+    fn default() -> Self {
+        DutyCycle {
+            enable: false,
+            window: Duration::from_secs(3600),
+            bands: vec![],
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct DutyCycleBand {
+    pub name: String,
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    pub duty_cycle: f32,
+}
+
 #[derive(Default, Serialize, Deserialize, Clone)]
 pub struct Location {
     pub latitude: f64,
@@ -50,6 +82,46 @@ pub struct Location {
     pub altitude: i16,
 }
 
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct TxLimits {
+    #[serde(default)]
+    pub max_eirp: i8,
+    #[serde(default)]
+    pub clamp_eirp: bool,
+    #[serde(default)]
+    pub dwell_time: Vec<DwellTime>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DwellTime {
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    #[serde(with = "humantime_serde")]
+    pub max_dwell_time: Duration,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Simulator {
+    pub uplink_script: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Board {
+    pub model: String,
+    #[serde(default)]
+    pub model_flags: Vec<String>,
+    #[serde(default)]
+    pub reset_pin: u32,
+    #[serde(default)]
+    pub antenna_gain: i8,
+    pub concentrator: Concentrator,
+    #[serde(default)]
+    pub jit: jitqueue::Configuration,
+
+    #[serde(skip)]
+    pub model_config: vendor::Configuration,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct Gateway {
     #[serde(default)]
@@ -60,6 +132,8 @@ pub struct Gateway {
     #[serde(default)]
     pub model_flags: Vec<String>,
     #[serde(default)]
+    pub model_dir: String,
+    #[serde(default)]
     pub reset_pin: u32,
     pub gateway_id: String,
     #[serde(default)]
@@ -70,11 +144,23 @@ pub struct Gateway {
     #[serde(default)]
     pub beacon: Beacon,
     #[serde(default)]
+    pub duty_cycle: DutyCycle,
+    #[serde(default)]
     pub location: Location,
+    #[serde(default)]
+    pub tx_limits: TxLimits,
+    #[serde(default)]
+    pub simulator: Simulator,
+    #[serde(default)]
+    pub jit: jitqueue::Configuration,
+    #[serde(default)]
+    pub board: Vec<Board>,
 
     #[serde(skip)]
     pub gateway_id_bytes: Vec<u8>,
     #[serde(skip)]
+    pub board_index: u32,
+    #[serde(skip)]
     pub model_config: vendor::Configuration,
     #[serde(skip)]
     pub config_version: String,
@@ -88,12 +174,29 @@ pub struct Concentratord {
     #[serde(with = "humantime_serde")]
     pub stats_interval: Duration,
     pub api: API,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub state_file: String,
+    #[serde(default)]
+    pub filters: filters::Configuration,
+    #[serde(default)]
+    pub capture: capture::Configuration,
 }
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct API {
     pub event_bind: String,
     pub command_bind: String,
+    #[serde(default)]
+    pub curve_secret_key: String,
+    #[serde(default)]
+    pub curve_allowed_clients: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Metrics {
+    pub bind: String,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -149,66 +252,160 @@ fn example_configuration() -> Configuration {
 }
 
 pub fn get(filenames: Vec<String>) -> Configuration {
-    let mut content: String = String::new();
-
-    for file_name in &filenames {
-        content.push_str(&fs::read_to_string(&file_name).expect("Error reading config file"));
+    match try_get(&filenames) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Load configuration error, error: {}", err);
+            process::exit(1);
+        }
     }
+}
 
-    let mut config: Configuration = toml::from_str(&content).expect("Error parsing config file");
+/// Read and validate the given configuration files.
+pub fn try_get(filenames: &[String]) -> Result<Configuration, Error> {
+    let mut config: Configuration = libconcentratord::config::parse(filenames)?;
+
+    // validate CURVE keys
+    let api = &config.concentratord.api;
+    if !api.curve_secret_key.is_empty() {
+        libconcentratord::curve::decode_key(&api.curve_secret_key).map_err(|e| Error::Value {
+            location: "concentratord.api.curve_secret_key".to_string(),
+            error: e,
+        })?;
+    }
+    for (i, key) in api.curve_allowed_clients.iter().enumerate() {
+        libconcentratord::curve::decode_key(key).map_err(|e| Error::Value {
+            location: format!("concentratord.api.curve_allowed_clients[{}]", i),
+            error: e,
+        })?;
+    }
 
     // decode gateway id
-    let bytes = hex::decode(&config.gateway.gateway_id).expect("Could not decode gateway_id");
+    let bytes = hex::decode(&config.gateway.gateway_id).map_err(|e| Error::Value {
+        location: "gateway.gateway_id".to_string(),
+        error: format!("decode error: {}", e),
+    })?;
     if bytes.len() != 8 {
-        panic!("gateway_id must be exactly 8 bytes");
+        return Err(Error::Value {
+            location: "gateway.gateway_id".to_string(),
+            error: "gateway_id must be exactly 8 bytes".to_string(),
+        });
     }
     config.gateway.gateway_id_bytes = bytes;
 
+    // validate uplink filters
+    filters::Filters::new(&config.concentratord.filters).map_err(|e| Error::Value {
+        location: "concentratord.filters".to_string(),
+        error: e,
+    })?;
+
     // get model configuration
-    config.gateway.model_config = match config.gateway.model.as_ref() {
-        "generic_as923" => vendor::generic::as923::new(&config),
-        "generic_au915" => vendor::generic::au915::new(&config),
-        "generic_cn470" => vendor::generic::cn470::new(&config),
-        "generic_eu868" => vendor::generic::eu868::new(&config),
-        "generic_in865" => vendor::generic::in865::new(&config),
-        "generic_kr920" => vendor::generic::kr920::new(&config),
-        "generic_ru864" => vendor::generic::ru864::new(&config),
-        "generic_us915" => vendor::generic::us915::new(&config),
-        "imst_ic880a_eu868" => vendor::imst::ic880a_eu868::new(&config),
-        "kerlink_ifemtocell_eu868" => vendor::kerlink::ifemtocell_eu868::new(),
-        "multitech_mtac_lora_h_868_eu868" => vendor::multitech::mtac_lora_h_868_eu868::new(&config),
-        "multitech_mtac_lora_h_915_us915" => vendor::multitech::mtac_lora_h_915_us915::new(&config),
-        "multitech_mtcap_lora_868_eu868" => vendor::multitech::mtcap_lora_868_eu868::new(),
-        "multitech_mtcap_lora_915_us915" => vendor::multitech::mtcap_lora_915_us915::new(),
-        "pi_supply_lora_gateway_hat_eu868" => {
-            vendor::pi_supply::lora_gateway_hat_eu868::new(&config)
+    let models = vendor::get_models(&config.gateway.model_dir).map_err(|e| Error::Value {
+        location: "gateway.model_dir".to_string(),
+        error: e,
+    })?;
+
+    if config.gateway.board.is_empty() {
+        config.gateway.model_config = get_model_config(&config, &models, "gateway")?;
+    } else {
+        for i in 0..config.gateway.board.len() {
+            let mut board_config: Configuration = Default::default();
+            board_config.gateway.model = config.gateway.board[i].model.clone();
+            board_config.gateway.model_flags = config.gateway.board[i].model_flags.clone();
+            board_config.gateway.reset_pin = config.gateway.board[i].reset_pin;
+            board_config.gateway.antenna_gain = config.gateway.board[i].antenna_gain;
+
+            config.gateway.board[i].model_config =
+                get_model_config(&board_config, &models, &format!("gateway.board[{}]", i))?;
         }
-        "pi_supply_lora_gateway_hat_us915" => {
-            vendor::pi_supply::lora_gateway_hat_us915::new(&config)
+    }
+
+    debug!("Antenna gain {} dBi", config.gateway.antenna_gain);
+
+    return Ok(config);
+}
+
+/// Configure the gateway as the given board (see gateway.board). The API
+/// sockets are bound to the internal endpoints of the board process.
+pub fn select_board(config: &mut Configuration, board: usize) -> Result<(), Error> {
+    if board >= config.gateway.board.len() {
+        return Err(Error::Value {
+            location: "gateway.board".to_string(),
+            error: format!(
+                "board {} is not configured, boards: {}",
+                board,
+                config.gateway.board.len()
+            ),
+        });
+    }
+
+    let boards = std::mem::replace(&mut config.gateway.board, vec![]);
+    let b = boards.into_iter().nth(board).unwrap();
+
+    config.gateway.board_index = board as u32;
+    config.gateway.model = b.model;
+    config.gateway.model_flags = b.model_flags;
+    config.gateway.reset_pin = b.reset_pin;
+    config.gateway.antenna_gain = b.antenna_gain;
+    config.gateway.concentrator = b.concentrator;
+    config.gateway.model_config = b.model_config;
+    config.gateway.jit = config.gateway.jit.merge(&b.jit);
+
+    // only the first board sends beacons
+    if board != 0 {
+        config.gateway.beacon.frequencies = vec![];
+    }
+
+    let (event_bind, command_bind) = boards::get_board_endpoints(board);
+    config.concentratord.api.event_bind = event_bind;
+    config.concentratord.api.command_bind = command_bind;
+    config.concentratord.api.curve_secret_key = "".to_string();
+    config.concentratord.api.curve_allowed_clients = vec![];
+    config.concentratord.metrics.bind = "".to_string();
+    config.concentratord.state_file = "".to_string();
+    config.concentratord.capture.file =
+        capture::get_board_file(&config.concentratord.capture.file, board);
+
+    return Ok(());
+}
+
+// Returns the model configuration for the model, model_flags, antenna_gain
+// and reset_pin of the given configuration. The location is used as prefix
+// for error locations.
+fn get_model_config(
+    config: &Configuration,
+    models: &[Model],
+    location: &str,
+) -> Result<vendor::Configuration, Error> {
+    let model = match models.iter().find(|m| m.name == config.gateway.model) {
+        Some(v) => v,
+        None => {
+            return Err(Error::Value {
+                location: format!("{}.model", location),
+                error: format!(
+                    "unknown gateway model: {}, see the models subcommand for the supported models",
+                    config.gateway.model
+                ),
+            })
         }
-        "rak_2245_as923" => vendor::rak::rak2245_as923::new(&config),
-        "rak_2245_au915" => vendor::rak::rak2245_au915::new(&config),
-        "rak_2245_cn470" => vendor::rak::rak2245_cn470::new(&config),
-        "rak_2245_eu433" => vendor::rak::rak2245_eu433::new(&config),
-        "rak_2245_eu868" => vendor::rak::rak2245_eu868::new(&config),
-        "rak_2245_in865" => vendor::rak::rak2245_in865::new(&config),
-        "rak_2245_kr920" => vendor::rak::rak2245_kr920::new(&config),
-        "rak_2245_ru864" => vendor::rak::rak2245_ru864::new(&config),
-        "rak_2245_us915" => vendor::rak::rak2245_us915::new(&config),
-        "rak_2246_as923" => vendor::rak::rak2246_as923::new(&config),
-        "rak_2246_au915" => vendor::rak::rak2246_au915::new(&config),
-        "rak_2246_eu868" => vendor::rak::rak2246_eu868::new(&config),
-        "rak_2246_in865" => vendor::rak::rak2246_in865::new(&config),
-        "rak_2246_kr920" => vendor::rak::rak2246_kr920::new(&config),
-        "rak_2246_ru864" => vendor::rak::rak2246_ru864::new(&config),
-        "rak_2246_us915" => vendor::rak::rak2246_us915::new(&config),
-        "sandbox_lorago_port_eu868" => vendor::sandbox::lorago_port_eu868::new(&config),
-        "sandbox_lorago_port_us915" => vendor::sandbox::lorago_port_us915::new(&config),
-        "wifx_lorix_one_eu868" => vendor::wifx::lorix_one_eu868::new(&config),
-        _ => panic!("unexpected gateway model: {}", config.gateway.model),
     };
 
-    debug!("Antenna gain {} dBi", config.gateway.antenna_gain);
+    for flag in &config.gateway.model_flags {
+        if !model.flags.contains(flag) {
+            return Err(Error::Value {
+                location: format!("{}.model_flags", location),
+                error: format!(
+                    "unknown flag: {}, accepted flags for {}: [{}]",
+                    flag,
+                    model.name,
+                    model.flags.join(", ")
+                ),
+            });
+        }
+    }
 
-    return config;
+    vendor::new(config, model).map_err(|e| Error::Value {
+        location: format!("{}.model", location),
+        error: e,
+    })
 }
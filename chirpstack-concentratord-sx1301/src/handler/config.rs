@@ -1,4 +1,6 @@
-use super::super::config::{Concentrator, Configuration};
+use std::mem;
+
+use super::super::config::{helpers, Concentrator, Configuration};
 
 pub fn update_configuration(
     config: &mut Configuration,
@@ -41,16 +43,21 @@ pub fn update_configuration(
         };
     }
 
-    // set config
+    // set config, the previous concentrator config is restored when the
+    // channels do not fit within the bandwidth of the radios
+    let previous = mem::replace(&mut config.gateway.concentrator, concentrator);
+    if let Err(err) = helpers::validate_channels(config) {
+        config.gateway.concentrator = previous;
+        return Err(err);
+    }
     config.gateway.config_version = new_config.version.clone();
-    config.gateway.concentrator = concentrator;
 
     return Ok(());
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::super::config::{FSKChannel, LoRaStdChannel};
+    use super::super::super::config::{vendor, FSKChannel, LoRaStdChannel};
     use super::{update_configuration, Concentrator, Configuration};
 
     #[test]
@@ -208,10 +215,47 @@ mod tests {
             ),
         ];
 
+        let models = vendor::get_models("").unwrap();
+        let model = models.iter().find(|m| m.name == "generic_eu868").unwrap();
+
         for (_, gw_config, expected_config) in tests {
             let mut config = Configuration::default();
+            config.gateway.model_config = vendor::new(&config, model).unwrap();
             update_configuration(&mut config, &gw_config).unwrap();
             assert_eq!(config.gateway.concentrator, expected_config);
         }
     }
+
+    #[test]
+    fn test_update_configuration_invalid_channels() {
+        let models = vendor::get_models("").unwrap();
+        let model = models.iter().find(|m| m.name == "generic_eu868").unwrap();
+
+        let mut config = Configuration::default();
+        config.gateway.model_config = vendor::new(&config, model).unwrap();
+        config.gateway.config_version = "1".to_string();
+        config.gateway.concentrator.multi_sf_channels[0] = 868100000;
+
+        // the channels do not fit within the bandwidth of the two radios
+        let gw_config = chirpstack_api::gw::GatewayConfiguration {
+            version: "2".to_string(),
+            channels: [863100000, 866100000, 869100000]
+                .iter()
+                .map(|f| chirpstack_api::gw::ChannelConfiguration {
+                    frequency: *f,
+                    modulation: chirpstack_api::common::Modulation::Lora as i32,
+                    modulation_config: Some(chirpstack_api::gw::channel_configuration::ModulationConfig::LoraModulationConfig(chirpstack_api::gw::LoRaModulationConfig {
+                        bandwidth: 125000,
+                        spreading_factors: vec![7, 8, 9, 10, 11, 12],
+                    })),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(update_configuration(&mut config, &gw_config).is_err());
+        assert_eq!("1", config.gateway.config_version);
+        assert_eq!(868100000, config.gateway.concentrator.multi_sf_channels[0]);
+    }
 }
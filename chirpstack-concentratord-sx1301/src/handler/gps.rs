@@ -7,6 +7,7 @@ use chrono::offset::Utc;
 use chrono::DateTime;
 
 use libconcentratord::signals::Signal;
+use libconcentratord::{metrics, simulator};
 use libloragw_sx1301::{gps, hal};
 
 lazy_static! {
@@ -127,6 +128,41 @@ pub fn gps_loop(gps_tty_path: &str, stop_receive: Receiver<Signal>) {
     debug!("GPS loop ended");
 }
 
+pub fn gps_simulator_loop(stop_receive: Receiver<Signal>) {
+    debug!("Starting GPS simulator loop");
+
+    loop {
+        // Instead of a 1s sleep, we receive from the stop channel with a
+        // timeout of 1 second.
+        match stop_receive.recv_timeout(Duration::from_secs(1)) {
+            Ok(v) => {
+                debug!("Received stop signal, signal: {}", v);
+                break;
+            }
+            _ => {}
+        };
+
+        let (count_us, system_time, gps_epoch) = match simulator::get_gps_time() {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Get simulated GPS time error, error: {}", err);
+                continue;
+            }
+        };
+
+        let mut time_reference = GPS_TIME_REF.lock().unwrap();
+        *time_reference = gps::TimeReference {
+            system_time: system_time,
+            count_us: count_us,
+            gps_time: system_time,
+            gps_epoch: gps_epoch,
+            xtal_err: 1.0,
+        };
+    }
+
+    debug!("GPS simulator loop ended");
+}
+
 pub fn gps_validate_loop(stop_receive: Receiver<Signal>) {
     info!("Starting GPS validation loop");
 
@@ -207,6 +243,9 @@ pub fn gps_validate_loop(stop_receive: Receiver<Signal>) {
                     );
                 }
             }
+
+            metrics::set_gps_locked(*gps_ref_valid);
+            metrics::set_xtal_correct(*xtal_correct);
         }
     }
 
@@ -1,22 +1,48 @@
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use libconcentratord::jitqueue::TxPacket;
 use libconcentratord::signals::Signal;
-use libconcentratord::{jitqueue, stats};
+use libconcentratord::{capture, events, jitqueue, proto, stats, testtx};
 use libloragw_sx1301::hal;
+use uuid::Uuid;
 
-use super::super::wrapper;
+use super::super::{concentrator, wrapper};
+
+// TX status polling interval and timeout (after the scheduled start of the
+// emission) in calibration mode.
+const CALIBRATION_POLL_INTERVAL: Duration = Duration::from_micros(100);
+const CALIBRATION_TIMEOUT: Duration = Duration::from_millis(100);
+
+// TX latency measurement, handled by the calibration thread.
+struct TxMeasurement {
+    downlink_id: Uuid,
+    tx_start: Instant,
+    send_duration: Duration,
+}
 use super::timersync;
 
 pub fn jit_loop(
+    gateway_id: &[u8],
     queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
     antenna_gain: i8,
+    calibrate: bool,
     stop_receive: Receiver<Signal>,
 ) {
     debug!("Starting JIT queue loop");
 
+    // The TX status is polled by a separate thread, so that the calibration
+    // does not delay the next packets in the queue.
+    let calibration_tx = match calibrate {
+        true => {
+            let (calibration_tx, calibration_rx) = sync_channel(0);
+            thread::spawn(move || calibration_loop(calibration_rx));
+            Some(calibration_tx)
+        }
+        false => None,
+    };
+
     loop {
         // Instead of a 10ms sleep, we receive from the stop channel with a
         // timeout of 10ms.
@@ -28,17 +54,26 @@ pub fn jit_loop(
             _ => {}
         };
 
-        let tx_packet = match get_tx_packet(&queue) {
+        let packet = match get_tx_packet(gateway_id, &queue) {
             Some(v) => v,
             None => continue,
         };
 
-        let downlink_id = tx_packet.get_id();
-        let mut tx_packet = tx_packet.tx_packet();
+        let downlink_id = packet.id();
+        let mut tx_packet = packet.tx_packet();
         tx_packet.rf_power = tx_packet.rf_power - antenna_gain;
 
-        match hal::send(&tx_packet) {
+        // The time and concentrator counter before sending the packet, used
+        // for calibration.
+        let send_start = match calibrate {
+            true => Some((Instant::now(), timersync::get_concentrator_count())),
+            false => None,
+        };
+
+        match concentrator::send(&tx_packet) {
             Ok(_) => {
+                let send_end = Instant::now();
+
                 info!(
                     "Scheduled packet for TX, downlink_id: {}, count_us: {}, freq: {}, bw: {}, mod: {:?}, dr: {:?}",
                     downlink_id,
@@ -50,9 +85,36 @@ pub fn jit_loop(
                 );
 
                 stats::inc_tx_packets_emitted();
+                if let Err(err) =
+                    capture::downlink(&wrapper::downlink_to_capture(gateway_id, &tx_packet))
+                {
+                    error!(
+                        "Capture downlink error, downlink_id: {}, error: {}",
+                        downlink_id, err
+                    );
+                }
+                send_tx_ack(gateway_id, &downlink_id, proto::TxAckStatus::Ok);
+
+                if let Some((send_time, send_count_us)) = send_start {
+                    start_tx_measurement(
+                        &calibration_tx,
+                        &downlink_id,
+                        &tx_packet,
+                        send_time,
+                        send_count_us,
+                        send_end - send_time,
+                    );
+                }
             }
             Err(err) => {
-                error!("Schedule packet for tx error, error: {}", err);
+                // the packet was not transmitted
+                queue.lock().unwrap().release(&packet);
+
+                error!(
+                    "Schedule packet for tx error, downlink_id: {}, error: {}",
+                    downlink_id, err
+                );
+                send_tx_ack(gateway_id, &downlink_id, proto::TxAckStatus::InternalError);
             }
         }
     }
@@ -61,10 +123,141 @@ pub fn jit_loop(
 }
 
 fn get_tx_packet(
+    gateway_id: &[u8],
     queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
 ) -> Option<wrapper::TxPacket> {
     let concentrator_count = timersync::get_concentrator_count();
     let mut queue = queue.lock().unwrap();
 
+    for tx_packet in queue.pop_expired(concentrator_count) {
+        send_tx_ack(gateway_id, &tx_packet.id(), proto::TxAckStatus::TooLate);
+    }
+
+    for (tx_packet, status) in queue.pop_evicted() {
+        send_tx_ack(gateway_id, &tx_packet.id(), status);
+    }
+
+    // The JIT queue is paused during a test transmission.
+    if testtx::is_active() {
+        return None;
+    }
+
     return queue.pop(concentrator_count);
 }
+
+// Hand over the TX latency measurement of the given packet to the calibration
+// thread. The measurement is skipped when the previous measurement is still in
+// progress.
+fn start_tx_measurement(
+    calibration_tx: &Option<SyncSender<TxMeasurement>>,
+    downlink_id: &Uuid,
+    tx_packet: &hal::TxPacket,
+    send_time: Instant,
+    send_count_us: u32,
+    send_duration: Duration,
+) {
+    let calibration_tx = match calibration_tx {
+        Some(v) => v,
+        None => return,
+    };
+
+    // Only the start of a timestamped packet is known in advance.
+    match tx_packet.tx_mode {
+        hal::TxMode::Timestamped => {}
+        _ => return,
+    };
+
+    let measurement = TxMeasurement {
+        downlink_id: *downlink_id,
+        tx_start: send_time
+            + Duration::from_micros(tx_packet.count_us.wrapping_sub(send_count_us) as u64),
+        send_duration: send_duration,
+    };
+
+    if calibration_tx.try_send(measurement).is_err() {
+        debug!(
+            "Skipping JIT calibration, previous measurement in progress, downlink_id: {}",
+            downlink_id
+        );
+    }
+}
+
+fn calibration_loop(calibration_rx: Receiver<TxMeasurement>) {
+    debug!("Starting JIT calibration loop");
+
+    let mut calibration: jitqueue::Calibration = Default::default();
+
+    // The receive fails when the JIT loop has ended.
+    while let Ok(measurement) = calibration_rx.recv() {
+        measure_tx_latency(&mut calibration, &measurement);
+    }
+
+    debug!("JIT calibration loop ended");
+}
+
+// Wait for the start of the emission of the measured packet and add the
+// measured latency to the calibration.
+fn measure_tx_latency(calibration: &mut jitqueue::Calibration, measurement: &TxMeasurement) {
+    let downlink_id = &measurement.downlink_id;
+    let tx_start = measurement.tx_start;
+
+    loop {
+        match concentrator::get_tx_status() {
+            Ok(hal::TxStatus::Emitting) => break,
+            Ok(hal::TxStatus::Scheduled) => {}
+            Ok(v) => {
+                warn!(
+                    "Start of emission not detected, downlink_id: {}, tx_status: {:?}",
+                    downlink_id, v
+                );
+                return;
+            }
+            Err(err) => {
+                error!(
+                    "Get TX status error, downlink_id: {}, error: {}",
+                    downlink_id, err
+                );
+                return;
+            }
+        }
+
+        if Instant::now() > tx_start + CALIBRATION_TIMEOUT {
+            warn!(
+                "Start of emission not detected in time, downlink_id: {}",
+                downlink_id
+            );
+            return;
+        }
+
+        thread::sleep(CALIBRATION_POLL_INTERVAL);
+    }
+
+    let now = Instant::now();
+    let tx_start_offset = match now.checked_duration_since(tx_start) {
+        Some(v) => v.as_micros() as i64,
+        None => -((tx_start - now).as_micros() as i64),
+    };
+
+    calibration.add(
+        &downlink_id.to_string(),
+        measurement.send_duration,
+        tx_start_offset,
+    );
+}
+
+fn send_tx_ack(gateway_id: &[u8], downlink_id: &Uuid, status: proto::TxAckStatus) {
+    let mut tx_ack = chirpstack_api::gw::DownlinkTxAck {
+        gateway_id: gateway_id.to_vec(),
+        downlink_id: downlink_id.as_bytes().to_vec(),
+        items: vec![Default::default()],
+        ..Default::default()
+    };
+    tx_ack.items[0].status = status as i32;
+    stats::inc_tx_status(status);
+
+    info!(
+        "Publishing tx ack event, downlink_id: {}, status: {:?}",
+        downlink_id, status
+    );
+    events::send_tx_ack(&tx_ack).expect("sending tx ack error");
+}
@@ -4,5 +4,6 @@ pub mod config;
 pub mod gps;
 pub mod jit;
 pub mod stats;
+pub mod test_tx;
 pub mod timersync;
 pub mod uplink;
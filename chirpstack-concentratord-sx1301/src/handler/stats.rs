@@ -1,17 +1,20 @@
 use std::collections::HashMap;
 use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use libconcentratord::signals::Signal;
-use libconcentratord::stats;
+use libconcentratord::{jitqueue, stats};
 
+use super::super::wrapper;
 use super::gps;
 
 pub fn stats_loop(
     gateway_id: &[u8],
     stats_interval: &Duration,
+    queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
     stop_receive: Receiver<Signal>,
-    metadata: &HashMap<String, String>,
+    mut metadata: HashMap<String, String>,
 ) {
     debug!("Starting stats loop, stats_interval: {:?}", stats_interval);
 
@@ -42,7 +45,15 @@ pub fn stats_loop(
             None => None,
         };
 
-        stats::send_and_reset(gateway_id, loc, metadata).expect("sending stats failed");
+        // fetch the duty-cycle usage (%).
+        for (band, usage) in queue.lock().unwrap().get_duty_cycle_usage() {
+            metadata.insert(
+                format!("duty_cycle_{}", band),
+                format!("{:.3}", usage * 100.0),
+            );
+        }
+
+        stats::send_and_reset(gateway_id, loc, &metadata).expect("sending stats failed");
     }
 
     debug!("Stats loop ended");
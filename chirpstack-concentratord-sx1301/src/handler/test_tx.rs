@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use libconcentratord::{proto, testtx};
+use libloragw_sx1301::hal;
+
+use super::super::config::vendor;
+use super::super::{concentrator, wrapper};
+
+struct Transmitter {
+    tx_packet: hal::TxPacket,
+}
+
+impl testtx::Transmitter for Transmitter {
+    fn cw_on(&mut self) -> Result<(), String> {
+        return Err("continuous wave is not supported by the SX1301 HAL".to_string());
+    }
+
+    fn cw_off(&mut self) -> Result<(), String> {
+        return Ok(());
+    }
+
+    fn send_packet(&mut self) -> Result<Duration, String> {
+        let time_on_air = hal::time_on_air(&self.tx_packet)?;
+        concentrator::send(&self.tx_packet)?;
+        return Ok(time_on_air);
+    }
+}
+
+/// Start the given test transmission. The concentrator must be started.
+pub fn start(vendor_config: &vendor::Configuration, pl: &proto::TestTx) -> Result<(), String> {
+    let params = testtx::Parameters::from_proto(pl)?;
+    if params.cw {
+        return Err("continuous wave is not supported by the SX1301 HAL".to_string());
+    }
+
+    let tx_packet = wrapper::downlink_from_proto(&testtx::get_downlink_item(pl))?;
+
+    let rf_chain = tx_packet.rf_chain as usize;
+    if rf_chain >= vendor_config.radio_count || !vendor_config.radio_tx_enabled[rf_chain] {
+        return Err(format!(
+            "antenna does not support TX, antenna: {}",
+            rf_chain
+        ));
+    }
+
+    let freqs = vendor_config.radio_min_max_tx_freq[rf_chain];
+    if tx_packet.freq_hz < freqs.0 || tx_packet.freq_hz > freqs.1 {
+        return Err(format!(
+            "frequency is not within min/max gateway frequency, min_freq: {}, max_freq: {}",
+            freqs.0, freqs.1
+        ));
+    }
+
+    info!(
+        "Configuring test transmission, freq: {}, power: {}, mod: {:?}, dr: {:?}",
+        tx_packet.freq_hz, tx_packet.rf_power, tx_packet.modulation, tx_packet.datarate
+    );
+
+    testtx::start(
+        Transmitter {
+            tx_packet: tx_packet,
+        },
+        params,
+    )
+}
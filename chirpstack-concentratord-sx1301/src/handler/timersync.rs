@@ -4,6 +4,7 @@ use std::time::{SystemTime, Duration};
 use crate::concentrator;
 
 use libconcentratord::signals::Signal;
+use libconcentratord::simulator;
 use libloragw_sx1301::{hal, reg, wrapper};
 
 lazy_static! {
@@ -38,6 +39,10 @@ pub fn timesync_loop(stop_receive: Receiver<Signal>) {
 }
 
 pub fn get_concentrator_count() -> u32 {
+    if simulator::enabled() {
+        return simulator::get_count_us().expect("get simulator count error");
+    }
+
     let prev_concentrator_count = PREV_CONCENTRATOR_COUNT.lock().unwrap();
     let prev_unix_time = PREV_UNIX_TIME.lock().unwrap();
 
@@ -50,6 +55,13 @@ pub fn get_concentrator_count() -> u32 {
 }
 
 fn timesync() {
+    if simulator::enabled() {
+        concentrator::timestamp::update_counter(
+            simulator::get_count_us().expect("get simulator count error"),
+        );
+        return;
+    }
+
     debug!("Disabling GPS mode for concentrator counter");
     reg::reg_w(wrapper::LGW_GPS_EN, 0).unwrap();
 
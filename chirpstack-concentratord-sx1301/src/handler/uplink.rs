@@ -3,13 +3,12 @@ use std::thread;
 use std::time::Duration;
 
 use libconcentratord::signals::Signal;
-use libconcentratord::{events, stats};
-use libloragw_sx1301::hal;
+use libconcentratord::{capture, events, filters, stats};
 use uuid::Uuid;
 
-use super::super::wrapper;
+use super::super::{concentrator, wrapper};
 
-pub fn handle_loop(gateway_id: &[u8], stop_receive: Receiver<Signal>) {
+pub fn handle_loop(gateway_id: &[u8], board: u32, stop_receive: Receiver<Signal>) {
     debug!("Starting uplink handle loop");
 
     loop {
@@ -21,10 +20,10 @@ pub fn handle_loop(gateway_id: &[u8], stop_receive: Receiver<Signal>) {
             _ => {}
         };
 
-        match hal::receive() {
+        match concentrator::receive() {
             Ok(frames) => {
                 for frame in frames {
-                    let proto = match wrapper::uplink_to_proto(gateway_id.clone(), &frame) {
+                    let proto = match wrapper::uplink_to_proto(gateway_id.clone(), board, &frame) {
                         Ok(v) => v,
                         Err(err) => {
                             error!("Convert uplink frame to protobuf error, error: {}", err);
@@ -49,6 +48,17 @@ pub fn handle_loop(gateway_id: &[u8], stop_receive: Receiver<Signal>) {
                     if rx_info.crc_status() == chirpstack_api::gw::CrcStatus::CrcOk {
                         stats::inc_rx_packets_received_ok();
                     }
+                    stats::inc_rx_counts(&proto);
+
+                    if let Err(err) = capture::uplink(&proto) {
+                        error!("Capture uplink error, uplink_id: {}, error: {}", uuid, err);
+                    }
+
+                    if let Err(reason) = filters::check(&proto) {
+                        debug!("Frame dropped, uplink_id: {}, reason: {}", uuid, reason);
+                        stats::inc_rx_packets_dropped(&reason.to_string());
+                        continue;
+                    }
 
                     events::send_uplink(&proto).unwrap();
                 }
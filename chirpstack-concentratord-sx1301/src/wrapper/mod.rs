@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use libconcentratord::jitqueue;
+use libconcentratord::{capture, jitqueue};
 use libloragw_sx1301::hal;
 use uuid::Uuid;
 use crate::concentrator::timestamp::{calculate_timestamp, calculate_epochtime};
@@ -8,16 +8,20 @@ use crate::concentrator::timestamp::{calculate_timestamp, calculate_epochtime};
 use super::handler::gps;
 
 #[derive(Copy, Clone)]
-pub struct TxPacket(hal::TxPacket, Uuid);
+pub struct TxPacket(hal::TxPacket, Uuid, jitqueue::Priority);
 
 impl TxPacket {
-    pub fn new(id: Uuid, tx_packet: hal::TxPacket) -> TxPacket {
-        TxPacket(tx_packet, id)
+    pub fn new(id: Uuid, tx_packet: hal::TxPacket, priority: jitqueue::Priority) -> TxPacket {
+        TxPacket(tx_packet, id, priority)
     }
 
     pub fn tx_packet(&self) -> hal::TxPacket {
         self.0
     }
+
+    pub fn id(&self) -> Uuid {
+        self.1
+    }
 }
 
 impl jitqueue::TxPacket for TxPacket {
@@ -49,10 +53,19 @@ impl jitqueue::TxPacket for TxPacket {
     fn get_id(&self) -> String {
         self.1.to_string()
     }
+
+    fn get_priority(&self) -> jitqueue::Priority {
+        self.2
+    }
+
+    fn get_frequency(&self) -> u32 {
+        self.0.freq_hz
+    }
 }
 
 pub fn uplink_to_proto(
     gateway_id: &[u8],
+    board: u32,
     packet: &hal::RxPacket,
 ) -> Result<chirpstack_api::gw::UplinkFrame, String> {
     // tx info
@@ -115,7 +128,7 @@ pub fn uplink_to_proto(
     rx_info.lora_snr = packet.snr as f64;
     rx_info.channel = packet.if_chain as u32;
     rx_info.rf_chain = packet.rf_chain as u32;
-    rx_info.board = 0;
+    rx_info.board = board;
     rx_info.antenna = 0;
     rx_info.set_crc_status(match packet.status {
         hal::CRC::Undefined => chirpstack_api::gw::CrcStatus::NoCrc,
@@ -272,3 +285,41 @@ pub fn downlink_from_proto(
 
     return Ok(packet);
 }
+
+pub fn downlink_to_capture(gateway_id: &[u8], packet: &hal::TxPacket) -> capture::Packet {
+    capture::Packet {
+        gateway_id: gateway_id.to_vec(),
+        count_us: packet.count_us,
+        frequency: packet.freq_hz,
+        bandwidth: packet.bandwidth,
+        spreading_factor: match packet.datarate {
+            hal::DataRate::SF7 => 7,
+            hal::DataRate::SF8 => 8,
+            hal::DataRate::SF9 => 9,
+            hal::DataRate::SF10 => 10,
+            hal::DataRate::SF11 => 11,
+            hal::DataRate::SF12 => 12,
+            _ => 0,
+        },
+        code_rate: match packet.coderate {
+            hal::CodeRate::LoRa4_5 => "4/5",
+            hal::CodeRate::LoRa4_6 => "4/6",
+            hal::CodeRate::LoRa4_7 => "4/7",
+            hal::CodeRate::LoRa4_8 => "4/8",
+            hal::CodeRate::Undefined => "",
+        }
+        .to_string(),
+        datarate: match packet.datarate {
+            hal::DataRate::FSK(v) => v,
+            _ => 0,
+        },
+        crc_status: match packet.no_crc {
+            true => chirpstack_api::gw::CrcStatus::NoCrc,
+            false => chirpstack_api::gw::CrcStatus::CrcOk,
+        },
+        iq_inverted: packet.invert_pol,
+        rf_chain: packet.rf_chain as u32,
+        phy_payload: packet.payload[..packet.size as usize].to_vec(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,19 @@
+use std::process;
+
+use libconcentratord::curve;
+
+/// Print a new CURVE keypair for securing the API sockets. The secret key is
+/// set as concentratord.api.curve_secret_key, the public key is used by the
+/// clients.
+pub fn run() {
+    let (public_key, secret_key) = match curve::generate_keypair() {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Generate keypair error, error: {}", err);
+            process::exit(1);
+        }
+    };
+
+    println!("public_key=\"{}\"", public_key);
+    println!("secret_key=\"{}\"", secret_key);
+}
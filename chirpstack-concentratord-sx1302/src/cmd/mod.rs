@@ -1,2 +1,6 @@
 pub mod configfile;
+pub mod keygen;
+pub mod models;
 pub mod root;
+pub mod test_tx;
+pub mod validate;
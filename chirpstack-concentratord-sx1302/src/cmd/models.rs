@@ -0,0 +1,65 @@
+use std::process;
+
+use super::super::config::{self, vendor};
+
+/// Print the supported gateway models, with their accepted model_flags and
+/// TX frequency range. When configuration files are given, the models in the
+/// configured gateway.model_dir are included.
+pub fn run(filenames: &[String]) {
+    let mut config: config::Configuration = match filenames.is_empty() {
+        true => Default::default(),
+        false => match libconcentratord::config::parse(filenames) {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        },
+    };
+    config.gateway.model_flags = vec![];
+
+    let models = match vendor::get_models(&config.gateway.model_dir) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("gateway.model_dir: {}", err);
+            process::exit(1);
+        }
+    };
+
+    println!("{:<34} {:<12} {}", "MODEL", "FLAGS", "TX FREQUENCY RANGE");
+    for model in &models {
+        let tx_freq_range = match vendor::new(&config, model) {
+            Ok(v) => match get_tx_freq_range(&v) {
+                Some((min, max)) => format!("{} - {}", min, max),
+                None => "-".to_string(),
+            },
+            Err(_) => "-".to_string(),
+        };
+
+        println!(
+            "{:<34} {:<12} {}{}",
+            model.name,
+            match model.flags.is_empty() {
+                true => "-".to_string(),
+                false => model.flags.join(","),
+            },
+            tx_freq_range,
+            match &model.file {
+                Some(file) => format!(" ({})", file),
+                None => "".to_string(),
+            }
+        );
+    }
+}
+
+fn get_tx_freq_range(model_config: &vendor::Configuration) -> Option<(u32, u32)> {
+    model_config
+        .radio_config
+        .iter()
+        .filter(|r| r.tx_enable)
+        .map(|r| (r.tx_freq_min, r.tx_freq_max))
+        .fold(None, |acc, (min, max)| match acc {
+            Some((acc_min, acc_max)) => Some((min.min(acc_min), max.max(acc_max))),
+            None => Some((min, max)),
+        })
+}
@@ -5,7 +5,9 @@ use std::thread;
 
 use libconcentratord::signals;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, events, jitqueue, reset};
+use libconcentratord::{
+    capture, commands, dutycycle, events, filters, jitqueue, reset, simulator, testtx,
+};
 use libloragw_sx1302::hal;
 
 use super::super::{concentrator, config, handler, wrapper};
@@ -21,16 +23,8 @@ pub fn run(
         "https://www.chirpstack.io/concentratord/"
     );
 
-    // reset concentrator
-    reset::reset().expect("concentrator reset failed");
-
-    // setup concentrator
-    concentrator::board_setconf(&config)?;
-    concentrator::timestamp_setconf(&config)?;
-    concentrator::txgain_setconf(&config)?;
-    concentrator::rxrf_setconf(&config)?;
-    concentrator::rxif_setconf(&config)?;
-    concentrator::start()?;
+    let simulator = config.gateway.model == "simulator";
+    setup_concentrator(&config)?;
 
     // setup static location
     handler::gps::set_static_gps_coords(
@@ -40,7 +34,7 @@ pub fn run(
     );
 
     // get concentrator eui
-    let gateway_id = concentrator::get_eui().unwrap();
+    let gateway_id = concentrator::get_eui(&config)?;
 
     info!(
         "Gateway ID retrieved, gateway_id: {:x?}",
@@ -48,7 +42,25 @@ pub fn run(
     );
 
     // setup jit queue
-    let queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::new(32);
+    let jit_config = config.gateway.model_config.jit.merge(&config.gateway.jit);
+    let mut queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::from_config(&jit_config);
+    if config.gateway.duty_cycle.enable {
+        queue.set_duty_cycle(dutycycle::DutyCycle::new(
+            config.gateway.duty_cycle.window,
+            config
+                .gateway
+                .duty_cycle
+                .bands
+                .iter()
+                .map(|b| dutycycle::Band {
+                    name: b.name.clone(),
+                    frequency_min: b.frequency_min,
+                    frequency_max: b.frequency_max,
+                    duty_cycle: b.duty_cycle,
+                })
+                .collect(),
+        ));
+    }
     let queue = Arc::new(Mutex::new(queue));
 
     // setup zeromq
@@ -56,6 +68,18 @@ pub fn run(
     let rep_sock = commands::get_socket(&config.concentratord.api.command_bind)
         .expect("bind command socket error");
 
+    // setup uplink filters
+    filters::setup(&config.concentratord.filters)?;
+
+    // setup packet capture
+    capture::setup(
+        &config.concentratord.capture,
+        match config.gateway.lorawan_public {
+            true => 0x34,
+            false => 0x12,
+        },
+    )?;
+
     // setup threads
     let mut signal_pool = signals::SignalPool::new();
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
@@ -72,25 +96,32 @@ pub fn run(
 
     // jit thread
     threads.push(thread::spawn({
+        let gateway_id = gateway_id.clone();
         let queue = Arc::clone(&queue);
         let antenna_gain = config.gateway.antenna_gain;
+        let calibrate = jit_config.calibrate;
         let stop_receive = signal_pool.new_receiver();
 
         move || {
-            handler::jit::jit_loop(queue, antenna_gain, stop_receive);
+            handler::jit::jit_loop(&gateway_id, queue, antenna_gain, calibrate, stop_receive);
         }
     }));
 
     // command thread
     threads.push(thread::spawn({
         let vendor_config = config.gateway.model_config.clone();
+        let tx_limits = config.gateway.tx_limits.clone();
+        let lbt_config = config.gateway.lbt.clone();
         let gateway_id = gateway_id.clone();
+        let queue = Arc::clone(&queue);
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
 
         move || {
             handler::command::handle_loop(
                 &vendor_config,
+                &tx_limits,
+                &lbt_config,
                 &gateway_id,
                 queue,
                 rep_sock,
@@ -104,6 +135,7 @@ pub fn run(
     threads.push(thread::spawn({
         let gateway_id = gateway_id.clone();
         let stats_interval = config.concentratord.stats_interval;
+        let queue = Arc::clone(&queue);
         let stop_receive = signal_pool.new_receiver();
         let mut metadata = HashMap::new();
         metadata.insert(
@@ -118,11 +150,39 @@ pub fn run(
         metadata.insert("hal_version".to_string(), hal::version_info());
 
         move || {
-            handler::stats::stats_loop(&gateway_id, &stats_interval, stop_receive, metadata);
+            handler::stats::stats_loop(&gateway_id, &stats_interval, queue, stop_receive, metadata);
         }
     }));
 
-    if config.gateway.model_config.gps_tty_path.is_some() {
+    if config.gateway.spectral_scan.enable {
+        // spectral scan thread
+        threads.push(thread::spawn({
+            let spectral_scan_config = config.gateway.spectral_scan.clone();
+            let gateway_id = gateway_id.clone();
+            let queue = Arc::clone(&queue);
+            let stop_receive = signal_pool.new_receiver();
+
+            move || {
+                handler::spectral_scan::spectral_scan_loop(
+                    &spectral_scan_config,
+                    &gateway_id,
+                    queue,
+                    stop_receive,
+                );
+            }
+        }));
+    }
+
+    if simulator {
+        // gps simulator thread
+        threads.push(thread::spawn({
+            let stop_receive = signal_pool.new_receiver();
+
+            move || {
+                handler::gps::gps_simulator_loop(stop_receive);
+            }
+        }));
+    } else if config.gateway.model_config.gps_tty_path.is_some() {
         // gps thread
         threads.push(thread::spawn({
             let gps_tty_path = config
@@ -138,7 +198,9 @@ pub fn run(
                 handler::gps::gps_loop(&gps_tty_path, stop_receive);
             }
         }));
+    }
 
+    if simulator || config.gateway.model_config.gps_tty_path.is_some() {
         // gps validate thread
         threads.push(thread::spawn({
             let stop_receive = signal_pool.new_receiver();
@@ -147,6 +209,19 @@ pub fn run(
                 handler::gps::gps_validate_loop(stop_receive);
             }
         }));
+
+        // beacon thread
+        if config.gateway.beacon.frequencies.len() != 0 {
+            threads.push(thread::spawn({
+                let beacon_config = config.gateway.beacon.clone();
+                let queue = Arc::clone(&queue);
+                let stop_receive = signal_pool.new_receiver();
+
+                move || {
+                    handler::beacon::beacon_loop(&beacon_config, queue, stop_receive);
+                }
+            }));
+        }
     }
 
     let stop_signal = stop_receive.recv().unwrap();
@@ -156,7 +231,35 @@ pub fn run(
         t.join().unwrap();
     }
 
-    concentrator::stop()?;
+    if testtx::is_active() {
+        testtx::stop()?;
+    }
+
+    if !simulator {
+        concentrator::stop()?;
+    }
 
     return Ok(stop_signal);
 }
+
+/// Reset and start the (simulated) concentrator.
+pub fn setup_concentrator(config: &config::Configuration) -> Result<(), String> {
+    if config.gateway.model == "simulator" {
+        // setup simulated concentrator
+        simulator::start(&config.gateway.simulator.uplink_script)?;
+    } else {
+        // reset concentrator
+        reset::reset().expect("concentrator reset failed");
+
+        // setup concentrator
+        concentrator::board_setconf(&config)?;
+        concentrator::timestamp_setconf(&config)?;
+        concentrator::sx1261_setconf(&config)?;
+        concentrator::txgain_setconf(&config)?;
+        concentrator::rxrf_setconf(&config)?;
+        concentrator::rxif_setconf(&config)?;
+        concentrator::start()?;
+    }
+
+    return Ok(());
+}
@@ -1,3 +1,4 @@
+use libconcentratord::simulator;
 use libloragw_sx1302::{com, hal};
 
 use super::config::vendor::ComType;
@@ -43,6 +44,64 @@ pub fn timestamp_setconf(config: &Configuration) -> Result<(), String> {
     return Ok(());
 }
 
+pub fn sx1261_setconf(config: &Configuration) -> Result<(), String> {
+    let lbt = &config.gateway.lbt;
+    if !lbt.enable && !config.gateway.spectral_scan.enable {
+        return Ok(());
+    }
+
+    let sx1261_config = match config.gateway.model_config.sx1261_config.as_ref() {
+        Some(v) => v,
+        None => {
+            return Err(
+                "lbt and spectral_scan require a gateway model with a SX1261 radio".to_string(),
+            );
+        }
+    };
+
+    info!(
+        "Setting up SX1261 radio, spi_path: {}, rssi_offset: {}",
+        sx1261_config.spi_path, sx1261_config.rssi_offset
+    );
+
+    let mut lbt_channels: Vec<hal::LBTChannelConfig> = Vec::new();
+    for c in &lbt.channels {
+        info!(
+            "Configuring LBT channel, freq: {}, bandwidth: {}, scan_time_us: {}, transmit_time_ms: {}",
+            c.frequency, c.bandwidth, c.scan_time_us, c.transmit_time_ms
+        );
+
+        lbt_channels.push(hal::LBTChannelConfig {
+            freq_hz: c.frequency,
+            bandwidth: c.bandwidth,
+            scan_time: match c.scan_time_us {
+                128 => hal::LBTScanTime::Scan128US,
+                5000 => hal::LBTScanTime::Scan5000US,
+                _ => return Err("lbt scan_time_us must be 128 or 5000".to_string()),
+            },
+            transmit_time_ms: c.transmit_time_ms,
+        });
+    }
+
+    info!(
+        "Setting up listen-before-talk, enable: {}, rssi_target: {}",
+        lbt.enable, lbt.rssi_target
+    );
+
+    hal::sx1261_setconf(&hal::SX1261Config {
+        enable: true,
+        spi_path: sx1261_config.spi_path.clone(),
+        rssi_offset: sx1261_config.rssi_offset,
+        lbt_config: hal::LBTConfig {
+            enable: lbt.enable,
+            rssi_target: lbt.rssi_target,
+            channels: lbt_channels,
+        },
+    })?;
+
+    return Ok(());
+}
+
 pub fn txgain_setconf(config: &Configuration) -> Result<(), String> {
     for (i, radio_config) in config.gateway.model_config.radio_config.iter().enumerate() {
         if radio_config.tx_gain_table.len() == 0 {
@@ -198,7 +257,113 @@ pub fn stop() -> Result<(), String> {
     return Ok(());
 }
 
-pub fn get_eui() -> Result<[u8; 8], String> {
+pub fn get_eui(config: &Configuration) -> Result<[u8; 8], String> {
     debug!("Getting gateway EUI");
+    if simulator::enabled() {
+        let mut eui: [u8; 8] = [0; 8];
+        let b = hex::decode(&config.gateway.simulator.gateway_id).map_err(|e| e.to_string())?;
+        if b.len() != eui.len() {
+            return Err("simulator gateway_id must be exactly 8 bytes".to_string());
+        }
+        eui.copy_from_slice(&b);
+        return Ok(eui);
+    }
+
     return hal::get_eui();
 }
+
+pub fn receive() -> Result<Vec<hal::RxPacket>, String> {
+    if !simulator::enabled() {
+        return hal::receive();
+    }
+
+    let mut packets: Vec<hal::RxPacket> = Vec::new();
+    for uplink in simulator::receive()? {
+        let mut payload: [u8; 256] = [0; 256];
+        let size = uplink.phy_payload.len().min(payload.len());
+        payload[..size].copy_from_slice(&uplink.phy_payload[..size]);
+
+        packets.push(hal::RxPacket {
+            freq_hz: uplink.frequency,
+            freq_offset: 0,
+            if_chain: 0,
+            status: hal::CRC::CRCOk,
+            count_us: uplink.count_us,
+            rf_chain: 0,
+            modem_id: 0,
+            modulation: hal::Modulation::LoRa,
+            bandwidth: uplink.bandwidth,
+            datarate: match uplink.spreading_factor {
+                5 => hal::DataRate::SF5,
+                6 => hal::DataRate::SF6,
+                7 => hal::DataRate::SF7,
+                8 => hal::DataRate::SF8,
+                9 => hal::DataRate::SF9,
+                10 => hal::DataRate::SF10,
+                11 => hal::DataRate::SF11,
+                12 => hal::DataRate::SF12,
+                _ => return Err("invalid simulated spreading_factor".to_string()),
+            },
+            coderate: hal::CodeRate::LoRa4_5,
+            rssic: uplink.rssi as f32,
+            rssis: uplink.rssi as f32,
+            snr: uplink.snr,
+            snr_min: uplink.snr,
+            snr_max: uplink.snr,
+            crc: 0,
+            size: size as u16,
+            payload: payload,
+            ftime_received: false,
+            ftime: 0,
+        });
+    }
+
+    return Ok(packets);
+}
+
+pub fn send(tx_packet: &hal::TxPacket) -> Result<(), hal::SendError> {
+    if simulator::enabled() {
+        return simulator::send(tx_packet.count_us, tx_packet.freq_hz, tx_packet.size)
+            .map_err(hal::SendError::Failed);
+    }
+
+    return hal::send(tx_packet);
+}
+
+pub fn get_instcnt() -> Result<u32, String> {
+    if simulator::enabled() {
+        return simulator::get_count_us();
+    }
+
+    return hal::get_instcnt();
+}
+
+pub fn abort_tx(rf_chain: u8) -> Result<(), String> {
+    if simulator::enabled() {
+        return Ok(());
+    }
+
+    return hal::abort_tx(rf_chain);
+}
+
+pub fn get_tx_status(rf_chain: u8) -> Result<hal::TxStatus, String> {
+    if simulator::enabled() {
+        return match simulator::is_emitting()? {
+            true => Ok(hal::TxStatus::Emitting),
+            false => Ok(hal::TxStatus::Scheduled),
+        };
+    }
+
+    match hal::status(rf_chain, hal::StatusSelect::Tx)? {
+        hal::StatusReturn::Tx(v) => Ok(v),
+        _ => Err("unexpected status type".to_string()),
+    }
+}
+
+pub fn get_temperature() -> Result<f32, String> {
+    if simulator::enabled() {
+        return Err("temperature is not available in simulator".to_string());
+    }
+
+    return hal::get_temperature();
+}
@@ -105,9 +105,44 @@ pub fn get_radio_for_channel(
     return Err("channel does not fit in radio bandwidth".to_string());
 }
 
+/// Validate that the configured channels fit within the bandwidth of the
+/// radios, as this is only validated by the concentrator setup otherwise.
+pub fn validate_channels(config: &Configuration) -> Result<(), String> {
+    let radios = get_radio_frequencies(config)?;
+    let concentrator = &config.gateway.concentrator;
+
+    for freq_hz in concentrator.multi_sf_channels.iter() {
+        if *freq_hz != 0 {
+            get_radio_for_channel(
+                &radios,
+                *freq_hz,
+                config.gateway.model_config.lora_multi_sf_bandwidth,
+            )?;
+        }
+    }
+
+    if concentrator.lora_std.frequency != 0 {
+        get_radio_for_channel(
+            &radios,
+            concentrator.lora_std.frequency,
+            concentrator.lora_std.bandwidth,
+        )?;
+    }
+
+    if concentrator.fsk.frequency != 0 {
+        get_radio_for_channel(
+            &radios,
+            concentrator.fsk.frequency,
+            concentrator.fsk.bandwidth,
+        )?;
+    }
+
+    return Ok(());
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::vendor::semtech;
+    use super::super::vendor;
 
     #[test]
     fn test_get_radio_frequencies() {
@@ -156,6 +191,12 @@ mod tests {
             ),
         ];
 
+        let models = vendor::get_models("").unwrap();
+        let model = models
+            .iter()
+            .find(|m| m.name == "semtech_sx1302c868gw1_eu868")
+            .unwrap();
+
         for (_, multi_sf_channels, lora_std_freq, lora_std_bw, fsk_freq, fsk_bw, expected) in tests
         {
             let mut config: super::super::Configuration = Default::default();
@@ -165,7 +206,7 @@ mod tests {
             config.gateway.concentrator.lora_std.bandwidth = lora_std_bw;
             config.gateway.concentrator.fsk.frequency = fsk_freq;
             config.gateway.concentrator.fsk.bandwidth = fsk_bw;
-            config.gateway.model_config = semtech::sx1302c868gw1_eu868::new(&config);
+            config.gateway.model_config = vendor::new(&config, model).unwrap();
 
             let radios = super::get_radio_frequencies(&config).unwrap();
             assert_eq!(radios, expected);
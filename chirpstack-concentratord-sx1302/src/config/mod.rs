@@ -1,8 +1,11 @@
-use std::fs;
+use std::process;
 
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use libconcentratord::config::Error;
+use libconcentratord::{capture, filters, jitqueue};
+
 pub mod helpers;
 pub mod vendor;
 
@@ -16,12 +19,29 @@ pub struct Concentratord {
     #[serde(with = "humantime_serde")]
     pub stats_interval: Duration,
     pub api: API,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub state_file: String,
+    #[serde(default)]
+    pub filters: filters::Configuration,
+    #[serde(default)]
+    pub capture: capture::Configuration,
 }
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct API {
     pub event_bind: String,
     pub command_bind: String,
+    #[serde(default)]
+    pub curve_secret_key: String,
+    #[serde(default)]
+    pub curve_allowed_clients: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Metrics {
+    pub bind: String,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -31,6 +51,24 @@ pub struct Location {
     pub altitude: i16,
 }
 
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct TxLimits {
+    #[serde(default)]
+    pub max_eirp: i8,
+    #[serde(default)]
+    pub clamp_eirp: bool,
+    #[serde(default)]
+    pub dwell_time: Vec<DwellTime>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DwellTime {
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    #[serde(with = "humantime_serde")]
+    pub max_dwell_time: Duration,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct Gateway {
     #[serde(default)]
@@ -42,16 +80,36 @@ pub struct Gateway {
     #[serde(default)]
     pub model_flags: Vec<String>,
     #[serde(default)]
+    pub model_dir: String,
+    #[serde(default)]
     pub reset_pin: u32,
     #[serde(default)]
     pub power_en_pin: u32,
     pub concentrator: Concentrator,
     #[serde(default)]
+    pub beacon: Beacon,
+    #[serde(default)]
+    pub duty_cycle: DutyCycle,
+    #[serde(default)]
     pub location: Location,
+    #[serde(default)]
+    pub tx_limits: TxLimits,
 
     #[serde(default)]
     pub fine_timestamp: FineTimestamp,
 
+    #[serde(default)]
+    pub lbt: LBT,
+
+    #[serde(default)]
+    pub spectral_scan: SpectralScan,
+
+    #[serde(default)]
+    pub simulator: Simulator,
+
+    #[serde(default)]
+    pub jit: jitqueue::Configuration,
+
     #[serde(skip)]
     pub model_config: vendor::Configuration,
 
@@ -90,6 +148,42 @@ pub struct FSKChannel {
     pub datarate: u32,
 }
 
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Beacon {
+    pub compulsory_rfu_size: usize,
+    pub frequencies: Vec<u32>,
+    pub spreading_factor: u32,
+    pub bandwidth: u32,
+    pub tx_power: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DutyCycle {
+    pub enable: bool,
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    #[serde(default)]
+    pub bands: Vec<DutyCycleBand>,
+}
+
+impl Default for DutyCycle {
+    fn default() -> Self {
+        DutyCycle {
+            enable: false,
+            window: Duration::from_secs(3600),
+            bands: vec![],
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct DutyCycleBand {
+    pub name: String,
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    pub duty_cycle: f32,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FineTimestamp {
     pub enable: bool,
@@ -105,6 +199,62 @@ impl Default for FineTimestamp {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LBT {
+    pub enable: bool,
+    pub rssi_target: i8,
+    #[serde(default)]
+    pub channels: Vec<LBTChannel>,
+}
+
+impl Default for LBT {
+    fn default() -> Self {
+        LBT {
+            enable: false,
+            rssi_target: -80,
+            channels: vec![],
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct LBTChannel {
+    pub frequency: u32,
+    pub bandwidth: u32,
+    pub scan_time_us: u32,
+    pub transmit_time_ms: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SpectralScan {
+    pub enable: bool,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    pub frequency_start: u32,
+    pub frequency_step: u32,
+    pub nb_chan: u32,
+    pub nb_scan: u16,
+}
+
+impl Default for SpectralScan {
+    fn default() -> Self {
+        SpectralScan {
+            enable: false,
+            interval: Duration::from_secs(10),
+            frequency_start: 867100000,
+            frequency_step: 200000,
+            nb_chan: 8,
+            nb_scan: 2000,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Simulator {
+    pub gateway_id: String,
+    pub uplink_script: String,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 #[serde(default = "example_configuration")]
 pub struct Configuration {
@@ -153,29 +303,78 @@ fn example_configuration() -> Configuration {
 }
 
 pub fn get(filenames: Vec<String>) -> Configuration {
-    let mut content: String = String::new();
+    match try_get(&filenames) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Load configuration error, error: {}", err);
+            process::exit(1);
+        }
+    }
+}
 
-    for file_name in &filenames {
-        content.push_str(&fs::read_to_string(&file_name).expect("Error reading config file"));
+/// Read and validate the given configuration files.
+pub fn try_get(filenames: &[String]) -> Result<Configuration, Error> {
+    let mut config: Configuration = libconcentratord::config::parse(filenames)?;
+
+    // validate CURVE keys
+    let api = &config.concentratord.api;
+    if !api.curve_secret_key.is_empty() {
+        libconcentratord::curve::decode_key(&api.curve_secret_key).map_err(|e| Error::Value {
+            location: "concentratord.api.curve_secret_key".to_string(),
+            error: e,
+        })?;
+    }
+    for (i, key) in api.curve_allowed_clients.iter().enumerate() {
+        libconcentratord::curve::decode_key(key).map_err(|e| Error::Value {
+            location: format!("concentratord.api.curve_allowed_clients[{}]", i),
+            error: e,
+        })?;
     }
 
-    let mut config: Configuration = toml::from_str(&content).expect("Error parsing config file");
+    // validate uplink filters
+    filters::Filters::new(&config.concentratord.filters).map_err(|e| Error::Value {
+        location: "concentratord.filters".to_string(),
+        error: e,
+    })?;
 
     // get model configuration
-    config.gateway.model_config = match config.gateway.model.as_ref() {
-        "semtech_sx1302c868gw1_eu868" => vendor::semtech::sx1302c868gw1_eu868::new(&config),
-        "semtech_sx1302c915gw1_us915" => vendor::semtech::sx1302c915gw1_us915::new(&config),
-        "rak_2287_as923" => vendor::rak::rak2287_as923::new(&config),
-        "rak_2287_au915" => vendor::rak::rak2287_au915::new(&config),
-        "rak_2287_eu868" => vendor::rak::rak2287_eu868::new(&config),
-        "rak_2287_in865" => vendor::rak::rak2287_in865::new(&config),
-        "rak_2287_kr920" => vendor::rak::rak2287_kr920::new(&config),
-        "rak_2287_ru864" => vendor::rak::rak2287_ru864::new(&config),
-        "rak_2287_us915" => vendor::rak::rak2287_us915::new(&config),
-        _ => panic!("unexpected gateway model: {}", config.gateway.model),
+    let models = vendor::get_models(&config.gateway.model_dir).map_err(|e| Error::Value {
+        location: "gateway.model_dir".to_string(),
+        error: e,
+    })?;
+    let model = match models.iter().find(|m| m.name == config.gateway.model) {
+        Some(v) => v,
+        None => {
+            return Err(Error::Value {
+                location: "gateway.model".to_string(),
+                error: format!(
+                    "unknown gateway model: {}, see the models subcommand for the supported models",
+                    config.gateway.model
+                ),
+            })
+        }
     };
 
+    for flag in &config.gateway.model_flags {
+        if !model.flags.contains(flag) {
+            return Err(Error::Value {
+                location: "gateway.model_flags".to_string(),
+                error: format!(
+                    "unknown flag: {}, accepted flags for {}: [{}]",
+                    flag,
+                    model.name,
+                    model.flags.join(", ")
+                ),
+            });
+        }
+    }
+
+    config.gateway.model_config = vendor::new(&config, model).map_err(|e| Error::Value {
+        location: "gateway.model".to_string(),
+        error: e,
+    })?;
+
     debug!("Antenna gain {} dB", config.gateway.antenna_gain);
 
-    return config;
+    return Ok(config);
 }
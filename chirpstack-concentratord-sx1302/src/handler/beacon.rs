@@ -0,0 +1,81 @@
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use libconcentratord::signals::Signal;
+use libconcentratord::{beacon, jitqueue};
+use libloragw_sx1302::hal;
+use uuid::Uuid;
+
+use super::super::{concentrator, config, wrapper};
+use super::gps;
+
+pub fn beacon_loop(
+    conf: &config::Beacon,
+    queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    stop_receive: Receiver<Signal>,
+) {
+    beacon::beacon_loop(
+        gps::get_gps_epoch,
+        |beacon_time| send_beacon(conf, beacon_time, &queue),
+        stop_receive,
+    );
+}
+
+fn send_beacon(
+    conf: &config::Beacon,
+    beacon_time: Duration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Result<(), String> {
+    let mut beacon_pl = beacon::get_beacon(conf.compulsory_rfu_size, beacon_time);
+    let data_size = beacon_pl.len();
+
+    let mut data: [u8; 256] = [0; 256];
+    beacon_pl.resize(data.len(), 0);
+    data.copy_from_slice(&beacon_pl);
+
+    let xtal_correct = match gps::get_xtal_correct() {
+        Ok(v) => v,
+        Err(err) => return Err(err),
+    };
+
+    let tx_freq = beacon::get_frequency(&conf.frequencies, beacon_time);
+    let tx_packet = hal::TxPacket {
+        freq_hz: (tx_freq as f64 * xtal_correct) as u32,
+        tx_mode: hal::TxMode::OnGPS,
+        count_us: match gps::epoch2cnt(&beacon_time) {
+            Ok(v) => v,
+            Err(err) => return Err(err),
+        },
+        rf_chain: 0,
+        rf_power: conf.tx_power as i8,
+        modulation: hal::Modulation::LoRa,
+        bandwidth: conf.bandwidth,
+        datarate: match conf.spreading_factor {
+            7 => hal::DataRate::SF7,
+            8 => hal::DataRate::SF8,
+            9 => hal::DataRate::SF9,
+            10 => hal::DataRate::SF10,
+            11 => hal::DataRate::SF11,
+            12 => hal::DataRate::SF12,
+            _ => return Err("invalid spreading-factor configured".to_string()),
+        },
+        coderate: hal::CodeRate::LoRa4_5,
+        invert_pol: false,
+        f_dev: 0,
+        preamble: 10,
+        no_crc: true,
+        no_header: true,
+        size: data_size as u16,
+        payload: data,
+        ..Default::default()
+    };
+    let tx_packet = wrapper::TxPacket::new(Uuid::new_v4(), tx_packet, jitqueue::Priority::Beacon);
+
+    let concentrator_count = concentrator::get_instcnt()?;
+
+    match queue.lock().unwrap().enqueue(concentrator_count, tx_packet) {
+        Ok(_) => Ok(()),
+        Err(status) => Err(format!("{:?}", status)),
+    }
+}
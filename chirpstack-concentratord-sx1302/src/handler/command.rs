@@ -3,16 +3,19 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, jitqueue, stats};
+use libconcentratord::{commands, jitqueue, proto, stats, testtx};
 use libloragw_sx1302::hal;
 use prost::Message;
 use uuid::Uuid;
 
-use super::super::config::vendor;
-use super::super::wrapper;
+use super::super::config::{vendor, TxLimits, LBT};
+use super::super::{concentrator, wrapper};
+use super::test_tx;
 
 pub fn handle_loop(
     vendor_config: &vendor::Configuration,
+    tx_limits: &TxLimits,
+    lbt_config: &LBT,
     gateway_id: &[u8],
     queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
     rep_sock: zmq::Socket,
@@ -38,12 +41,23 @@ pub fn handle_loop(
                 continue;
             }
             commands::Command::Downlink(pl) => {
-                match handle_downlink(vendor_config, gateway_id, &queue, &pl) {
+                match handle_downlink(
+                    vendor_config,
+                    tx_limits,
+                    lbt_config,
+                    gateway_id,
+                    &queue,
+                    &pl,
+                ) {
                     Ok(v) => v,
                     Err(_) => Vec::new(),
                 }
             }
             commands::Command::GatewayID => gateway_id.to_vec(),
+            commands::Command::Queue => handle_queue(gateway_id, &queue),
+            commands::Command::Cancel(id) => handle_cancel(gateway_id, &queue, &id),
+            commands::Command::TestTx(pl) => handle_test_tx(vendor_config, gateway_id, &queue, &pl),
+            commands::Command::TestTxStop => handle_test_tx_stop(gateway_id),
             commands::Command::Configuration(pl) => {
                 match handle_configuration(stop_send.clone(), pl) {
                     Ok(v) => v,
@@ -68,6 +82,8 @@ pub fn handle_loop(
 
 fn handle_downlink(
     vendor_config: &vendor::Configuration,
+    tx_limits: &TxLimits,
+    lbt_config: &LBT,
     gateway_id: &[u8],
     queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
     pl: &chirpstack_api::gw::DownlinkFrame,
@@ -92,7 +108,7 @@ fn handle_downlink(
 
     for (i, item) in pl.items.iter().enumerate() {
         // convert protobuf to hal struct
-        let tx_packet = match wrapper::downlink_from_proto(item) {
+        let mut tx_packet = match wrapper::downlink_from_proto(item) {
             Ok(v) => v,
             Err(err) => {
                 error!(
@@ -108,40 +124,198 @@ fn handle_downlink(
             Some(v) => {
                 if tx_packet.freq_hz < v.tx_freq_min || tx_packet.freq_hz > v.tx_freq_max {
                     error!("Frequency is not within min/max gateway frequency, downlink_id: {}, min_freq: {}, max_freq: {}", id, v.tx_freq_min, v.tx_freq_max);
-                    tx_ack.items[i].set_status(chirpstack_api::gw::TxAckStatus::TxFreq);
+                    tx_ack.items[i].status = proto::TxAckStatus::TxFreq as i32;
 
                     // try next
                     continue;
                 }
             }
             None => {
-                tx_ack.items[i].set_status(chirpstack_api::gw::TxAckStatus::TxFreq);
+                tx_ack.items[i].status = proto::TxAckStatus::TxFreq as i32;
 
                 // try next
                 continue;
             }
         };
 
+        // validate that the frequency is a configured LBT channel
+        if lbt_config.enable
+            && !lbt_config
+                .channels
+                .iter()
+                .any(|c| c.frequency == tx_packet.freq_hz)
+        {
+            error!(
+                "Frequency is not a configured LBT channel, downlink_id: {}, freq: {}",
+                id, tx_packet.freq_hz
+            );
+            tx_ack.items[i].status = proto::TxAckStatus::TxFreq as i32;
+
+            // try next
+            continue;
+        }
+
+        // validate tx power (EIRP)
+        if tx_limits.max_eirp != 0 && tx_packet.rf_power > tx_limits.max_eirp {
+            if tx_limits.clamp_eirp {
+                warn!(
+                    "Clamping tx power to max EIRP, downlink_id: {}, power: {}, max_eirp: {}",
+                    id, tx_packet.rf_power, tx_limits.max_eirp
+                );
+                tx_packet.rf_power = tx_limits.max_eirp;
+            } else {
+                error!(
+                    "TX power exceeds max EIRP, downlink_id: {}, power: {}, max_eirp: {}",
+                    id, tx_packet.rf_power, tx_limits.max_eirp
+                );
+                tx_ack.items[i].status = proto::TxAckStatus::TxPower as i32;
+
+                // try next
+                continue;
+            }
+        }
+
+        // validate dwell time
+        if let Some(dwell_time) = tx_limits
+            .dwell_time
+            .iter()
+            .find(|d| tx_packet.freq_hz >= d.frequency_min && tx_packet.freq_hz <= d.frequency_max)
+        {
+            let time_on_air = match hal::time_on_air(&tx_packet) {
+                Ok(v) => v,
+                Err(err) => {
+                    error!(
+                        "Get time on air for tx packet error, downlink_id: {}, error: {}",
+                        id, err
+                    );
+                    tx_ack.items[i].status = proto::TxAckStatus::InternalError as i32;
+
+                    // try next
+                    continue;
+                }
+            };
+
+            if time_on_air > dwell_time.max_dwell_time {
+                error!(
+                    "Time on air exceeds max dwell time, downlink_id: {}, freq: {}, time_on_air: {:?}, max_dwell_time: {:?}",
+                    id, tx_packet.freq_hz, time_on_air, dwell_time.max_dwell_time
+                );
+                tx_ack.items[i].status = proto::TxAckStatus::TxFreq as i32;
+
+                // try next
+                continue;
+            }
+        }
+
         // try enqueue
         match queue.lock().unwrap().enqueue(
-            hal::get_instcnt().expect("get concentrator count error"),
-            wrapper::TxPacket::new(id, tx_packet),
+            concentrator::get_instcnt().expect("get concentrator count error"),
+            wrapper::TxPacket::new(id, tx_packet, jitqueue::Priority::from_downlink(item)),
         ) {
             Ok(_) => {
-                tx_ack.items[i].set_status(chirpstack_api::gw::TxAckStatus::Ok);
+                tx_ack.items[i].status = proto::TxAckStatus::Ok as i32;
+                if let Some(tx_info) = &item.tx_info {
+                    stats::inc_tx_counts(tx_info);
+                }
 
                 // break out of loop
                 break;
             }
-            Err(status) => tx_ack.items[i].set_status(status),
+            Err(status) => tx_ack.items[i].status = status as i32,
         };
     }
 
+    // The status of an accepted downlink is counted by the JIT loop.
+    if !tx_ack
+        .items
+        .iter()
+        .any(|i| proto::TxAckStatus::from_item(i) == proto::TxAckStatus::Ok)
+    {
+        if let Some(item) = tx_ack.items.last() {
+            stats::inc_tx_status(proto::TxAckStatus::from_item(item));
+        }
+    }
+
     let mut buf = Vec::new();
     tx_ack.encode(&mut buf).unwrap();
     return Ok(buf);
 }
 
+fn handle_queue(
+    gateway_id: &[u8],
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Vec<u8> {
+    let concentrator_count = concentrator::get_instcnt().expect("get concentrator count error");
+    let pl = proto::JitQueue {
+        gateway_id: gateway_id.to_vec(),
+        items: queue.lock().unwrap().get_items(concentrator_count),
+    };
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).unwrap();
+    return buf;
+}
+
+fn handle_cancel(
+    gateway_id: &[u8],
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    id: &Uuid,
+) -> Vec<u8> {
+    let concentrator_count = concentrator::get_instcnt().expect("get concentrator count error");
+    let pl = proto::JitQueue {
+        gateway_id: gateway_id.to_vec(),
+        items: queue
+            .lock()
+            .unwrap()
+            .cancel(concentrator_count, &id.to_string()),
+    };
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).unwrap();
+    return buf;
+}
+
+fn handle_test_tx(
+    vendor_config: &vendor::Configuration,
+    gateway_id: &[u8],
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    pl: &proto::TestTx,
+) -> Vec<u8> {
+    let res = testtx::Parameters::from_proto(pl).and_then(|params| {
+        let concentrator_count = concentrator::get_instcnt()?;
+        if queue
+            .lock()
+            .unwrap()
+            .has_pending_tx(concentrator_count, params.duration)
+        {
+            return Err("a queued downlink is scheduled during the test transmission".to_string());
+        }
+
+        test_tx::start(vendor_config, pl)
+    });
+
+    return test_tx_response(gateway_id, res);
+}
+
+fn handle_test_tx_stop(gateway_id: &[u8]) -> Vec<u8> {
+    return test_tx_response(gateway_id, testtx::stop());
+}
+
+fn test_tx_response(gateway_id: &[u8], res: Result<(), String>) -> Vec<u8> {
+    if let Err(err) = &res {
+        error!("Test transmission error, error: {}", err);
+    }
+
+    let pl = proto::TestTxResponse {
+        gateway_id: gateway_id.to_vec(),
+        error: res.err().unwrap_or_default(),
+    };
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).unwrap();
+    return buf;
+}
+
 fn handle_configuration(
     stop_send: Sender<Signal>,
     pl: chirpstack_api::gw::GatewayConfiguration,
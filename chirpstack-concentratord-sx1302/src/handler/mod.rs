@@ -1,6 +1,9 @@
+pub mod beacon;
 pub mod command;
 pub mod config;
 pub mod gps;
 pub mod jit;
+pub mod spectral_scan;
 pub mod stats;
+pub mod test_tx;
 pub mod uplink;
@@ -0,0 +1,140 @@
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libconcentratord::signals::Signal;
+use libconcentratord::{events, jitqueue, proto, testtx};
+use libloragw_sx1302::hal;
+
+use super::super::{concentrator, config, wrapper};
+
+// Max. duration of a single scan. When exceeded, the scan is aborted. This
+// is also the duration for which the JIT queue must be free of TX packets
+// before a scan is started.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn spectral_scan_loop(
+    conf: &config::SpectralScan,
+    gateway_id: &[u8],
+    queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    stop_receive: Receiver<Signal>,
+) {
+    debug!(
+        "Starting spectral scan loop, interval: {:?}, freq_start: {}, freq_step: {}, nb_chan: {}, nb_scan: {}",
+        conf.interval, conf.frequency_start, conf.frequency_step, conf.nb_chan, conf.nb_scan
+    );
+
+    'outer: loop {
+        // Instead of an 'interval' sleep, we receive from the stop channel with a
+        // timeout equal to the 'interval'.
+        match stop_receive.recv_timeout(conf.interval) {
+            Ok(v) => {
+                debug!("Received stop signal, signal: {}", v);
+                break;
+            }
+            _ => {}
+        };
+
+        for i in 0..conf.nb_chan {
+            let freq = conf.frequency_start + i * conf.frequency_step;
+
+            if has_pending_tx(&queue) {
+                debug!(
+                    "Skipping spectral scan, a TX packet is pending, freq: {}",
+                    freq
+                );
+                continue;
+            }
+
+            match scan(gateway_id, freq, conf.nb_scan, &stop_receive) {
+                Ok(Some(v)) => {
+                    events::send_spectral_scan(&v).expect("sending spectral scan error");
+                }
+                Ok(None) => {
+                    break 'outer;
+                }
+                Err(err) => {
+                    error!("Spectral scan error, freq: {}, error: {}", freq, err);
+                }
+            }
+        }
+    }
+
+    debug!("Spectral scan loop ended");
+}
+
+// Scan the given frequency. This returns None when a stop signal was received
+// during the scan.
+fn scan(
+    gateway_id: &[u8],
+    freq: u32,
+    nb_scan: u16,
+    stop_receive: &Receiver<Signal>,
+) -> Result<Option<proto::SpectralScanResult>, String> {
+    debug!(
+        "Starting spectral scan, freq: {}, nb_scan: {}",
+        freq, nb_scan
+    );
+
+    hal::spectral_scan_start(freq, nb_scan)?;
+    let start = Instant::now();
+
+    loop {
+        match stop_receive.try_recv() {
+            Ok(v) => {
+                debug!("Received stop signal, signal: {}", v);
+                hal::spectral_scan_abort()?;
+                return Ok(None);
+            }
+            _ => {}
+        };
+
+        match hal::spectral_scan_get_status()? {
+            hal::SpectralScanStatus::Completed => break,
+            hal::SpectralScanStatus::Aborted => {
+                return Err("spectral scan was aborted".to_string());
+            }
+            _ => {}
+        }
+
+        if start.elapsed() > SCAN_TIMEOUT {
+            hal::spectral_scan_abort()?;
+            return Err("spectral scan timeout".to_string());
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let results = hal::spectral_scan_get_results()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+    return Ok(Some(proto::SpectralScanResult {
+        gateway_id: gateway_id.to_vec(),
+        time: Some(prost_types::Timestamp {
+            seconds: now.as_secs() as i64,
+            nanos: now.subsec_nanos() as i32,
+        }),
+        frequency: freq,
+        nb_scan: nb_scan as u32,
+        levels: results
+            .iter()
+            .map(|r| proto::SpectralScanLevel {
+                rssi: r.dbm_level as i32,
+                count: r.result as u32,
+            })
+            .collect(),
+    }));
+}
+
+// A test transmission is handled as pending TX.
+fn has_pending_tx(queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>) -> bool {
+    if testtx::is_active() {
+        return true;
+    }
+
+    let concentrator_count = concentrator::get_instcnt().expect("get concentrator count error");
+    let queue = queue.lock().unwrap();
+
+    return queue.has_pending_tx(concentrator_count, SCAN_TIMEOUT);
+}
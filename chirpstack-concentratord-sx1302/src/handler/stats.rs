@@ -1,16 +1,18 @@
 use std::collections::HashMap;
 use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use libconcentratord::signals::Signal;
-use libconcentratord::stats;
-use libloragw_sx1302::hal;
+use libconcentratord::{jitqueue, metrics, stats};
 
+use super::super::{concentrator, wrapper};
 use super::gps;
 
 pub fn stats_loop(
     gateway_id: &[u8],
     stats_interval: &Duration,
+    queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
     stop_receive: Receiver<Signal>,
     mut metadata: HashMap<String, String>,
 ) {
@@ -44,9 +46,10 @@ pub fn stats_loop(
         };
 
         // fetch the concentrator temperature.
-        match hal::get_temperature() {
+        match concentrator::get_temperature() {
             Ok(v) => {
                 metadata.insert("concentrator_temp".to_string(), format!("{}", v));
+                metrics::set_concentrator_temperature(v);
             }
             Err(err) => {
                 metadata.remove(&"concentrator_temp".to_string());
@@ -54,6 +57,14 @@ pub fn stats_loop(
             }
         }
 
+        // fetch the duty-cycle usage (%).
+        for (band, usage) in queue.lock().unwrap().get_duty_cycle_usage() {
+            metadata.insert(
+                format!("duty_cycle_{}", band),
+                format!("{:.3}", usage * 100.0),
+            );
+        }
+
         stats::send_and_reset(gateway_id, loc, &metadata).expect("sending stats failed");
     }
 
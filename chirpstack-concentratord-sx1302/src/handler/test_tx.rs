@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use libconcentratord::{proto, testtx};
+use libloragw_sx1302::hal;
+
+use super::super::config::vendor;
+use super::super::{concentrator, wrapper};
+
+struct Transmitter {
+    tx_packet: hal::TxPacket,
+}
+
+impl testtx::Transmitter for Transmitter {
+    fn cw_on(&mut self) -> Result<(), String> {
+        concentrator::send(&self.tx_packet).map_err(|e| e.to_string())
+    }
+
+    fn cw_off(&mut self) -> Result<(), String> {
+        concentrator::abort_tx(self.tx_packet.rf_chain)
+    }
+
+    fn send_packet(&mut self) -> Result<Duration, String> {
+        let time_on_air = hal::time_on_air(&self.tx_packet)?;
+        concentrator::send(&self.tx_packet).map_err(|e| e.to_string())?;
+        return Ok(time_on_air);
+    }
+}
+
+/// Start the given test transmission. The concentrator must be started.
+pub fn start(vendor_config: &vendor::Configuration, pl: &proto::TestTx) -> Result<(), String> {
+    let params = testtx::Parameters::from_proto(pl)?;
+
+    let tx_packet = match pl.cw {
+        // The continuous wave is stopped by aborting the TX.
+        true => hal::TxPacket {
+            freq_hz: pl.frequency,
+            tx_mode: hal::TxMode::Immediate,
+            rf_chain: 0,
+            rf_power: pl.power as i8,
+            modulation: hal::Modulation::CW,
+            ..Default::default()
+        },
+        false => wrapper::downlink_from_proto(&testtx::get_downlink_item(pl))?,
+    };
+
+    match vendor_config.radio_config.get(tx_packet.rf_chain as usize) {
+        Some(v) => {
+            if tx_packet.freq_hz < v.tx_freq_min || tx_packet.freq_hz > v.tx_freq_max {
+                return Err(format!(
+                    "frequency is not within min/max gateway frequency, min_freq: {}, max_freq: {}",
+                    v.tx_freq_min, v.tx_freq_max
+                ));
+            }
+        }
+        None => return Err("no TX radio configured".to_string()),
+    };
+
+    info!(
+        "Configuring test transmission, freq: {}, power: {}, mod: {:?}, dr: {:?}",
+        tx_packet.freq_hz, tx_packet.rf_power, tx_packet.modulation, tx_packet.datarate
+    );
+
+    testtx::start(
+        Transmitter {
+            tx_packet: tx_packet,
+        },
+        params,
+    )
+}
@@ -12,13 +12,13 @@ use std::sync::Arc;
 use std::thread;
 
 use clap::{App, Arg};
-use signal_hook::consts::signal::SIGINT;
+use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGTERM};
 use signal_hook::iterator::Signals;
 use simple_logger::SimpleLogger;
 use syslog::{BasicLogger, Facility, Formatter3164};
 
-use libconcentratord::reset;
 use libconcentratord::signals::Signal;
+use libconcentratord::{curve, metrics, reset, state};
 
 mod cmd;
 mod concentrator;
@@ -38,14 +38,103 @@ fn main() {
                 .value_name("FILE")
                 .multiple(true)
                 .number_of_values(1)
-                .help("Path to configuration file")
+                .help("Path to configuration file or directory (*.toml files, in lexical order)")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("debug-config")
+                .long("debug-config")
+                .help("Print which configuration file or environment variable set each value"),
+        )
         .subcommand(App::new("configfile").about("Print the configuration template"))
+        .subcommand(App::new("models").about("Print the supported gateway models"))
+        .subcommand(App::new("keygen").about("Generate a CURVE keypair for the API sockets"))
+        .subcommand(
+            App::new("validate")
+                .about("Validate the configuration files without accessing the concentrator"),
+        )
+        .subcommand(
+            App::new("test-tx")
+                .about("Transmit a continuous wave or LoRa test packets (the Concentratord must not be running)")
+                .arg(
+                    Arg::with_name("frequency")
+                        .long("frequency")
+                        .value_name("HZ")
+                        .help("Frequency")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("power")
+                        .long("power")
+                        .value_name("DBM")
+                        .help("TX power")
+                        .default_value("14"),
+                )
+                .arg(
+                    Arg::with_name("duration")
+                        .long("duration")
+                        .value_name("SECONDS")
+                        .help("Duration of the test transmission")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("cw")
+                        .long("cw")
+                        .help("Transmit a continuous wave instead of LoRa test packets"),
+                )
+                .arg(
+                    Arg::with_name("bandwidth")
+                        .long("bandwidth")
+                        .value_name("HZ")
+                        .help("Bandwidth of the LoRa test packets")
+                        .default_value("125000"),
+                )
+                .arg(
+                    Arg::with_name("spreading-factor")
+                        .long("spreading-factor")
+                        .value_name("SF")
+                        .help("Spreading factor of the LoRa test packets")
+                        .default_value("7"),
+                )
+                .arg(
+                    Arg::with_name("payload-size")
+                        .long("payload-size")
+                        .value_name("BYTES")
+                        .help("Payload size of the LoRa test packets")
+                        .default_value("16"),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .value_name("MS")
+                        .help("Time between the LoRa test packets")
+                        .default_value("1000"),
+                ),
+        )
         .get_matches();
 
     let config_files = matches.values_of_lossy("config").unwrap_or(vec![]);
-    let mut config = config::get(config_files);
+
+    if matches.is_present("debug-config") {
+        libconcentratord::config::print_origins(&config_files);
+    }
+
+    if let Some(_) = matches.subcommand_matches("models") {
+        cmd::models::run(&config_files);
+        process::exit(0);
+    }
+
+    if let Some(_) = matches.subcommand_matches("keygen") {
+        cmd::keygen::run();
+        process::exit(0);
+    }
+
+    if let Some(_) = matches.subcommand_matches("validate") {
+        process::exit(cmd::validate::run(&config_files));
+    }
+
+    let mut config = config::get(config_files.clone());
 
     if let Some(_) = matches.subcommand_matches("configfile") {
         cmd::configfile::run(&config);
@@ -80,40 +169,179 @@ fn main() {
             .unwrap();
     }
 
-    let mut signals = Signals::new(&[SIGINT]).expect("error registering channels");
+    // restore gateway configuration from state file
+    let mut pushed_config = load_state(&mut config);
+
+    // setup CURVE security of the API sockets
+    setup_curve(&config).expect("setup curve error");
+
+    // setup prometheus metrics
+    if !config.concentratord.metrics.bind.is_empty() {
+        // The metrics are not essential, the concentratord continues without
+        // the metrics endpoint when it can not be started.
+        if let Err(err) = metrics::start(&config.concentratord.metrics.bind) {
+            error!(
+                "Start metrics server error, continuing without metrics, bind: {}, error: {}",
+                config.concentratord.metrics.bind, err
+            );
+        }
+    }
+
+    // configure concentrator reset pin
+    setup_reset_pins(&config).expect("setup reset pin error");
+
+    if let Some(matches) = matches.subcommand_matches("test-tx") {
+        process::exit(cmd::test_tx::run(&config, matches));
+    }
+
+    let mut signals = Signals::new(&[SIGINT, SIGTERM, SIGHUP]).expect("error registering channels");
     let (stop_send, stop_receive) = channel();
     let stop_receive = Arc::new(stop_receive);
 
     thread::spawn({
         let stop_send = stop_send.clone();
+        let config_files = config_files.clone();
 
         move || {
-            let mut signal_iter = signals.forever();
-            let _ = signal_iter.next();
-            warn!("Received stop signal, stopping Concentratord");
-            stop_send.send(Signal::Stop).unwrap();
-            let _ = signal_iter.next();
-            warn!("Received stop signal, terminating Concentratord immediately");
-            process::exit(0);
+            let mut stopping = false;
+
+            for signal in signals.forever() {
+                if signal == SIGHUP {
+                    // Validate the configuration before restarting, so that an
+                    // invalid configuration does not stop the Concentratord.
+                    match config::try_get(&config_files) {
+                        Ok(_) => {
+                            warn!("Received reload signal, reloading configuration");
+                            stop_send.send(Signal::Reload).unwrap();
+                        }
+                        Err(err) => {
+                            error!("Reload configuration error, error: {}", err);
+                        }
+                    }
+
+                    continue;
+                }
+
+                if stopping {
+                    warn!("Received stop signal, terminating Concentratord immediately");
+                    process::exit(0);
+                }
+
+                warn!("Received stop signal, stopping Concentratord");
+                stop_send.send(Signal::Stop).unwrap();
+                stopping = true;
+            }
         }
     });
 
-    // configure concentrator reset pin
-    if config.gateway.model_config.reset_pin.is_some() {
-        reset::setup_pins(
-            config.gateway.model_config.reset_pin.unwrap(),
-            config.gateway.model_config.power_en_pin,
-        )
-        .expect("setup reset pin error");
-    }
-
     loop {
-        match cmd::root::run(&config, stop_send.clone(), stop_receive.clone()).unwrap() {
+        let signal = match cmd::root::run(&config, stop_send.clone(), stop_receive.clone()) {
+            Ok(v) => v,
+            Err(err) if pushed_config => {
+                // Fall back to the configuration files, so that an invalid
+                // (restored) gateway configuration does not keep the
+                // Concentratord from starting.
+                error!(
+                    "Start with gateway configuration error, falling back to configuration files, error: {}",
+                    err
+                );
+                config = config::get(config_files.clone());
+                pushed_config = false;
+                continue;
+            }
+            Err(err) => panic!("Start Concentratord error, error: {}", err),
+        };
+
+        match signal {
             Signal::Stop => process::exit(0),
+            Signal::Reload => match config::try_get(&config_files) {
+                Ok(v) => {
+                    // The CURVE keys and the gateway model (and its reset pin)
+                    // might have changed. When these can not be set up, the
+                    // previous configuration is kept.
+                    match setup_curve(&v).and_then(|_| setup_reset_pins(&v)) {
+                        Ok(_) => {
+                            config = v;
+                            pushed_config = load_state(&mut config);
+                        }
+                        Err(err) => {
+                            error!(
+                                "Reload configuration error, keeping previous configuration, error: {}",
+                                err
+                            );
+                            if let Err(err) =
+                                setup_curve(&config).and_then(|_| setup_reset_pins(&config))
+                            {
+                                error!("Restore previous configuration error, error: {}", err);
+                            }
+                        }
+                    }
+                }
+                Err(err) => error!("Reload configuration error, error: {}", err),
+            },
             Signal::Configuration(new_config) => {
-                handler::config::update_configuration(&mut config, &new_config)
-                    .expect("update configuration failed")
+                // The gateway configuration is only saved when it is valid, as
+                // it would otherwise be restored on every start.
+                match handler::config::update_configuration(&mut config, &new_config) {
+                    Ok(_) => {
+                        save_state(&config, &new_config);
+                        pushed_config = true;
+                    }
+                    Err(err) => error!("Update gateway configuration error, error: {}", err),
+                }
             }
         }
     }
 }
+
+fn setup_curve(config: &config::Configuration) -> Result<(), String> {
+    curve::setup(
+        &config.concentratord.api.curve_secret_key,
+        &config.concentratord.api.curve_allowed_clients,
+    )
+    .map_err(|e| format!("setup curve error: {}", e))
+}
+
+// Configure the concentrator reset pin of the gateway model.
+fn setup_reset_pins(config: &config::Configuration) -> Result<(), String> {
+    match config.gateway.model_config.reset_pin {
+        Some(reset_pin) => reset::setup_pins(reset_pin, config.gateway.model_config.power_en_pin)
+            .map_err(|e| format!("setup reset pin error: {}", e)),
+        None => {
+            reset::clear_pins();
+            return Ok(());
+        }
+    }
+}
+
+// Restore the gateway configuration from the state file. This returns true
+// when the gateway configuration was restored.
+fn load_state(config: &mut config::Configuration) -> bool {
+    if config.concentratord.state_file.is_empty() {
+        return false;
+    }
+
+    match state::load_configuration(&config.concentratord.state_file) {
+        Ok(Some(v)) => match handler::config::update_configuration(config, &v) {
+            Ok(_) => return true,
+            Err(err) => error!("Restore gateway configuration error, error: {}", err),
+        },
+        Ok(None) => {}
+        Err(err) => error!("Load state file error, error: {}", err),
+    }
+
+    return false;
+}
+
+fn save_state(
+    config: &config::Configuration,
+    new_config: &chirpstack_api::gw::GatewayConfiguration,
+) {
+    if config.concentratord.state_file.is_empty() {
+        return;
+    }
+
+    if let Err(err) = state::save_configuration(&config.concentratord.state_file, new_config) {
+        error!("Save state file error, error: {}", err);
+    }
+}
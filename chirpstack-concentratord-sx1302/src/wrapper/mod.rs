@@ -1,22 +1,26 @@
 use std::time::{Duration, UNIX_EPOCH};
 
-use libconcentratord::jitqueue;
+use libconcentratord::{capture, jitqueue};
 use libloragw_sx1302::hal;
 use uuid::Uuid;
 
 use super::handler::gps;
 
 #[derive(Copy, Clone)]
-pub struct TxPacket(hal::TxPacket, Uuid);
+pub struct TxPacket(hal::TxPacket, Uuid, jitqueue::Priority);
 
 impl TxPacket {
-    pub fn new(id: Uuid, tx_packet: hal::TxPacket) -> TxPacket {
-        TxPacket(tx_packet, id)
+    pub fn new(id: Uuid, tx_packet: hal::TxPacket, priority: jitqueue::Priority) -> TxPacket {
+        TxPacket(tx_packet, id, priority)
     }
 
     pub fn tx_packet(&self) -> hal::TxPacket {
         self.0
     }
+
+    pub fn id(&self) -> Uuid {
+        self.1
+    }
 }
 
 impl jitqueue::TxPacket for TxPacket {
@@ -48,6 +52,14 @@ impl jitqueue::TxPacket for TxPacket {
     fn get_id(&self) -> String {
         self.1.to_string()
     }
+
+    fn get_priority(&self) -> jitqueue::Priority {
+        self.2
+    }
+
+    fn get_frequency(&self) -> u32 {
+        self.0.freq_hz
+    }
 }
 
 pub fn uplink_to_proto(
@@ -98,7 +110,7 @@ pub fn uplink_to_proto(
                 chirpstack_api::gw::uplink_tx_info::ModulationInfo::FskModulationInfo(mod_info),
             );
         }
-        hal::Modulation::Undefined => {
+        hal::Modulation::CW | hal::Modulation::Undefined => {
             return Err("undefined modulation".to_string());
         }
     }
@@ -152,6 +164,33 @@ pub fn uplink_to_proto(
             );
         }
     }
+    if packet.ftime_received {
+        // The fine timestamp is the number of nanoseconds since the last PPS,
+        // the seconds are taken from the GPS time reference.
+        match gps::cnt2time(packet.count_us) {
+            Ok(v) => {
+                let v = v.duration_since(UNIX_EPOCH).unwrap();
+
+                rx_info.set_fine_timestamp_type(chirpstack_api::gw::FineTimestampType::Plain);
+                rx_info.fine_timestamp = Some(
+                    chirpstack_api::gw::uplink_rx_info::FineTimestamp::PlainFineTimestamp(
+                        chirpstack_api::gw::PlainFineTimestamp {
+                            time: Some(prost_types::Timestamp {
+                                seconds: v.as_secs() as i64,
+                                nanos: packet.ftime as i32,
+                            }),
+                        },
+                    ),
+                );
+            }
+            Err(err) => {
+                debug!(
+                    "Could not get GPS time for fine timestamp, uplink_id: {}, error: {}",
+                    uplink_id, err
+                );
+            }
+        }
+    }
     match gps::get_coords() {
         Some(v) => {
             let mut proto_loc = chirpstack_api::common::Location {
@@ -287,3 +326,43 @@ pub fn downlink_from_proto(
 
     return Ok(packet);
 }
+
+pub fn downlink_to_capture(gateway_id: &[u8], packet: &hal::TxPacket) -> capture::Packet {
+    capture::Packet {
+        gateway_id: gateway_id.to_vec(),
+        count_us: packet.count_us,
+        frequency: packet.freq_hz,
+        bandwidth: packet.bandwidth,
+        spreading_factor: match packet.datarate {
+            hal::DataRate::SF5 => 5,
+            hal::DataRate::SF6 => 6,
+            hal::DataRate::SF7 => 7,
+            hal::DataRate::SF8 => 8,
+            hal::DataRate::SF9 => 9,
+            hal::DataRate::SF10 => 10,
+            hal::DataRate::SF11 => 11,
+            hal::DataRate::SF12 => 12,
+            _ => 0,
+        },
+        code_rate: match packet.coderate {
+            hal::CodeRate::LoRa4_5 => "4/5",
+            hal::CodeRate::LoRa4_6 => "4/6",
+            hal::CodeRate::LoRa4_7 => "4/7",
+            hal::CodeRate::LoRa4_8 => "4/8",
+            hal::CodeRate::Undefined => "",
+        }
+        .to_string(),
+        datarate: match packet.datarate {
+            hal::DataRate::FSK(v) => v,
+            _ => 0,
+        },
+        crc_status: match packet.no_crc {
+            true => chirpstack_api::gw::CrcStatus::NoCrc,
+            false => chirpstack_api::gw::CrcStatus::CrcOk,
+        },
+        iq_inverted: packet.invert_pol,
+        rf_chain: packet.rf_chain as u32,
+        phy_payload: packet.payload[..packet.size as usize].to_vec(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,79 @@
+use handlebars::Handlebars;
+
+use super::super::config;
+
+pub fn run(config: &config::Configuration) {
+    let template = r#"
+# This configuration can be split over multiple files, which are merged in the
+# order given by the -c / --config flags. Any value can be overridden by a
+# CONCENTRATORD_<SECTION>__<KEY> environment variable, e.g.
+# CONCENTRATORD_UDP_BRIDGE__SERVER__SERVER_ADDRESS for the server address.
+
+# UDP Bridge configuration.
+#
+# The UDP Bridge forwards the uplinks and gateway stats of the Concentratord
+# to a network server using the Semtech UDP protocol (PUSH_DATA) and sends the
+# downlinks received from the network server (PULL_RESP) to the Concentratord.
+[udp_bridge]
+  # Log level.
+  #
+  # Valid options are:
+  #   * TRACE
+  #   * DEBUG
+  #   * INFO
+  #   * WARN
+  #   * ERROR
+  #   * OFF
+  log_level="{{ udp_bridge.log_level }}"
+
+  # Log to syslog.
+  #
+  # When set to true, log messages are being written to syslog instead of stdout.
+  log_to_syslog={{ udp_bridge.log_to_syslog }}
+
+
+  # Concentratord API configuration.
+  [udp_bridge.concentratord]
+    # Event API URL.
+    event_url="{{ udp_bridge.concentratord.event_url }}"
+
+    # Command API URL.
+    command_url="{{ udp_bridge.concentratord.command_url }}"
+
+    # CURVE server public key (Z85 encoded).
+    #
+    # This must be set when CURVE is enabled on the Concentratord API sockets
+    # (concentratord.api.curve_secret_key) and is the public key of that
+    # keypair. When set, the client keypair below must be set too.
+    curve_server_key="{{ udp_bridge.concentratord.curve_server_key }}"
+
+    # CURVE client public key (Z85 encoded).
+    #
+    # When the Concentratord only allows specific clients, this key must be
+    # part of its concentratord.api.curve_allowed_clients. A keypair can be
+    # generated with the keygen subcommand of the Concentratord.
+    curve_public_key="{{ udp_bridge.concentratord.curve_public_key }}"
+
+    # CURVE client secret key (Z85 encoded).
+    curve_secret_key="{{ udp_bridge.concentratord.curve_secret_key }}"
+
+
+  # Network server configuration.
+  [udp_bridge.server]
+    # Server address (hostname:port).
+    server_address="{{ udp_bridge.server.server_address }}"
+
+    # Keep-alive interval.
+    #
+    # This is the interval at which PULL_DATA packets are sent to the server,
+    # so that the server is able to send downlinks to the gateway.
+    keepalive_interval="{{ udp_bridge.server.keepalive_interval }}"
+"#;
+
+    let reg = Handlebars::new();
+    println!(
+        "{}",
+        reg.render_template(template, config)
+            .expect("render configfile error")
+    );
+}
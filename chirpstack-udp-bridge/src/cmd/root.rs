@@ -0,0 +1,95 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::thread;
+
+use super::super::{concentratord, config, handler};
+
+pub fn run(config: &config::Configuration) -> Result<(), String> {
+    info!(
+        "Starting ChirpStack UDP Bridge (version: {}, docs: {})",
+        config::VERSION,
+        "https://www.chirpstack.io/concentratord/"
+    );
+
+    // setup concentratord api
+    let zmq_ctx = zmq::Context::new();
+    let curve_keys = concentratord::CurveKeys::from_config(&config.udp_bridge.concentratord)?;
+    let mut client = concentratord::Client::connect(
+        &zmq_ctx,
+        &config.udp_bridge.concentratord.command_url,
+        &curve_keys,
+    )?;
+    let sub_sock = concentratord::get_sub_socket(
+        &zmq_ctx,
+        &config.udp_bridge.concentratord.event_url,
+        &curve_keys,
+    )?;
+
+    // get gateway id
+    let gateway_id = client
+        .command("gateway_id", b"")
+        .map_err(|e| format!("get gateway_id error: {}", e))?;
+    if gateway_id.len() != 8 {
+        return Err("gateway_id must be exactly 8 bytes".to_string());
+    }
+    info!(
+        "Received gateway ID from Concentratord, gateway_id: {}",
+        hex::encode(&gateway_id)
+    );
+
+    // setup udp socket
+    let udp_sock = get_udp_socket(&config.udp_bridge.server.server_address)?;
+
+    // setup threads
+    let mut threads: Vec<thread::JoinHandle<()>> = vec![];
+
+    threads.push(thread::spawn({
+        let gateway_id = gateway_id.clone();
+        let udp_sock = udp_sock.try_clone().map_err(|e| e.to_string())?;
+
+        move || handler::event::event_loop(&gateway_id, sub_sock, udp_sock)
+    }));
+
+    threads.push(thread::spawn({
+        let gateway_id = gateway_id.clone();
+        let udp_sock = udp_sock.try_clone().map_err(|e| e.to_string())?;
+        let keepalive_interval = config.udp_bridge.server.keepalive_interval;
+
+        move || handler::pull::pull_loop(&gateway_id, udp_sock, keepalive_interval)
+    }));
+
+    threads.push(thread::spawn({
+        let gateway_id = gateway_id.clone();
+
+        move || handler::udp::udp_loop(&gateway_id, udp_sock, client)
+    }));
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    return Ok(());
+}
+
+// Returns the UDP socket, connected to the given server address. The server
+// address is only resolved once.
+fn get_udp_socket(server_address: &str) -> Result<UdpSocket, String> {
+    let addr = server_address
+        .to_socket_addrs()
+        .map_err(|e| format!("resolve server_address error: {}", e))?
+        .next()
+        .ok_or("server_address did not resolve to an address")?;
+
+    info!(
+        "Connecting to UDP server, server_address: {}, addr: {}",
+        server_address, addr
+    );
+
+    let sock = match addr {
+        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0"),
+        SocketAddr::V6(_) => UdpSocket::bind("[::]:0"),
+    }
+    .map_err(|e| e.to_string())?;
+    sock.connect(addr).map_err(|e| e.to_string())?;
+
+    return Ok(sock);
+}
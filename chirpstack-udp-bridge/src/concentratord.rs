@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use libconcentratord::curve;
+
+use super::config;
+
+// Max. time to wait for a command response.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// CURVE client keys (decoded) for the Concentratord API sockets.
+#[derive(Clone)]
+pub struct CurveKeys {
+    server_key: Vec<u8>,
+    public_key: Vec<u8>,
+    secret_key: Vec<u8>,
+}
+
+impl CurveKeys {
+    /// Returns the CURVE keys of the given configuration, this returns None
+    /// when CURVE is not configured.
+    pub fn from_config(conf: &config::Concentratord) -> Result<Option<CurveKeys>, String> {
+        if conf.curve_server_key.is_empty() {
+            return Ok(None);
+        }
+
+        return Ok(Some(CurveKeys {
+            server_key: curve::decode_key(&conf.curve_server_key)?,
+            public_key: curve::decode_key(&conf.curve_public_key)?,
+            secret_key: curve::decode_key(&conf.curve_secret_key)?,
+        }));
+    }
+}
+
+/// Client for the Concentratord command API.
+pub struct Client {
+    zmq_ctx: zmq::Context,
+    command_url: String,
+    curve_keys: Option<CurveKeys>,
+    sock: zmq::Socket,
+}
+
+impl Client {
+    pub fn connect(
+        zmq_ctx: &zmq::Context,
+        command_url: &str,
+        curve_keys: &Option<CurveKeys>,
+    ) -> Result<Client, String> {
+        info!(
+            "Connecting to Concentratord command API, command_url: {}",
+            command_url
+        );
+
+        Ok(Client {
+            zmq_ctx: zmq_ctx.clone(),
+            command_url: command_url.to_string(),
+            curve_keys: curve_keys.clone(),
+            sock: get_req_socket(zmq_ctx, command_url, curve_keys)?,
+        })
+    }
+
+    /// Send the given command and return the response. An empty response
+    /// means that the Concentratord could not handle the command.
+    pub fn command(&mut self, command: &str, pl: &[u8]) -> Result<Vec<u8>, String> {
+        self.sock
+            .send(command, zmq::SNDMORE)
+            .map_err(|e| e.to_string())?;
+        self.sock.send(pl, 0).map_err(|e| e.to_string())?;
+
+        // set poller so that we can timeout
+        let mut items = [self.sock.as_poll_item(zmq::POLLIN)];
+        zmq::poll(&mut items, COMMAND_TIMEOUT.as_millis() as i64).map_err(|e| e.to_string())?;
+        if !items[0].is_readable() {
+            // A REQ socket can not send a new request before it received the
+            // response of the previous request, therefore it is re-created.
+            self.sock = get_req_socket(&self.zmq_ctx, &self.command_url, &self.curve_keys)?;
+            return Err(format!("{} command timeout", command));
+        }
+
+        self.sock.recv_bytes(0).map_err(|e| e.to_string())
+    }
+}
+
+/// Returns a socket subscribed to all events of the Concentratord.
+pub fn get_sub_socket(
+    zmq_ctx: &zmq::Context,
+    event_url: &str,
+    curve_keys: &Option<CurveKeys>,
+) -> Result<zmq::Socket, String> {
+    info!(
+        "Connecting to Concentratord event API, event_url: {}",
+        event_url
+    );
+
+    let sock = zmq_ctx.socket(zmq::SUB).map_err(|e| e.to_string())?;
+    configure_socket(&sock, curve_keys)?;
+    sock.connect(event_url).map_err(|e| e.to_string())?;
+    sock.set_subscribe(b"").map_err(|e| e.to_string())?;
+
+    return Ok(sock);
+}
+
+fn get_req_socket(
+    zmq_ctx: &zmq::Context,
+    command_url: &str,
+    curve_keys: &Option<CurveKeys>,
+) -> Result<zmq::Socket, String> {
+    let sock = zmq_ctx.socket(zmq::REQ).map_err(|e| e.to_string())?;
+    configure_socket(&sock, curve_keys)?;
+    sock.connect(command_url).map_err(|e| e.to_string())?;
+
+    // do not block on close when the Concentratord did not respond
+    sock.set_linger(0).map_err(|e| e.to_string())?;
+
+    return Ok(sock);
+}
+
+// Configure the given (client) socket for CURVE, when configured. This must be
+// called before connecting the socket.
+fn configure_socket(sock: &zmq::Socket, curve_keys: &Option<CurveKeys>) -> Result<(), String> {
+    if let Some(keys) = curve_keys {
+        sock.set_curve_serverkey(&keys.server_key)
+            .map_err(|e| e.to_string())?;
+        sock.set_curve_publickey(&keys.public_key)
+            .map_err(|e| e.to_string())?;
+        sock.set_curve_secretkey(&keys.secret_key)
+            .map_err(|e| e.to_string())?;
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_curve_command() {
+        let (server_public, server_secret) = curve::generate_keypair().unwrap();
+        let (client_public, client_secret) = curve::generate_keypair().unwrap();
+
+        let zmq_ctx = zmq::Context::new();
+        let rep_sock = zmq_ctx.socket(zmq::REP).unwrap();
+        rep_sock.set_curve_server(true).unwrap();
+        rep_sock
+            .set_curve_secretkey(&curve::decode_key(&server_secret).unwrap())
+            .unwrap();
+        rep_sock.bind("tcp://127.0.0.1:*").unwrap();
+        let command_url = rep_sock.get_last_endpoint().unwrap().unwrap();
+
+        let server = thread::spawn(move || {
+            let msg = rep_sock.recv_multipart(0).unwrap();
+            rep_sock.send("pong", 0).unwrap();
+            msg
+        });
+
+        let curve_keys = CurveKeys::from_config(&config::Concentratord {
+            event_url: "".to_string(),
+            command_url: command_url.clone(),
+            curve_server_key: server_public,
+            curve_public_key: client_public,
+            curve_secret_key: client_secret,
+        })
+        .unwrap();
+        assert!(curve_keys.is_some());
+
+        let mut client = Client::connect(&zmq_ctx, &command_url, &curve_keys).unwrap();
+        let resp = client.command("ping", b"").unwrap();
+        assert_eq!(b"pong".to_vec(), resp);
+        assert_eq!(vec![b"ping".to_vec(), b"".to_vec()], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_curve_keys_disabled() {
+        let curve_keys = CurveKeys::from_config(&config::Concentratord {
+            event_url: "".to_string(),
+            command_url: "".to_string(),
+            curve_server_key: "".to_string(),
+            curve_public_key: "".to_string(),
+            curve_secret_key: "".to_string(),
+        })
+        .unwrap();
+        assert!(curve_keys.is_none());
+    }
+}
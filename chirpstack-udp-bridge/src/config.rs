@@ -0,0 +1,111 @@
+use std::process;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use libconcentratord::config::Error;
+
+pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default = "example_configuration")]
+pub struct Configuration {
+    pub udp_bridge: UdpBridge,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct UdpBridge {
+    pub log_level: String,
+    #[serde(default)]
+    pub log_to_syslog: bool,
+    pub concentratord: Concentratord,
+    pub server: Server,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Concentratord {
+    pub event_url: String,
+    pub command_url: String,
+    #[serde(default)]
+    pub curve_server_key: String,
+    #[serde(default)]
+    pub curve_public_key: String,
+    #[serde(default)]
+    pub curve_secret_key: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Server {
+    pub server_address: String,
+    #[serde(with = "humantime_serde")]
+    pub keepalive_interval: Duration,
+}
+
+fn example_configuration() -> Configuration {
+    Configuration {
+        udp_bridge: UdpBridge {
+            log_level: "INFO".to_string(),
+            log_to_syslog: false,
+            concentratord: Concentratord {
+                event_url: "ipc:///tmp/concentratord_event".to_string(),
+                command_url: "ipc:///tmp/concentratord_command".to_string(),
+                curve_server_key: "".to_string(),
+                curve_public_key: "".to_string(),
+                curve_secret_key: "".to_string(),
+            },
+            server: Server {
+                server_address: "localhost:1700".to_string(),
+                keepalive_interval: Duration::from_secs(10),
+            },
+        },
+    }
+}
+
+pub fn get(filenames: Vec<String>) -> Configuration {
+    match try_get(&filenames) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Load configuration error, error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Read and validate the given configuration files.
+pub fn try_get(filenames: &[String]) -> Result<Configuration, Error> {
+    let config: Configuration = libconcentratord::config::parse(filenames)?;
+
+    if config.udp_bridge.server.server_address.is_empty() {
+        return Err(Error::Value {
+            location: "udp_bridge.server.server_address".to_string(),
+            error: "server_address must be set".to_string(),
+        });
+    }
+
+    // validate CURVE keys, these must all be set when CURVE is used
+    let concentratord = &config.udp_bridge.concentratord;
+    if !concentratord.curve_server_key.is_empty()
+        || !concentratord.curve_public_key.is_empty()
+        || !concentratord.curve_secret_key.is_empty()
+    {
+        for (name, key) in &[
+            ("curve_server_key", &concentratord.curve_server_key),
+            ("curve_public_key", &concentratord.curve_public_key),
+            ("curve_secret_key", &concentratord.curve_secret_key),
+        ] {
+            libconcentratord::curve::decode_key(key).map_err(|e| Error::Value {
+                location: format!("udp_bridge.concentratord.{}", name),
+                error: e,
+            })?;
+        }
+    }
+
+    if config.udp_bridge.server.keepalive_interval == Duration::from_secs(0) {
+        return Err(Error::Value {
+            location: "udp_bridge.server.keepalive_interval".to_string(),
+            error: "keepalive_interval must be greater than 0".to_string(),
+        });
+    }
+
+    Ok(config)
+}
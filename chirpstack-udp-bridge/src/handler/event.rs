@@ -0,0 +1,140 @@
+use std::net::UdpSocket;
+
+use libconcentratord::proto;
+use prost::Message;
+use uuid::Uuid;
+
+use super::super::{packets, structs};
+use super::stats;
+
+pub fn event_loop(gateway_id: &[u8], sub_sock: zmq::Socket, udp_sock: UdpSocket) {
+    debug!("Starting event loop");
+
+    loop {
+        let msg = match sub_sock.recv_multipart(0) {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Receive event error, error: {}", err);
+                continue;
+            }
+        };
+
+        if msg.len() != 2 {
+            warn!("Event must have two frames, frames: {}", msg.len());
+            continue;
+        }
+
+        let event = String::from_utf8_lossy(&msg[0]).to_string();
+        let res = match event.as_ref() {
+            "up" => handle_uplink(gateway_id, &udp_sock, &msg[1]),
+            "stats" => handle_stats(gateway_id, &udp_sock, &msg[1]),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = res {
+            error!("Handle event error, event: {}, error: {}", event, err);
+        }
+    }
+}
+
+fn handle_uplink(gateway_id: &[u8], udp_sock: &UdpSocket, b: &[u8]) -> Result<(), String> {
+    let pl = chirpstack_api::gw::UplinkFrame::decode(b).map_err(|e| e.to_string())?;
+    let uplink_id = match pl.rx_info.as_ref() {
+        Some(v) => Uuid::from_slice(&v.uplink_id).unwrap_or_default(),
+        None => Uuid::nil(),
+    };
+
+    let push_data = structs::PushData {
+        rxpk: vec![structs::rxpk_from_proto(&pl)?],
+        ..Default::default()
+    };
+
+    let token = packets::get_random_token();
+    info!(
+        "Sending uplink as PUSH_DATA, uplink_id: {}, token: {}",
+        uplink_id, token
+    );
+
+    udp_sock
+        .send(&packets::push_data(token, gateway_id, &push_data))
+        .map_err(|e| e.to_string())?;
+    stats::inc_rx_forwarded();
+    stats::inc_push_data_sent();
+
+    return Ok(());
+}
+
+fn handle_stats(gateway_id: &[u8], udp_sock: &UdpSocket, b: &[u8]) -> Result<(), String> {
+    let pl = proto::GatewayStats::decode(b).map_err(|e| e.to_string())?;
+    let (rxfw, ackr) = stats::export();
+
+    let push_data = structs::PushData {
+        stat: Some(structs::stat_from_proto(&pl, rxfw, ackr)),
+        ..Default::default()
+    };
+
+    let token = packets::get_random_token();
+    info!("Sending stats as PUSH_DATA, token: {}", token);
+
+    udp_sock
+        .send(&packets::push_data(token, gateway_id, &push_data))
+        .map_err(|e| e.to_string())?;
+    stats::inc_push_data_sent();
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_uplink() {
+        // local stand-in for the network server
+        let server_sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let udp_sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        udp_sock.connect(server_sock.local_addr().unwrap()).unwrap();
+
+        let mut pl = chirpstack_api::gw::UplinkFrame {
+            phy_payload: vec![1, 2, 3],
+            tx_info: Some(chirpstack_api::gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation_info: Some(
+                    chirpstack_api::gw::uplink_tx_info::ModulationInfo::LoraModulationInfo(
+                        chirpstack_api::gw::LoRaModulationInfo {
+                            bandwidth: 125000,
+                            spreading_factor: 12,
+                            code_rate: "4/5".to_string(),
+                            polarization_inversion: false,
+                        },
+                    ),
+                ),
+                ..Default::default()
+            }),
+            rx_info: Some(chirpstack_api::gw::UplinkRxInfo {
+                context: vec![0, 0, 0, 1],
+                ..Default::default()
+            }),
+        };
+        pl.rx_info
+            .as_mut()
+            .unwrap()
+            .set_crc_status(chirpstack_api::gw::CrcStatus::CrcOk);
+
+        let mut b = Vec::new();
+        pl.encode(&mut b).unwrap();
+        handle_uplink(&[1, 2, 3, 4, 5, 6, 7, 8], &udp_sock, &b).unwrap();
+
+        let mut buf = [0; 1024];
+        let size = server_sock.recv(&mut buf).unwrap();
+
+        // version, token, PUSH_DATA, gateway id
+        assert_eq!(2, buf[0]);
+        assert_eq!(0, buf[3]);
+        assert_eq!([1, 2, 3, 4, 5, 6, 7, 8], buf[4..12]);
+
+        let push_data: structs::PushData = serde_json::from_slice(&buf[12..size]).unwrap();
+        assert_eq!(vec![structs::rxpk_from_proto(&pl).unwrap()], push_data.rxpk);
+        assert_eq!(None, push_data.stat);
+    }
+}
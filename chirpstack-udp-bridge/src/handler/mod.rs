@@ -0,0 +1,4 @@
+pub mod event;
+pub mod pull;
+pub mod stats;
+pub mod udp;
@@ -0,0 +1,22 @@
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+
+use super::super::packets;
+
+/// Periodically send a PULL_DATA packet, so that the server knows where to
+/// send the downlinks to (e.g. through NAT).
+pub fn pull_loop(gateway_id: &[u8], udp_sock: UdpSocket, keepalive_interval: Duration) {
+    debug!("Starting PULL_DATA loop");
+
+    loop {
+        let token = packets::get_random_token();
+        debug!("Sending PULL_DATA, token: {}", token);
+
+        if let Err(err) = udp_sock.send(&packets::pull_data(token, gateway_id)) {
+            error!("Send PULL_DATA error, error: {}", err);
+        }
+
+        thread::sleep(keepalive_interval);
+    }
+}
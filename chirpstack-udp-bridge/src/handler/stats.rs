@@ -0,0 +1,41 @@
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref COUNTERS: Mutex<Counters> = Mutex::new(Default::default());
+}
+
+#[derive(Default)]
+struct Counters {
+    rx_forwarded: u32,
+    push_data_sent: u32,
+    push_ack_received: u32,
+}
+
+pub fn inc_rx_forwarded() {
+    COUNTERS.lock().unwrap().rx_forwarded += 1;
+}
+
+pub fn inc_push_data_sent() {
+    COUNTERS.lock().unwrap().push_data_sent += 1;
+}
+
+pub fn inc_push_ack_received() {
+    COUNTERS.lock().unwrap().push_ack_received += 1;
+}
+
+/// Returns the number of forwarded uplinks and the percentage of
+/// acknowledged PUSH_DATA packets since the previous call and resets the
+/// counters.
+pub fn export() -> (u32, f64) {
+    let mut counters = COUNTERS.lock().unwrap();
+
+    let ackr = match counters.push_data_sent {
+        0 => 0.0,
+        sent => 100.0 * (counters.push_ack_received.min(sent) as f64) / (sent as f64),
+    };
+    let rxfw = counters.rx_forwarded;
+
+    *counters = Default::default();
+
+    return (rxfw, ackr);
+}
@@ -0,0 +1,96 @@
+use std::net::UdpSocket;
+
+use prost::Message;
+use uuid::Uuid;
+
+use super::super::{concentratord, packets, structs};
+use super::stats;
+
+pub fn udp_loop(gateway_id: &[u8], udp_sock: UdpSocket, mut client: concentratord::Client) {
+    debug!("Starting UDP receive loop");
+
+    let mut buf = [0; 65535];
+
+    loop {
+        let size = match udp_sock.recv(&mut buf) {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Receive UDP packet error, error: {}", err);
+                continue;
+            }
+        };
+
+        let packet = match packets::ServerPacket::decode(&buf[..size]) {
+            Ok(v) => v,
+            Err(err) => {
+                warn!("Decode UDP packet error, error: {}", err);
+                continue;
+            }
+        };
+
+        match packet.packet_type {
+            packets::PacketType::PushAck => {
+                debug!("Received PUSH_ACK, token: {}", packet.token);
+                stats::inc_push_ack_received();
+            }
+            packets::PacketType::PullAck => {
+                debug!("Received PULL_ACK, token: {}", packet.token);
+            }
+            packets::PacketType::PullResp => {
+                let tx_ack = match handle_pull_resp(gateway_id, &mut client, &packet) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!(
+                            "Handle PULL_RESP error, token: {}, error: {}",
+                            packet.token, err
+                        );
+                        structs::TxAck {
+                            txpk_ack: structs::TxPkAck {
+                                error: "INTERNAL_ERROR".to_string(),
+                            },
+                        }
+                    }
+                };
+
+                if let Err(err) = udp_sock.send(&packets::tx_ack(packet.token, gateway_id, &tx_ack))
+                {
+                    error!("Send TX_ACK error, error: {}", err);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn handle_pull_resp(
+    gateway_id: &[u8],
+    client: &mut concentratord::Client,
+    packet: &packets::ServerPacket,
+) -> Result<structs::TxAck, String> {
+    let pull_resp = packet.pull_resp()?;
+    let pl = structs::downlink_from_txpk(gateway_id, &pull_resp.txpk)?;
+    let downlink_id = Uuid::from_slice(&pl.downlink_id).unwrap();
+
+    info!(
+        "Received PULL_RESP, sending downlink to Concentratord, token: {}, downlink_id: {}",
+        packet.token, downlink_id
+    );
+
+    let mut b = Vec::new();
+    pl.encode(&mut b).map_err(|e| e.to_string())?;
+
+    let resp = client.command("down", &b)?;
+    if resp.is_empty() {
+        return Err("downlink was rejected by the Concentratord".to_string());
+    }
+
+    let tx_ack = chirpstack_api::gw::DownlinkTxAck::decode(&resp[..]).map_err(|e| e.to_string())?;
+    let tx_ack = structs::tx_ack_from_proto(&tx_ack);
+
+    info!(
+        "Sending TX_ACK, token: {}, downlink_id: {}, error: {}",
+        packet.token, downlink_id, tx_ack.txpk_ack.error
+    );
+
+    return Ok(tx_ack);
+}
@@ -0,0 +1,101 @@
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate lazy_static;
+extern crate simple_logger;
+extern crate syslog;
+
+use std::process;
+use std::str::FromStr;
+use std::thread;
+
+use clap::{App, Arg};
+use signal_hook::consts::signal::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use simple_logger::SimpleLogger;
+use syslog::{BasicLogger, Facility, Formatter3164};
+
+mod cmd;
+mod concentratord;
+mod config;
+mod handler;
+mod packets;
+mod structs;
+
+fn main() {
+    let matches = App::new("chirpstack-udp-bridge")
+        .version(config::VERSION)
+        .author("Orne Brocaar <info@brocaar.com>")
+        .about("Semtech UDP protocol bridge for the Concentratord")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Path to configuration file or directory (*.toml files, in lexical order)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("debug-config")
+                .long("debug-config")
+                .help("Print which configuration file or environment variable set each value"),
+        )
+        .subcommand(App::new("configfile").about("Print the configuration template"))
+        .get_matches();
+
+    let config_files = matches.values_of_lossy("config").unwrap_or(vec![]);
+
+    if matches.is_present("debug-config") {
+        libconcentratord::config::print_origins(&config_files);
+    }
+
+    let config = config::get(config_files);
+
+    if let Some(_) = matches.subcommand_matches("configfile") {
+        cmd::configfile::run(&config);
+        process::exit(0);
+    }
+
+    if config.udp_bridge.log_to_syslog {
+        let formatter = Formatter3164 {
+            facility: Facility::LOG_USER,
+            hostname: None,
+            process: "chirpstack-udp-bridge".into(),
+            pid: process::id() as i32,
+        };
+        let logger = syslog::unix(formatter).expect("could not connect to syslog");
+        log::set_boxed_logger(Box::new(BasicLogger::new(logger)))
+            .map(|()| {
+                log::set_max_level(
+                    log::Level::from_str(&config.udp_bridge.log_level)
+                        .unwrap()
+                        .to_level_filter(),
+                )
+            })
+            .unwrap();
+    } else {
+        SimpleLogger::new()
+            .with_level(
+                log::Level::from_str(&config.udp_bridge.log_level)
+                    .unwrap()
+                    .to_level_filter(),
+            )
+            .init()
+            .unwrap();
+    }
+
+    let mut signals = Signals::new(&[SIGINT, SIGTERM]).expect("error registering channels");
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            warn!("Received stop signal, stopping UDP Bridge");
+            process::exit(0);
+        }
+    });
+
+    if let Err(err) = cmd::root::run(&config) {
+        error!("UDP Bridge error, error: {}", err);
+        process::exit(1);
+    }
+}
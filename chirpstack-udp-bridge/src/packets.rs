@@ -0,0 +1,191 @@
+use uuid::Uuid;
+
+use super::structs;
+
+// Semtech UDP protocol (GWMP) version, see PROTOCOL.TXT of the Semtech
+// packet forwarder.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PacketType {
+    PushData,
+    PushAck,
+    PullData,
+    PullResp,
+    PullAck,
+    TxAck,
+}
+
+impl PacketType {
+    fn from_u8(v: u8) -> Result<PacketType, String> {
+        Ok(match v {
+            0x00 => PacketType::PushData,
+            0x01 => PacketType::PushAck,
+            0x02 => PacketType::PullData,
+            0x03 => PacketType::PullResp,
+            0x04 => PacketType::PullAck,
+            0x05 => PacketType::TxAck,
+            _ => return Err(format!("unexpected packet type: {}", v)),
+        })
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            PacketType::PushData => 0x00,
+            PacketType::PushAck => 0x01,
+            PacketType::PullData => 0x02,
+            PacketType::PullResp => 0x03,
+            PacketType::PullAck => 0x04,
+            PacketType::TxAck => 0x05,
+        }
+    }
+}
+
+/// Packet received from the network server.
+#[derive(Debug, PartialEq)]
+pub struct ServerPacket {
+    pub packet_type: PacketType,
+    pub token: u16,
+    pub payload: Vec<u8>,
+}
+
+impl ServerPacket {
+    /// Decode the given packet. The PUSH_ACK, PULL_ACK and PULL_RESP packets
+    /// only have a 4 byte header (version, token and type), the remaining
+    /// bytes are returned as payload.
+    pub fn decode(b: &[u8]) -> Result<ServerPacket, String> {
+        if b.len() < 4 {
+            return Err("at least 4 bytes expected".to_string());
+        }
+
+        // Protocol version 1 only differs in the (missing) TX_ACK, which is
+        // always sent by the bridge.
+        if b[0] != 1 && b[0] != PROTOCOL_VERSION {
+            return Err(format!("unexpected protocol version: {}", b[0]));
+        }
+
+        let packet_type = PacketType::from_u8(b[3])?;
+        match packet_type {
+            PacketType::PushAck | PacketType::PullAck | PacketType::PullResp => {}
+            _ => {
+                return Err(format!(
+                    "unexpected packet type from server: {:?}",
+                    packet_type
+                ))
+            }
+        }
+
+        Ok(ServerPacket {
+            packet_type: packet_type,
+            token: u16::from_be_bytes([b[1], b[2]]),
+            payload: b[4..].to_vec(),
+        })
+    }
+
+    /// Returns the (decoded) PULL_RESP payload.
+    pub fn pull_resp(&self) -> Result<structs::PullResp, String> {
+        serde_json::from_slice(&self.payload).map_err(|e| e.to_string())
+    }
+}
+
+/// Returns the PUSH_DATA packet for the given payload.
+pub fn push_data(token: u16, gateway_id: &[u8], pl: &structs::PushData) -> Vec<u8> {
+    let mut b = get_header(PacketType::PushData, token, gateway_id);
+    b.extend(serde_json::to_vec(pl).unwrap());
+    b
+}
+
+/// Returns the PULL_DATA (keep-alive) packet.
+pub fn pull_data(token: u16, gateway_id: &[u8]) -> Vec<u8> {
+    get_header(PacketType::PullData, token, gateway_id)
+}
+
+/// Returns the TX_ACK packet for the given payload. The token must be the
+/// token of the PULL_RESP packet.
+pub fn tx_ack(token: u16, gateway_id: &[u8], pl: &structs::TxAck) -> Vec<u8> {
+    let mut b = get_header(PacketType::TxAck, token, gateway_id);
+    b.extend(serde_json::to_vec(pl).unwrap());
+    b
+}
+
+/// Returns a random token.
+pub fn get_random_token() -> u16 {
+    let b = Uuid::new_v4();
+    let b = b.as_bytes();
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+// The packets sent by the gateway have a 12 byte header (version, token, type
+// and gateway ID).
+fn get_header(packet_type: PacketType, token: u16, gateway_id: &[u8]) -> Vec<u8> {
+    let mut b = vec![PROTOCOL_VERSION];
+    b.extend_from_slice(&token.to_be_bytes());
+    b.push(packet_type.to_u8());
+    b.extend_from_slice(gateway_id);
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pull_data() {
+        assert_eq!(
+            vec![2, 0x12, 0x34, 0x02, 1, 2, 3, 4, 5, 6, 7, 8],
+            pull_data(0x1234, &[1, 2, 3, 4, 5, 6, 7, 8])
+        );
+    }
+
+    #[test]
+    fn test_push_data() {
+        let b = push_data(
+            0x1234,
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+            &structs::PushData {
+                rxpk: vec![],
+                stat: None,
+            },
+        );
+        assert_eq!(vec![2, 0x12, 0x34, 0x00, 1, 2, 3, 4, 5, 6, 7, 8], b[..12]);
+        assert_eq!(b"{}".to_vec(), b[12..]);
+    }
+
+    #[test]
+    fn test_server_packet_decode() {
+        assert!(ServerPacket::decode(&[2, 0x12, 0x34]).is_err());
+        assert!(ServerPacket::decode(&[3, 0x12, 0x34, 0x01]).is_err());
+        assert!(ServerPacket::decode(&[2, 0x12, 0x34, 0x00]).is_err());
+
+        assert_eq!(
+            ServerPacket {
+                packet_type: PacketType::PushAck,
+                token: 0x1234,
+                payload: vec![],
+            },
+            ServerPacket::decode(&[2, 0x12, 0x34, 0x01]).unwrap()
+        );
+
+        let mut b = vec![2, 0x12, 0x34, 0x03];
+        b.extend_from_slice(
+            br#"{"txpk":{"imme":true,"freq":868.1,"powe":14,"modu":"LORA","datr":"SF7BW125","codr":"4/5","ipol":true,"size":3,"data":"AQID"}}"#,
+        );
+        let p = ServerPacket::decode(&b).unwrap();
+        assert_eq!(PacketType::PullResp, p.packet_type);
+        assert_eq!(
+            structs::TxPk {
+                imme: true,
+                freq: 868.1,
+                powe: 14,
+                modu: "LORA".to_string(),
+                datr: structs::DataRate::LoRa("SF7BW125".to_string()),
+                codr: Some("4/5".to_string()),
+                ipol: true,
+                size: 3,
+                data: "AQID".to_string(),
+                ..Default::default()
+            },
+            p.pull_resp().unwrap().txpk
+        );
+    }
+}
@@ -0,0 +1,553 @@
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use libconcentratord::proto;
+
+/// PUSH_DATA payload.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct PushData {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rxpk: Vec<RxPk>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stat: Option<Stat>,
+}
+
+/// Received packet.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct RxPk {
+    /// UTC time of the packet (ISO 8601 compact format).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// GPS time of the packet (milliseconds since GPS epoch).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmms: Option<u64>,
+    /// Internal timestamp of the concentrator counter (microseconds).
+    pub tmst: u32,
+    /// Frequency (MHz).
+    pub freq: f64,
+    /// IF channel.
+    pub chan: u32,
+    /// RF chain.
+    pub rfch: u32,
+    /// Board.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub brd: u32,
+    /// CRC status (1 = OK, -1 = fail, 0 = no CRC).
+    pub stat: i8,
+    /// Modulation (LORA or FSK).
+    pub modu: String,
+    /// Datarate.
+    pub datr: DataRate,
+    /// LoRa coding-rate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codr: Option<String>,
+    /// RSSI (dBm).
+    pub rssi: i32,
+    /// LoRa SNR (dB).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsnr: Option<f64>,
+    /// Payload size (bytes).
+    pub size: u16,
+    /// Base64 encoded payload.
+    pub data: String,
+}
+
+/// Datarate, this is a string for LoRa (e.g. SF7BW125) and the bitrate for
+/// FSK.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum DataRate {
+    LoRa(String),
+    FSK(u32),
+}
+
+impl Default for DataRate {
+    fn default() -> Self {
+        DataRate::LoRa("".to_string())
+    }
+}
+
+/// Gateway status.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct Stat {
+    /// UTC system time of the gateway.
+    pub time: String,
+    /// Latitude.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lati: Option<f64>,
+    /// Longitude.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long: Option<f64>,
+    /// Altitude (meters).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alti: Option<i32>,
+    /// Number of radio packets received.
+    pub rxnb: u32,
+    /// Number of radio packets received with a valid PHY CRC.
+    pub rxok: u32,
+    /// Number of radio packets forwarded.
+    pub rxfw: u32,
+    /// Percentage of upstream datagrams that were acknowledged.
+    pub ackr: f64,
+    /// Number of downlink datagrams received.
+    pub dwnb: u32,
+    /// Number of packets emitted.
+    pub txnb: u32,
+}
+
+/// PULL_RESP payload.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct PullResp {
+    pub txpk: TxPk,
+}
+
+/// Packet to transmit.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct TxPk {
+    /// Send the packet immediately.
+    #[serde(default)]
+    pub imme: bool,
+    /// Send the packet on the given concentrator counter value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmst: Option<u32>,
+    /// Send the packet at the given GPS time (milliseconds since GPS epoch).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmms: Option<u64>,
+    /// Frequency (MHz).
+    pub freq: f64,
+    /// RF chain.
+    #[serde(default)]
+    pub rfch: u32,
+    /// TX power (dBm).
+    #[serde(default)]
+    pub powe: i32,
+    /// Modulation (LORA or FSK).
+    pub modu: String,
+    /// Datarate.
+    pub datr: DataRate,
+    /// LoRa coding-rate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codr: Option<String>,
+    /// FSK frequency deviation (Hz).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fdev: Option<u32>,
+    /// LoRa polarization inversion.
+    #[serde(default)]
+    pub ipol: bool,
+    /// Preamble size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prea: Option<u16>,
+    /// Payload size (bytes).
+    pub size: u16,
+    /// Base64 encoded payload.
+    pub data: String,
+    /// Disable the CRC.
+    #[serde(default)]
+    pub ncrc: bool,
+    /// Board.
+    #[serde(default)]
+    pub brd: u32,
+    /// Antenna.
+    #[serde(default)]
+    pub ant: u32,
+}
+
+/// TX_ACK payload.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct TxAck {
+    pub txpk_ack: TxPkAck,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct TxPkAck {
+    pub error: String,
+}
+
+/// Returns the rxpk for the given uplink.
+pub fn rxpk_from_proto(pl: &chirpstack_api::gw::UplinkFrame) -> Result<RxPk, String> {
+    let tx_info = match pl.tx_info.as_ref() {
+        Some(v) => v,
+        None => return Err("tx_info must not be blank".to_string()),
+    };
+    let rx_info = match pl.rx_info.as_ref() {
+        Some(v) => v,
+        None => return Err("rx_info must not be blank".to_string()),
+    };
+
+    // the context contains the concentrator counter
+    if rx_info.context.len() != 4 {
+        return Err("context must be exactly 4 bytes".to_string());
+    }
+    let mut tmst = [0; 4];
+    tmst.copy_from_slice(&rx_info.context);
+
+    let mut rxpk = RxPk {
+        time: rx_info
+            .time
+            .as_ref()
+            .and_then(|v| Utc.timestamp_opt(v.seconds, v.nanos as u32).single())
+            .map(|v| v.to_rfc3339_opts(SecondsFormat::Micros, true)),
+        tmms: rx_info
+            .time_since_gps_epoch
+            .as_ref()
+            .map(|v| v.seconds as u64 * 1000 + v.nanos as u64 / 1_000_000),
+        tmst: u32::from_be_bytes(tmst),
+        freq: tx_info.frequency as f64 / 1_000_000.0,
+        chan: rx_info.channel,
+        rfch: rx_info.rf_chain,
+        brd: rx_info.board,
+        stat: match rx_info.crc_status() {
+            chirpstack_api::gw::CrcStatus::CrcOk => 1,
+            chirpstack_api::gw::CrcStatus::BadCrc => -1,
+            chirpstack_api::gw::CrcStatus::NoCrc => 0,
+        },
+        rssi: rx_info.rssi,
+        size: pl.phy_payload.len() as u16,
+        data: base64::encode(&pl.phy_payload),
+        ..Default::default()
+    };
+
+    match &tx_info.modulation_info {
+        Some(chirpstack_api::gw::uplink_tx_info::ModulationInfo::LoraModulationInfo(v)) => {
+            rxpk.modu = "LORA".to_string();
+            rxpk.datr = DataRate::LoRa(format!("SF{}BW{}", v.spreading_factor, v.bandwidth / 1000));
+            rxpk.codr = Some(v.code_rate.clone());
+            rxpk.lsnr = Some(rx_info.lora_snr);
+        }
+        Some(chirpstack_api::gw::uplink_tx_info::ModulationInfo::FskModulationInfo(v)) => {
+            rxpk.modu = "FSK".to_string();
+            rxpk.datr = DataRate::FSK(v.datarate);
+        }
+        None => return Err("modulation_info must not be blank".to_string()),
+    }
+
+    return Ok(rxpk);
+}
+
+/// Returns the stat for the given gateway stats. The number of forwarded
+/// uplinks and the acknowledged datagrams percentage are tracked by the
+/// bridge.
+pub fn stat_from_proto(pl: &proto::GatewayStats, rxfw: u32, ackr: f64) -> Stat {
+    let time: DateTime<Utc> = pl
+        .time
+        .as_ref()
+        .and_then(|v| Utc.timestamp_opt(v.seconds, v.nanos as u32).single())
+        .unwrap_or_else(Utc::now);
+
+    Stat {
+        time: time.format("%Y-%m-%d %H:%M:%S GMT").to_string(),
+        lati: pl.location.as_ref().map(|v| v.latitude),
+        long: pl.location.as_ref().map(|v| v.longitude),
+        alti: pl.location.as_ref().map(|v| v.altitude.round() as i32),
+        rxnb: pl.rx_packets_received,
+        rxok: pl.rx_packets_received_ok,
+        rxfw: rxfw,
+        ackr: ackr,
+        dwnb: pl.tx_packets_received,
+        txnb: pl.tx_packets_emitted,
+    }
+}
+
+/// Returns the downlink for the given txpk, which can be sent to the
+/// Concentratord using the down command.
+pub fn downlink_from_txpk(
+    gateway_id: &[u8],
+    txpk: &TxPk,
+) -> Result<chirpstack_api::gw::DownlinkFrame, String> {
+    let phy_payload =
+        base64::decode(&txpk.data).map_err(|e| format!("decode data error: {}", e))?;
+    if phy_payload.len() != txpk.size as usize {
+        return Err("size does not match the data length".to_string());
+    }
+
+    let mut tx_info = chirpstack_api::gw::DownlinkTxInfo {
+        gateway_id: gateway_id.to_vec(),
+        frequency: (txpk.freq * 1_000_000.0).round() as u32,
+        power: txpk.powe,
+        board: txpk.brd,
+        antenna: txpk.ant,
+        ..Default::default()
+    };
+
+    match (txpk.modu.as_ref(), &txpk.datr) {
+        ("LORA", DataRate::LoRa(datr)) => {
+            let (spreading_factor, bandwidth) = parse_lora_datarate(datr)?;
+
+            tx_info.set_modulation(chirpstack_api::common::Modulation::Lora);
+            tx_info.modulation_info = Some(
+                chirpstack_api::gw::downlink_tx_info::ModulationInfo::LoraModulationInfo(
+                    chirpstack_api::gw::LoRaModulationInfo {
+                        bandwidth: bandwidth,
+                        spreading_factor: spreading_factor,
+                        code_rate: txpk.codr.clone().unwrap_or_default(),
+                        polarization_inversion: txpk.ipol,
+                    },
+                ),
+            );
+        }
+        ("FSK", DataRate::FSK(datr)) => {
+            tx_info.set_modulation(chirpstack_api::common::Modulation::Fsk);
+            tx_info.modulation_info = Some(
+                chirpstack_api::gw::downlink_tx_info::ModulationInfo::FskModulationInfo(
+                    chirpstack_api::gw::FskModulationInfo {
+                        datarate: *datr,
+                        frequency_deviation: txpk.fdev.unwrap_or_default(),
+                    },
+                ),
+            );
+        }
+        _ => {
+            return Err(format!(
+                "unexpected modulation and datarate, modu: {}, datr: {:?}",
+                txpk.modu, txpk.datr
+            ))
+        }
+    }
+
+    if txpk.imme {
+        tx_info.set_timing(chirpstack_api::gw::DownlinkTiming::Immediately);
+        tx_info.timing_info = Some(
+            chirpstack_api::gw::downlink_tx_info::TimingInfo::ImmediatelyTimingInfo(
+                chirpstack_api::gw::ImmediatelyTimingInfo {},
+            ),
+        );
+    } else if let Some(tmms) = txpk.tmms {
+        tx_info.set_timing(chirpstack_api::gw::DownlinkTiming::GpsEpoch);
+        tx_info.timing_info = Some(
+            chirpstack_api::gw::downlink_tx_info::TimingInfo::GpsEpochTimingInfo(
+                chirpstack_api::gw::GpsEpochTimingInfo {
+                    time_since_gps_epoch: Some(prost_types::Duration {
+                        seconds: (tmms / 1000) as i64,
+                        nanos: ((tmms % 1000) * 1_000_000) as i32,
+                    }),
+                },
+            ),
+        );
+    } else if let Some(tmst) = txpk.tmst {
+        // The Concentratord adds the delay to the concentrator counter in
+        // the context, tmst already is the concentrator counter to use.
+        tx_info.set_timing(chirpstack_api::gw::DownlinkTiming::Delay);
+        tx_info.context = tmst.to_be_bytes().to_vec();
+        tx_info.timing_info = Some(
+            chirpstack_api::gw::downlink_tx_info::TimingInfo::DelayTimingInfo(
+                chirpstack_api::gw::DelayTimingInfo {
+                    delay: Some(prost_types::Duration {
+                        seconds: 0,
+                        nanos: 0,
+                    }),
+                },
+            ),
+        );
+    } else {
+        return Err("imme, tmst or tmms must be set".to_string());
+    }
+
+    Ok(chirpstack_api::gw::DownlinkFrame {
+        gateway_id: gateway_id.to_vec(),
+        downlink_id: Uuid::new_v4().as_bytes().to_vec(),
+        items: vec![chirpstack_api::gw::DownlinkFrameItem {
+            phy_payload: phy_payload,
+            tx_info: Some(tx_info),
+        }],
+        ..Default::default()
+    })
+}
+
+/// Returns the TX_ACK payload for the given down command response.
+pub fn tx_ack_from_proto(pl: &chirpstack_api::gw::DownlinkTxAck) -> TxAck {
+    // The statuses without a TX_ACK error of the Semtech UDP protocol are
+    // mapped to the closest defined error, as the Semtech packet forwarder
+    // does for a full JIT queue.
+    let error = match pl.items.first().map(proto::TxAckStatus::from_item) {
+        Some(proto::TxAckStatus::Ok) => "NONE",
+        Some(proto::TxAckStatus::TooLate) => "TOO_LATE",
+        Some(proto::TxAckStatus::TooEarly) => "TOO_EARLY",
+        Some(proto::TxAckStatus::CollisionPacket) => "COLLISION_PACKET",
+        Some(proto::TxAckStatus::CollisionBeacon) => "COLLISION_BEACON",
+        Some(proto::TxAckStatus::TxFreq) => "TX_FREQ",
+        Some(proto::TxAckStatus::TxPower) => "TX_POWER",
+        Some(proto::TxAckStatus::GpsUnlocked) => "GPS_UNLOCKED",
+        Some(proto::TxAckStatus::QueueFull) => "COLLISION_PACKET",
+        Some(proto::TxAckStatus::DutyCycleOverflow) => "TX_FREQ",
+        Some(proto::TxAckStatus::InternalError) | Some(proto::TxAckStatus::Ignored) | None => {
+            "INTERNAL_ERROR"
+        }
+    };
+
+    TxAck {
+        txpk_ack: TxPkAck {
+            error: error.to_string(),
+        },
+    }
+}
+
+// Parse the LoRa datarate (e.g. SF7BW125) into the spreading-factor and
+// bandwidth (Hz).
+fn parse_lora_datarate(datr: &str) -> Result<(u32, u32), String> {
+    let err = || format!("invalid LoRa datarate: {}", datr);
+
+    if !datr.starts_with("SF") {
+        return Err(err());
+    }
+
+    let i = datr.find("BW").ok_or_else(err)?;
+    let spreading_factor: u32 = datr[2..i].parse().map_err(|_| err())?;
+    let bandwidth: u32 = match &datr[i + 2..] {
+        // the 2.4GHz bandwidths are rounded in the datarate
+        "203" => 203125,
+        "406" => 406250,
+        "812" => 812500,
+        "1625" => 1625000,
+        v => v.parse::<u32>().map_err(|_| err())? * 1000,
+    };
+
+    Ok((spreading_factor, bandwidth))
+}
+
+fn is_zero(v: &u32) -> bool {
+    *v == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rxpk_from_proto() {
+        let mut tx_info = chirpstack_api::gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation_info: Some(
+                chirpstack_api::gw::uplink_tx_info::ModulationInfo::LoraModulationInfo(
+                    chirpstack_api::gw::LoRaModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 7,
+                        code_rate: "4/5".to_string(),
+                        polarization_inversion: false,
+                    },
+                ),
+            ),
+            ..Default::default()
+        };
+        tx_info.set_modulation(chirpstack_api::common::Modulation::Lora);
+
+        let mut rx_info = chirpstack_api::gw::UplinkRxInfo {
+            time: Some(prost_types::Timestamp {
+                seconds: 1609459200,
+                nanos: 500000000,
+            }),
+            rssi: -60,
+            lora_snr: 7.5,
+            channel: 2,
+            rf_chain: 1,
+            context: vec![0, 0, 0x03, 0xe8],
+            ..Default::default()
+        };
+        rx_info.set_crc_status(chirpstack_api::gw::CrcStatus::CrcOk);
+
+        let pl = chirpstack_api::gw::UplinkFrame {
+            phy_payload: vec![1, 2, 3],
+            tx_info: Some(tx_info),
+            rx_info: Some(rx_info),
+        };
+
+        let rxpk = rxpk_from_proto(&pl).unwrap();
+        assert_eq!(
+            RxPk {
+                time: Some("2021-01-01T00:00:00.500000Z".to_string()),
+                tmst: 1000,
+                freq: 868.1,
+                chan: 2,
+                rfch: 1,
+                stat: 1,
+                modu: "LORA".to_string(),
+                datr: DataRate::LoRa("SF7BW125".to_string()),
+                codr: Some("4/5".to_string()),
+                rssi: -60,
+                lsnr: Some(7.5),
+                size: 3,
+                data: "AQID".to_string(),
+                ..Default::default()
+            },
+            rxpk
+        );
+        assert_eq!(
+            r#"{"time":"2021-01-01T00:00:00.500000Z","tmst":1000,"freq":868.1,"chan":2,"rfch":1,"stat":1,"modu":"LORA","datr":"SF7BW125","codr":"4/5","rssi":-60,"lsnr":7.5,"size":3,"data":"AQID"}"#,
+            serde_json::to_string(&rxpk).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_downlink_from_txpk() {
+        let txpk = TxPk {
+            tmst: Some(1000),
+            freq: 869.525,
+            powe: 27,
+            modu: "LORA".to_string(),
+            datr: DataRate::LoRa("SF9BW125".to_string()),
+            codr: Some("4/5".to_string()),
+            ipol: true,
+            size: 3,
+            data: "AQID".to_string(),
+            ..Default::default()
+        };
+
+        let pl = downlink_from_txpk(&[1, 2, 3, 4, 5, 6, 7, 8], &txpk).unwrap();
+        assert_eq!(1, pl.items.len());
+        assert_eq!(vec![1, 2, 3], pl.items[0].phy_payload);
+
+        let tx_info = pl.items[0].tx_info.as_ref().unwrap();
+        assert_eq!(869525000, tx_info.frequency);
+        assert_eq!(27, tx_info.power);
+        assert_eq!(vec![0, 0, 0x03, 0xe8], tx_info.context);
+        assert_eq!(chirpstack_api::gw::DownlinkTiming::Delay, tx_info.timing());
+        assert_eq!(
+            Some(
+                chirpstack_api::gw::downlink_tx_info::ModulationInfo::LoraModulationInfo(
+                    chirpstack_api::gw::LoRaModulationInfo {
+                        bandwidth: 125000,
+                        spreading_factor: 9,
+                        code_rate: "4/5".to_string(),
+                        polarization_inversion: true,
+                    },
+                ),
+            ),
+            tx_info.modulation_info
+        );
+
+        // no timing
+        let txpk = TxPk { tmst: None, ..txpk };
+        assert!(downlink_from_txpk(&[1, 2, 3, 4, 5, 6, 7, 8], &txpk).is_err());
+    }
+
+    #[test]
+    fn test_parse_lora_datarate() {
+        assert_eq!(Ok((7, 125000)), parse_lora_datarate("SF7BW125"));
+        assert_eq!(Ok((12, 203125)), parse_lora_datarate("SF12BW203"));
+        assert_eq!(Ok((12, 406250)), parse_lora_datarate("SF12BW406"));
+        assert_eq!(Ok((12, 812500)), parse_lora_datarate("SF12BW812"));
+        assert_eq!(Ok((12, 1625000)), parse_lora_datarate("SF12BW1625"));
+        assert!(parse_lora_datarate("SF7").is_err());
+        assert!(parse_lora_datarate("BW125").is_err());
+    }
+
+    #[test]
+    fn test_tx_ack_from_proto() {
+        let mut pl = chirpstack_api::gw::DownlinkTxAck {
+            items: vec![Default::default()],
+            ..Default::default()
+        };
+
+        pl.items[0].set_status(chirpstack_api::gw::TxAckStatus::Ok);
+        assert_eq!("NONE", tx_ack_from_proto(&pl).txpk_ack.error);
+
+        pl.items[0].set_status(chirpstack_api::gw::TxAckStatus::TooLate);
+        assert_eq!("TOO_LATE", tx_ack_from_proto(&pl).txpk_ack.error);
+
+        pl.items[0].set_status(chirpstack_api::gw::TxAckStatus::QueueFull);
+        assert_eq!("COLLISION_PACKET", tx_ack_from_proto(&pl).txpk_ack.error);
+
+        pl.items[0].status = proto::TxAckStatus::DutyCycleOverflow as i32;
+        assert_eq!("TX_FREQ", tx_ack_from_proto(&pl).txpk_ack.error);
+    }
+}
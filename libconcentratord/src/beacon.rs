@@ -0,0 +1,120 @@
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+
+use super::signals::Signal;
+
+/// Class-B beacon period (seconds).
+pub const PERIOD: u64 = 128;
+
+const MARGIN: Duration = Duration::from_secs(5);
+
+/// Beacon loop. This enqueues a beacon for every beacon period.
+///
+/// The get_gps_epoch function must return the current time since GPS epoch.
+/// The send_beacon function must enqueue the beacon for the given time since
+/// GPS epoch.
+pub fn beacon_loop<E, S>(get_gps_epoch: E, send_beacon: S, stop_receive: Receiver<Signal>)
+where
+    E: Fn() -> Result<Duration, String>,
+    S: Fn(Duration) -> Result<(), String>,
+{
+    debug!("Starting beacon loop");
+
+    loop {
+        // Instead of a MARGIN sleep, we receive from the stop channel with a
+        // timeout of MARGIN seconds.
+        match stop_receive.recv_timeout(MARGIN) {
+            Ok(v) => {
+                debug!("Received stop signal, signal: {}", v);
+                break;
+            }
+            _ => {}
+        };
+
+        let gps_epoch = match get_gps_epoch() {
+            Ok(v) => v,
+            Err(err) => {
+                debug!("Get GPS epoch error, error: {}", err);
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+
+        let next_beacon_time =
+            Duration::from_secs(gps_epoch.as_secs() - (gps_epoch.as_secs() % PERIOD) + PERIOD);
+        let sleep_time = match next_beacon_time.checked_sub(gps_epoch + MARGIN) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        // Instead of a sleep_time sleep, we receive from the stop channel with a
+        // timeout of sleep_time.
+        match stop_receive.recv_timeout(sleep_time) {
+            Ok(v) => {
+                debug!("Received stop signal, signal: {}", v);
+                break;
+            }
+            _ => {}
+        };
+
+        match send_beacon(next_beacon_time) {
+            Ok(_) => info!(
+                "Beacon enqueued, beacon_time_gps_epoch: {:?}",
+                next_beacon_time
+            ),
+            Err(err) => warn!("Enqueue beacon failed, error: {}", err),
+        }
+    }
+
+    debug!("Beacon loop ended");
+}
+
+/// Returns the beacon frequency for the given beacon time.
+pub fn get_frequency(frequencies: &[u32], beacon_time: Duration) -> u32 {
+    frequencies[((beacon_time.as_secs() % (1 << 32)) % frequencies.len() as u64) as usize]
+}
+
+/// Returns the beacon payload for the given beacon time.
+pub fn get_beacon(rfu_size: usize, beacon_time: Duration) -> Vec<u8> {
+    // [N: RFU | 4: TIME | 2: CRC]
+    let mut b: Vec<u8> = vec![0; rfu_size + 6];
+    let beacon_time = beacon_time.as_secs();
+
+    let time_bytes = ((beacon_time % (1 << 32)) as u32).to_le_bytes();
+    b[rfu_size..4 + rfu_size].copy_from_slice(&time_bytes);
+
+    let poly: u16 = 0x1021;
+    let mut x: u16 = 0;
+
+    for i in 0..b.len() - 2 {
+        x ^= (b[i] as u16) << 8;
+        for _j in 0..8 {
+            if x & 0x8000 != 0 {
+                x = (x << 1) ^ poly;
+            } else {
+                x = x << 1;
+            }
+        }
+    }
+
+    let crc_bytes = x.to_le_bytes();
+    b[rfu_size + 4..rfu_size + 6].copy_from_slice(&crc_bytes);
+
+    return b;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_beacon() {
+        let beacon_time = Duration::from_secs(0xcc020000);
+        let beacon = get_beacon(2, beacon_time);
+
+        assert_eq!(vec![0x00, 0x00, 0x00, 0x00, 0x02, 0xcc, 0xa2, 0x7e], beacon);
+    }
+}
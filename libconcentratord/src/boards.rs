@@ -0,0 +1,696 @@
+use std::env;
+use std::os::unix::process::parent_id;
+use std::process::{Child, Command};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use log::{debug, error, info};
+use prost::Message;
+use uuid::Uuid;
+
+use super::curve;
+use super::proto;
+use super::signals::Signal;
+use super::socket::ZMQ_CONTEXT;
+use super::stats;
+
+// Timeout for receiving the response of a board process.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Returns the internal event and command endpoints of the given board
+/// process, spawned by the process with the given pid.
+pub fn get_endpoints(pid: u32, board: usize) -> (String, String) {
+    (
+        format!("ipc:///tmp/concentratord_{}_board_{}_event", pid, board),
+        format!("ipc:///tmp/concentratord_{}_board_{}_command", pid, board),
+    )
+}
+
+/// Returns the internal event and command endpoints of the current board
+/// process.
+pub fn get_board_endpoints(board: usize) -> (String, String) {
+    get_endpoints(parent_id(), board)
+}
+
+/// Setup the current process as board process. This makes sure that the
+/// board process is stopped when the parent process exits.
+pub fn init_board_process() {
+    unsafe {
+        libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+    }
+}
+
+/// Run a process per board and proxy the API sockets to the board processes,
+/// until a signal is received.
+///
+/// Every board process is the current executable, started with the given
+/// configuration files and the (hidden) board subcommand. Events of all
+/// boards are published on the event_bind socket. Downlinks received on the
+/// command_bind socket are sent to the board of the downlink item, test
+/// transmissions to the board of the test transmission, the queue, cancel and
+/// test_tx_stop commands are sent to all boards, other commands are sent to the
+/// first board.
+pub fn run(
+    config_files: &[String],
+    boards: usize,
+    event_bind: &str,
+    command_bind: &str,
+    stop_receive: &Receiver<Signal>,
+) -> Result<Signal, String> {
+    info!("Starting board processes, boards: {}", boards);
+
+    let mut children: Vec<Child> = Vec::new();
+    for board in 0..boards {
+        match spawn(config_files, board) {
+            Ok(v) => children.push(v),
+            Err(err) => {
+                stop(&mut children);
+                return Err(err);
+            }
+        }
+    }
+
+    let res = proxy_loop(&mut children, event_bind, command_bind, stop_receive);
+    stop(&mut children);
+
+    return res;
+}
+
+fn spawn(config_files: &[String], board: usize) -> Result<Child, String> {
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let mut cmd = Command::new(exe);
+    for file_name in config_files {
+        cmd.arg("-c").arg(file_name);
+    }
+    cmd.arg("board").arg(board.to_string());
+
+    let child = cmd
+        .spawn()
+        .map_err(|e| format!("start board process error, board: {}, error: {}", board, e))?;
+
+    info!(
+        "Board process started, board: {}, pid: {}",
+        board,
+        child.id()
+    );
+
+    return Ok(child);
+}
+
+fn stop(children: &mut Vec<Child>) {
+    for child in children.iter() {
+        unsafe {
+            libc::kill(child.id() as i32, libc::SIGTERM);
+        }
+    }
+
+    for (board, child) in children.iter_mut().enumerate() {
+        match child.wait() {
+            Ok(status) => info!(
+                "Board process stopped, board: {}, status: {}",
+                board, status
+            ),
+            Err(err) => error!(
+                "Wait for board process error, board: {}, error: {}",
+                board, err
+            ),
+        }
+    }
+
+    children.clear();
+}
+
+fn proxy_loop(
+    children: &mut Vec<Child>,
+    event_bind: &str,
+    command_bind: &str,
+    stop_receive: &Receiver<Signal>,
+) -> Result<Signal, String> {
+    let zmq_ctx = ZMQ_CONTEXT.lock().unwrap().clone();
+
+    info!(
+        "Creating socket for publishing events, bind: {}",
+        event_bind
+    );
+    let pub_sock = zmq_ctx.socket(zmq::PUB).map_err(|e| e.to_string())?;
+    curve::configure_socket(&pub_sock).map_err(|e| e.to_string())?;
+    pub_sock.bind(event_bind).map_err(|e| e.to_string())?;
+
+    info!(
+        "Creating socket for receiving commands, bind: {}",
+        command_bind
+    );
+    let rep_sock = zmq_ctx.socket(zmq::REP).map_err(|e| e.to_string())?;
+    curve::configure_socket(&rep_sock).map_err(|e| e.to_string())?;
+    rep_sock.bind(command_bind).map_err(|e| e.to_string())?;
+
+    let mut sub_socks: Vec<zmq::Socket> = Vec::new();
+    let mut req_socks: Vec<zmq::Socket> = Vec::new();
+    for board in 0..children.len() {
+        let (event_endpoint, command_endpoint) = get_endpoints(std::process::id(), board);
+
+        let sock = zmq_ctx.socket(zmq::SUB).map_err(|e| e.to_string())?;
+        sock.connect(&event_endpoint).map_err(|e| e.to_string())?;
+        sock.set_subscribe(b"").map_err(|e| e.to_string())?;
+        sub_socks.push(sock);
+
+        req_socks.push(get_req_socket(&zmq_ctx, &command_endpoint)?);
+    }
+
+    // stats of the boards, see handle_stats
+    let mut board_stats: Vec<Option<proto::GatewayStats>> = vec![None; children.len()];
+
+    loop {
+        if let Ok(v) = stop_receive.recv_timeout(Duration::from_millis(0)) {
+            debug!("Received stop signal, signal: {}", v);
+            return Ok(v);
+        }
+
+        for (board, child) in children.iter_mut().enumerate() {
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(format!(
+                    "board process exited, board: {}, status: {}",
+                    board, status
+                ));
+            }
+        }
+
+        let readable: Vec<bool> = {
+            let mut items = vec![rep_sock.as_poll_item(zmq::POLLIN)];
+            for sock in &sub_socks {
+                items.push(sock.as_poll_item(zmq::POLLIN));
+            }
+            zmq::poll(&mut items, 100).map_err(|e| e.to_string())?;
+            items.iter().map(|i| i.is_readable()).collect()
+        };
+
+        for (i, sock) in sub_socks.iter().enumerate() {
+            if readable[i + 1] {
+                let msg = sock.recv_multipart(0).map_err(|e| e.to_string())?;
+                if msg.len() == 2 && msg[0] == b"stats" {
+                    handle_stats(&pub_sock, &mut board_stats, i, &msg[1])?;
+                    continue;
+                }
+
+                pub_sock.send_multipart(msg, 0).map_err(|e| e.to_string())?;
+            }
+        }
+
+        if readable[0] {
+            let msg = rep_sock.recv_multipart(0).map_err(|e| e.to_string())?;
+            let resp = handle_command(&req_socks, msg);
+            rep_sock.send(resp, 0).map_err(|e| e.to_string())?;
+        }
+    }
+}
+
+fn get_req_socket(zmq_ctx: &zmq::Context, command_endpoint: &str) -> Result<zmq::Socket, String> {
+    // The relaxed + correlate options make it possible to send the next
+    // command after a command timed out.
+    let sock = zmq_ctx.socket(zmq::REQ).map_err(|e| e.to_string())?;
+    sock.set_rcvtimeo(COMMAND_TIMEOUT.as_millis() as i32)
+        .map_err(|e| e.to_string())?;
+    sock.set_req_relaxed(true).map_err(|e| e.to_string())?;
+    sock.set_req_correlate(true).map_err(|e| e.to_string())?;
+    sock.set_linger(0).map_err(|e| e.to_string())?;
+    sock.connect(command_endpoint).map_err(|e| e.to_string())?;
+
+    return Ok(sock);
+}
+
+// The stats of the boards are published as a single stats event, once every
+// board has reported its stats. The counters of a board which reports its
+// stats again before that are added up.
+fn handle_stats(
+    pub_sock: &zmq::Socket,
+    board_stats: &mut [Option<proto::GatewayStats>],
+    board: usize,
+    b: &[u8],
+) -> Result<(), String> {
+    let pl = match proto::GatewayStats::decode(b) {
+        Ok(v) => v,
+        Err(err) => {
+            error!("Decode stats error, board: {}, error: {}", board, err);
+            return Ok(());
+        }
+    };
+
+    match board_stats[board].as_mut() {
+        Some(v) => stats::merge(v, pl),
+        None => board_stats[board] = Some(pl),
+    }
+
+    if board_stats.iter().any(|v| v.is_none()) {
+        return Ok(());
+    }
+
+    // the gateway ID, location and meta-data of the first board are used
+    let mut boards = board_stats.iter_mut().map(|v| v.take().unwrap());
+    let mut pl = boards.next().unwrap();
+    for v in boards {
+        stats::merge(&mut pl, v);
+    }
+
+    let stats_id = Uuid::new_v4();
+    pl.stats_id = stats_id.as_bytes().to_vec();
+
+    info!(
+        "Publishing stats event of all boards, stats_id: {}, boards: {}",
+        stats_id,
+        board_stats.len()
+    );
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).map_err(|e| e.to_string())?;
+    pub_sock
+        .send("stats", zmq::SNDMORE)
+        .map_err(|e| e.to_string())?;
+    pub_sock.send(buf, 0).map_err(|e| e.to_string())?;
+
+    return Ok(());
+}
+
+fn handle_command(req_socks: &[zmq::Socket], msg: Vec<Vec<u8>>) -> Vec<u8> {
+    if msg.len() != 2 {
+        error!("Read command error, error: command must have two frames");
+        return Vec::new();
+    }
+
+    let command = String::from_utf8_lossy(&msg[0]).to_string();
+    match command.as_ref() {
+        "down" => handle_downlink(req_socks, &msg[1]),
+        "queue" | "cancel" => handle_queue(req_socks, &command, msg),
+        "test_tx" => handle_test_tx(req_socks, msg),
+        "test_tx_stop" => handle_test_tx_stop(req_socks, msg),
+        "config" => {
+            error!("The gateway configuration command is not supported when using multiple boards");
+            Vec::new()
+        }
+        _ => match forward(&req_socks[0], msg) {
+            Ok(v) => v,
+            Err(err) => {
+                error!(
+                    "Forward command error, command: {}, board: 0, error: {}",
+                    command, err
+                );
+                Vec::new()
+            }
+        },
+    }
+}
+
+// Every downlink item is sent to the board of the item, until one of the
+// items has been accepted.
+fn handle_downlink(req_socks: &[zmq::Socket], b: &[u8]) -> Vec<u8> {
+    let pl = match chirpstack_api::gw::DownlinkFrame::decode(b) {
+        Ok(v) => v,
+        Err(err) => {
+            error!("Decode downlink error, error: {}", err);
+            return Vec::new();
+        }
+    };
+    let id = Uuid::from_slice(&pl.downlink_id).unwrap_or(Uuid::nil());
+
+    let mut tx_ack = chirpstack_api::gw::DownlinkTxAck {
+        token: pl.token,
+        downlink_id: pl.downlink_id.clone(),
+        items: vec![Default::default(); pl.items.len()],
+        ..Default::default()
+    };
+
+    for (i, item) in pl.items.iter().enumerate() {
+        let board = item.tx_info.as_ref().map_or(0, |v| v.board as usize);
+        let sock = match req_socks.get(board) {
+            Some(v) => v,
+            None => {
+                error!("Invalid board, downlink_id: {}, board: {}", id, board);
+                tx_ack.items[i].status = proto::TxAckStatus::InternalError as i32;
+
+                // try next
+                continue;
+            }
+        };
+
+        let frame = chirpstack_api::gw::DownlinkFrame {
+            items: vec![item.clone()],
+            ..pl.clone()
+        };
+        let mut buf = Vec::new();
+        frame.encode(&mut buf).unwrap();
+
+        let resp = match forward(sock, vec![b"down".to_vec(), buf]) {
+            Ok(v) => v,
+            Err(err) => {
+                error!(
+                    "Forward downlink error, downlink_id: {}, board: {}, error: {}",
+                    id, board, err
+                );
+                tx_ack.items[i].status = proto::TxAckStatus::InternalError as i32;
+
+                // try next
+                continue;
+            }
+        };
+
+        let status = match chirpstack_api::gw::DownlinkTxAck::decode(&resp[..]) {
+            Ok(v) => {
+                tx_ack.gateway_id = v.gateway_id.clone();
+                match v.items.first() {
+                    Some(v) => proto::TxAckStatus::from_item(v),
+                    None => proto::TxAckStatus::InternalError,
+                }
+            }
+            Err(err) => {
+                error!(
+                    "Decode downlink ack error, downlink_id: {}, board: {}, error: {}",
+                    id, board, err
+                );
+                proto::TxAckStatus::InternalError
+            }
+        };
+
+        tx_ack.items[i].status = status as i32;
+        if status == proto::TxAckStatus::Ok {
+            break;
+        }
+    }
+
+    let mut buf = Vec::new();
+    tx_ack.encode(&mut buf).unwrap();
+    return buf;
+}
+
+// The (cancelled) queue items of all boards are combined.
+fn handle_queue(req_socks: &[zmq::Socket], command: &str, msg: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut queue: proto::JitQueue = Default::default();
+
+    for (board, sock) in req_socks.iter().enumerate() {
+        let resp = match forward(sock, msg.clone()) {
+            Ok(v) => v,
+            Err(err) => {
+                error!(
+                    "Forward command error, command: {}, board: {}, error: {}",
+                    command, board, err
+                );
+                continue;
+            }
+        };
+
+        match proto::JitQueue::decode(&resp[..]) {
+            Ok(v) => {
+                if queue.gateway_id.is_empty() {
+                    queue.gateway_id = v.gateway_id;
+                }
+                queue.items.extend(v.items);
+            }
+            Err(err) => error!(
+                "Decode queue error, command: {}, board: {}, error: {}",
+                command, board, err
+            ),
+        }
+    }
+
+    let mut buf = Vec::new();
+    queue.encode(&mut buf).unwrap();
+    return buf;
+}
+
+fn handle_test_tx(req_socks: &[zmq::Socket], msg: Vec<Vec<u8>>) -> Vec<u8> {
+    let board = match proto::TestTx::decode(&msg[1][..]) {
+        Ok(v) => v.board as usize,
+        Err(err) => {
+            error!("Decode test transmission error, error: {}", err);
+            return Vec::new();
+        }
+    };
+
+    let resp = match req_socks.get(board) {
+        Some(sock) => forward(sock, msg).map_err(|e| {
+            format!(
+                "forward command error, command: test_tx, board: {}, error: {}",
+                board, e
+            )
+        }),
+        None => Err(format!("invalid board: {}", board)),
+    };
+
+    match resp {
+        Ok(v) => v,
+        Err(err) => {
+            error!("Test transmission error, error: {}", err);
+
+            let mut buf = Vec::new();
+            proto::TestTxResponse {
+                error: err,
+                ..Default::default()
+            }
+            .encode(&mut buf)
+            .unwrap();
+            buf
+        }
+    }
+}
+
+// The stop command is sent to all boards, as only the board running the test
+// transmission knows about it. The response of that board is returned.
+fn handle_test_tx_stop(req_socks: &[zmq::Socket], msg: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut resp: proto::TestTxResponse = Default::default();
+
+    for (board, sock) in req_socks.iter().enumerate() {
+        let v = match forward(sock, msg.clone()) {
+            Ok(v) => v,
+            Err(err) => {
+                error!(
+                    "Forward command error, command: test_tx_stop, board: {}, error: {}",
+                    board, err
+                );
+                continue;
+            }
+        };
+
+        match proto::TestTxResponse::decode(&v[..]) {
+            Ok(v) => {
+                let stopped = v.error.is_empty();
+                if stopped || resp.gateway_id.is_empty() {
+                    resp = v;
+                }
+                if stopped {
+                    break;
+                }
+            }
+            Err(err) => error!(
+                "Decode test transmission response error, board: {}, error: {}",
+                board, err
+            ),
+        }
+    }
+
+    let mut buf = Vec::new();
+    resp.encode(&mut buf).unwrap();
+    return buf;
+}
+
+fn forward(sock: &zmq::Socket, msg: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    sock.send_multipart(msg, 0).map_err(|e| e.to_string())?;
+
+    match sock.recv_bytes(0) {
+        Ok(v) => Ok(v),
+        Err(zmq::Error::EAGAIN) => Err("board process did not respond in time".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Starts a REP stand-in per board and returns the REQ sockets connected
+    // to them. The stand-in of a board responds to every command with the
+    // response returned by the given function, until no command is received
+    // for a second.
+    fn get_boards(
+        name: &str,
+        boards: usize,
+        f: fn(usize, &[Vec<u8>]) -> Vec<u8>,
+    ) -> Vec<zmq::Socket> {
+        let zmq_ctx = ZMQ_CONTEXT.lock().unwrap().clone();
+        let mut req_socks = Vec::new();
+
+        for board in 0..boards {
+            let endpoint = format!("inproc://boards_test_{}_{}", name, board);
+
+            let rep_sock = zmq_ctx.socket(zmq::REP).unwrap();
+            rep_sock.set_rcvtimeo(1000).unwrap();
+            rep_sock.bind(&endpoint).unwrap();
+
+            thread::spawn(move || {
+                while let Ok(msg) = rep_sock.recv_multipart(0) {
+                    rep_sock.send(f(board, &msg), 0).unwrap();
+                }
+            });
+
+            req_socks.push(get_req_socket(&zmq_ctx, &endpoint).unwrap());
+        }
+
+        return req_socks;
+    }
+
+    // Accepts downlinks on board 1 only, the gateway ID is set to the board
+    // index.
+    fn downlink_response(board: usize, msg: &[Vec<u8>]) -> Vec<u8> {
+        assert_eq!(b"down".to_vec(), msg[0]);
+
+        let pl = chirpstack_api::gw::DownlinkFrame::decode(&msg[1][..]).unwrap();
+        assert_eq!(1, pl.items.len());
+        assert_eq!(board as u32, pl.items[0].tx_info.as_ref().unwrap().board);
+
+        let status = match board {
+            1 => proto::TxAckStatus::Ok,
+            _ => proto::TxAckStatus::TxFreq,
+        };
+
+        let mut buf = Vec::new();
+        chirpstack_api::gw::DownlinkTxAck {
+            gateway_id: vec![board as u8],
+            token: pl.token,
+            downlink_id: pl.downlink_id,
+            items: vec![chirpstack_api::gw::DownlinkTxAckItem {
+                status: status as i32,
+            }],
+            ..Default::default()
+        }
+        .encode(&mut buf)
+        .unwrap();
+        buf
+    }
+
+    fn get_downlink(boards: &[u32]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        chirpstack_api::gw::DownlinkFrame {
+            token: 123,
+            downlink_id: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16],
+            items: boards
+                .iter()
+                .map(|board| chirpstack_api::gw::DownlinkFrameItem {
+                    tx_info: Some(chirpstack_api::gw::DownlinkTxInfo {
+                        board: *board,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+        .encode(&mut buf)
+        .unwrap();
+        buf
+    }
+
+    fn get_statuses(b: &[u8]) -> (Vec<u8>, Vec<proto::TxAckStatus>) {
+        let tx_ack = chirpstack_api::gw::DownlinkTxAck::decode(b).unwrap();
+        assert_eq!(123, tx_ack.token);
+        (
+            tx_ack.gateway_id,
+            tx_ack
+                .items
+                .iter()
+                .map(proto::TxAckStatus::from_item)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_get_endpoints() {
+        assert_eq!(
+            (
+                "ipc:///tmp/concentratord_123_board_1_event".to_string(),
+                "ipc:///tmp/concentratord_123_board_1_command".to_string()
+            ),
+            get_endpoints(123, 1)
+        );
+        assert_ne!(get_endpoints(123, 0), get_endpoints(123, 1));
+        assert_ne!(get_endpoints(123, 0), get_endpoints(124, 0));
+    }
+
+    #[test]
+    fn test_handle_downlink() {
+        let req_socks = get_boards("downlink", 2, downlink_response);
+
+        // the first item is rejected by board 0, the second item is accepted
+        // by board 1
+        let (gateway_id, statuses) =
+            get_statuses(&handle_downlink(&req_socks, &get_downlink(&[0, 1, 0])));
+        assert_eq!(vec![1], gateway_id);
+        assert_eq!(
+            vec![
+                proto::TxAckStatus::TxFreq,
+                proto::TxAckStatus::Ok,
+                proto::TxAckStatus::Ignored
+            ],
+            statuses
+        );
+    }
+
+    #[test]
+    fn test_handle_downlink_invalid_board() {
+        let req_socks = get_boards("downlink_invalid", 2, downlink_response);
+
+        // board 2 does not exist, the next item is tried
+        let (gateway_id, statuses) =
+            get_statuses(&handle_downlink(&req_socks, &get_downlink(&[2, 1])));
+        assert_eq!(vec![1], gateway_id);
+        assert_eq!(
+            vec![proto::TxAckStatus::InternalError, proto::TxAckStatus::Ok],
+            statuses
+        );
+
+        let (_, statuses) = get_statuses(&handle_downlink(&req_socks, &get_downlink(&[2])));
+        assert_eq!(vec![proto::TxAckStatus::InternalError], statuses);
+    }
+
+    #[test]
+    fn test_handle_command() {
+        // Every board returns a queue item with its board index as frequency.
+        let req_socks = get_boards("command", 3, |board, msg| {
+            let mut buf = Vec::new();
+            let frequency = match msg[0].as_slice() {
+                b"queue" | b"cancel" => board as u32,
+                _ => 100 + board as u32,
+            };
+            proto::JitQueue {
+                gateway_id: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                items: vec![proto::JitQueueItem {
+                    frequency: frequency,
+                    ..Default::default()
+                }],
+            }
+            .encode(&mut buf)
+            .unwrap();
+            buf
+        });
+
+        // the queue and cancel commands are sent to all boards
+        for command in &["queue", "cancel"] {
+            let resp = handle_command(&req_socks, vec![command.as_bytes().to_vec(), vec![]]);
+            let queue = proto::JitQueue::decode(&resp[..]).unwrap();
+            assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], queue.gateway_id);
+            assert_eq!(
+                vec![0, 1, 2],
+                queue
+                    .items
+                    .iter()
+                    .map(|i| i.frequency)
+                    .collect::<Vec<u32>>()
+            );
+        }
+
+        // other commands are sent to the first board
+        let resp = handle_command(&req_socks, vec![b"gateway_id".to_vec(), vec![]]);
+        let queue = proto::JitQueue::decode(&resp[..]).unwrap();
+        assert_eq!(100, queue.items[0].frequency);
+
+        // the configuration command is rejected
+        assert!(handle_command(&req_socks, vec![b"config".to_vec(), vec![]]).is_empty());
+    }
+}
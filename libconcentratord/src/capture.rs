@@ -0,0 +1,484 @@
+use std::fs;
+use std::io::Write;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+// pcap link-type of LoRaTap, see
+// https://www.tcpdump.org/linktypes/LINKTYPE_LORATAP.html.
+const LINKTYPE_LORATAP: u32 = 270;
+
+// LoRaTap version 1 header length, see https://github.com/eriknl/LoRaTap.
+const LORATAP_VERSION: u8 = 1;
+const LORATAP_LENGTH: u16 = 35;
+
+// LoRaTap flags.
+const FLAG_MOD_FSK: u8 = 0x01;
+const FLAG_IQ_INVERTED: u8 = 0x02;
+const FLAG_CRC_OK: u8 = 0x08;
+const FLAG_CRC_BAD: u8 = 0x10;
+const FLAG_NO_CRC: u8 = 0x20;
+
+const PCAP_HEADER_LENGTH: u64 = 24;
+const PCAP_SNAPLEN: u32 = 65535;
+
+lazy_static! {
+    static ref CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+}
+
+/// Packet capture configuration.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Configuration {
+    /// Path of the pcap file. When empty, no file is written.
+    pub file: String,
+    /// File size (bytes) after which the file is rotated, 0 to disable
+    /// rotation.
+    pub max_file_size: u64,
+    /// Number of rotated files to keep (file.1 ... file.N).
+    pub max_files: usize,
+    /// UDP target (host:port) of the live stream. When empty, no stream is
+    /// sent.
+    pub udp_target: String,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            file: "".to_string(),
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
+            udp_target: "".to_string(),
+        }
+    }
+}
+
+/// Captured packet.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub gateway_id: Vec<u8>,
+    /// Concentrator counter at which the packet was received or sent.
+    pub count_us: u32,
+    pub frequency: u32,
+    /// LoRa bandwidth (Hz).
+    pub bandwidth: u32,
+    /// LoRa spreading factor, 0 for FSK.
+    pub spreading_factor: u32,
+    /// LoRa coding rate (e.g. 4/5).
+    pub code_rate: String,
+    /// FSK datarate (bps).
+    pub datarate: u32,
+    /// RSSI (dBm), 0 for downlinks.
+    pub rssi: i32,
+    /// LoRa SNR (dB), 0 for downlinks.
+    pub snr: f64,
+    pub crc_status: chirpstack_api::gw::CrcStatus,
+    pub iq_inverted: bool,
+    pub if_chain: u32,
+    pub rf_chain: u32,
+    pub phy_payload: Vec<u8>,
+}
+
+impl Packet {
+    pub fn from_uplink(pl: &chirpstack_api::gw::UplinkFrame) -> Packet {
+        let mut packet = Packet {
+            phy_payload: pl.phy_payload.clone(),
+            ..Default::default()
+        };
+
+        if let Some(tx_info) = &pl.tx_info {
+            packet.frequency = tx_info.frequency;
+
+            match &tx_info.modulation_info {
+                Some(chirpstack_api::gw::uplink_tx_info::ModulationInfo::LoraModulationInfo(v)) => {
+                    packet.bandwidth = v.bandwidth;
+                    packet.spreading_factor = v.spreading_factor;
+                    packet.code_rate = v.code_rate.clone();
+                }
+                Some(chirpstack_api::gw::uplink_tx_info::ModulationInfo::FskModulationInfo(v)) => {
+                    packet.datarate = v.datarate;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(rx_info) = &pl.rx_info {
+            packet.gateway_id = rx_info.gateway_id.clone();
+            packet.rssi = rx_info.rssi;
+            packet.snr = rx_info.lora_snr;
+            packet.crc_status = rx_info.crc_status();
+            packet.if_chain = rx_info.channel;
+            packet.rf_chain = rx_info.rf_chain;
+
+            if rx_info.context.len() == 4 {
+                let mut array = [0; 4];
+                array.copy_from_slice(&rx_info.context);
+                packet.count_us = u32::from_be_bytes(array);
+            }
+        }
+
+        packet
+    }
+}
+
+struct Capture {
+    sync_word: u8,
+    file: Option<PcapFile>,
+    udp: Option<(UdpSocket, SocketAddr)>,
+}
+
+struct PcapFile {
+    path: String,
+    max_file_size: u64,
+    max_files: usize,
+    file: fs::File,
+    size: u64,
+}
+
+impl PcapFile {
+    fn create(conf: &Configuration) -> Result<PcapFile, String> {
+        let mut f = PcapFile {
+            path: conf.file.clone(),
+            max_file_size: conf.max_file_size,
+            max_files: conf.max_files,
+            file: open_file(&conf.file)?,
+            size: 0,
+        };
+        f.size = f.file.metadata().map_err(|e| e.to_string())?.len();
+
+        // Appending to an existing (non-empty) file continues the capture.
+        if f.size == 0 {
+            f.file
+                .write_all(&get_pcap_header())
+                .map_err(|e| e.to_string())?;
+            f.size = PCAP_HEADER_LENGTH;
+        }
+
+        return Ok(f);
+    }
+
+    fn write(&mut self, record: &[u8]) -> Result<(), String> {
+        if self.max_file_size != 0
+            && self.size > PCAP_HEADER_LENGTH
+            && self.size + record.len() as u64 > self.max_file_size
+        {
+            self.rotate()?;
+        }
+
+        self.file.write_all(record).map_err(|e| e.to_string())?;
+        self.size += record.len() as u64;
+
+        return Ok(());
+    }
+
+    fn rotate(&mut self) -> Result<(), String> {
+        info!("Rotating capture file, file: {}", self.path);
+
+        if self.max_files == 0 {
+            fs::remove_file(&self.path).map_err(|e| e.to_string())?;
+        } else {
+            for i in (1..self.max_files).rev() {
+                let from = format!("{}.{}", self.path, i);
+                if fs::metadata(&from).is_ok() {
+                    fs::rename(&from, format!("{}.{}", self.path, i + 1))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            fs::rename(&self.path, format!("{}.1", self.path)).map_err(|e| e.to_string())?;
+        }
+
+        self.file = open_file(&self.path)?;
+        self.file
+            .write_all(&get_pcap_header())
+            .map_err(|e| e.to_string())?;
+        self.size = PCAP_HEADER_LENGTH;
+
+        return Ok(());
+    }
+}
+
+/// Setup packet capturing. Packets are written to a (rotating) pcap file
+/// and / or sent as LoRaTap datagrams to the UDP target, e.g. to be received
+/// by the Wireshark udpdump extcap (payload: loratap). The sync_word is
+/// reported in the LoRaTap header. When neither file nor udp_target is set,
+/// capturing is disabled.
+pub fn setup(conf: &Configuration, sync_word: u8) -> Result<(), String> {
+    let mut capture = CAPTURE.lock().unwrap();
+    *capture = None;
+
+    if conf.file.is_empty() && conf.udp_target.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Setting up packet capture, file: {}, udp_target: {}",
+        conf.file, conf.udp_target
+    );
+
+    *capture = Some(Capture {
+        sync_word: sync_word,
+        file: match conf.file.is_empty() {
+            true => None,
+            false => Some(
+                PcapFile::create(conf).map_err(|e| format!("open capture file error: {}", e))?,
+            ),
+        },
+        udp: match conf.udp_target.is_empty() {
+            true => None,
+            false => Some(get_udp_socket(&conf.udp_target)?),
+        },
+    });
+
+    return Ok(());
+}
+
+/// Capture the given uplink, when capturing is enabled.
+pub fn uplink(pl: &chirpstack_api::gw::UplinkFrame) -> Result<(), String> {
+    if !enabled() {
+        return Ok(());
+    }
+
+    capture(&Packet::from_uplink(pl))
+}
+
+/// Capture the given (emitted) downlink, when capturing is enabled.
+pub fn downlink(packet: &Packet) -> Result<(), String> {
+    capture(packet)
+}
+
+/// Returns true when capturing is enabled.
+pub fn enabled() -> bool {
+    CAPTURE.lock().unwrap().is_some()
+}
+
+/// Returns the capture file for the given board, so that board processes do
+/// not write to the same file. E.g. /tmp/capture.pcap becomes
+/// /tmp/capture_board1.pcap.
+pub fn get_board_file(file: &str, board: usize) -> String {
+    if file.is_empty() {
+        return "".to_string();
+    }
+
+    let path = Path::new(file);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => path
+            .with_file_name(format!(
+                "{}_board{}.{}",
+                stem.to_string_lossy(),
+                board,
+                ext.to_string_lossy()
+            ))
+            .display()
+            .to_string(),
+        _ => format!("{}_board{}", file, board),
+    }
+}
+
+fn capture(packet: &Packet) -> Result<(), String> {
+    let mut capture = CAPTURE.lock().unwrap();
+    let capture = match capture.as_mut() {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    let mut data = get_loratap_header(packet, capture.sync_word);
+    data.extend_from_slice(&packet.phy_payload);
+
+    if let Some(f) = capture.file.as_mut() {
+        f.write(&get_pcap_record(&data, SystemTime::now()))?;
+    }
+
+    if let Some((sock, addr)) = &capture.udp {
+        sock.send_to(&data, addr).map_err(|e| e.to_string())?;
+    }
+
+    return Ok(());
+}
+
+fn open_file(path: &str) -> Result<fs::File, String> {
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())
+}
+
+fn get_udp_socket(target: &str) -> Result<(UdpSocket, SocketAddr), String> {
+    let addr = target
+        .to_socket_addrs()
+        .map_err(|e| format!("resolve udp_target error: {}", e))?
+        .next()
+        .ok_or("udp_target did not resolve to an address")?;
+
+    let sock = match addr {
+        SocketAddr::V4(_) => UdpSocket::bind("0.0.0.0:0"),
+        SocketAddr::V6(_) => UdpSocket::bind("[::]:0"),
+    }
+    .map_err(|e| e.to_string())?;
+
+    return Ok((sock, addr));
+}
+
+fn get_pcap_header() -> Vec<u8> {
+    let mut b: Vec<u8> = Vec::with_capacity(PCAP_HEADER_LENGTH as usize);
+    b.extend_from_slice(&0xa1b2c3d4_u32.to_le_bytes()); // magic number
+    b.extend_from_slice(&2_u16.to_le_bytes()); // version major
+    b.extend_from_slice(&4_u16.to_le_bytes()); // version minor
+    b.extend_from_slice(&0_i32.to_le_bytes()); // thiszone
+    b.extend_from_slice(&0_u32.to_le_bytes()); // sigfigs
+    b.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+    b.extend_from_slice(&LINKTYPE_LORATAP.to_le_bytes());
+    b
+}
+
+fn get_pcap_record(data: &[u8], time: SystemTime) -> Vec<u8> {
+    let ts = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    let mut b: Vec<u8> = Vec::with_capacity(16 + data.len());
+    b.extend_from_slice(&(ts.as_secs() as u32).to_le_bytes());
+    b.extend_from_slice(&ts.subsec_micros().to_le_bytes());
+    b.extend_from_slice(&(data.len() as u32).to_le_bytes()); // incl_len
+    b.extend_from_slice(&(data.len() as u32).to_le_bytes()); // orig_len
+    b.extend_from_slice(data);
+    b
+}
+
+// The LoRaTap RSSI values are encoded as offset from -139 dBm. Only the
+// packet RSSI is known, the max and current RSSI are left blank.
+fn get_loratap_header(packet: &Packet, sync_word: u8) -> Vec<u8> {
+    let mut flags: u8 = match packet.crc_status {
+        chirpstack_api::gw::CrcStatus::CrcOk => FLAG_CRC_OK,
+        chirpstack_api::gw::CrcStatus::BadCrc => FLAG_CRC_BAD,
+        chirpstack_api::gw::CrcStatus::NoCrc => FLAG_NO_CRC,
+    };
+    if packet.spreading_factor == 0 {
+        flags |= FLAG_MOD_FSK;
+    }
+    if packet.iq_inverted {
+        flags |= FLAG_IQ_INVERTED;
+    }
+
+    let mut gateway_id = [0; 8];
+    if packet.gateway_id.len() == gateway_id.len() {
+        gateway_id.copy_from_slice(&packet.gateway_id);
+    }
+
+    let mut b: Vec<u8> = Vec::with_capacity(LORATAP_LENGTH as usize);
+    b.push(LORATAP_VERSION);
+    b.push(0); // padding
+    b.extend_from_slice(&LORATAP_LENGTH.to_be_bytes());
+    b.extend_from_slice(&packet.frequency.to_be_bytes());
+    b.push(((packet.bandwidth + 62500) / 125000) as u8); // 125 kHz steps
+    b.push(packet.spreading_factor as u8);
+    b.push((packet.rssi + 139).clamp(0, 255) as u8);
+    b.push(0); // max rssi
+    b.push(0); // current rssi
+    b.push((packet.snr * 4.0).round().clamp(-128.0, 127.0) as i8 as u8);
+    b.push(sync_word);
+    b.extend_from_slice(&gateway_id);
+    b.extend_from_slice(&packet.count_us.to_be_bytes());
+    b.push(flags);
+    b.push(
+        packet
+            .code_rate
+            .split('/')
+            .nth(1)
+            .and_then(|v| v.get(..1))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+    );
+    b.extend_from_slice(&(packet.datarate.min(u16::MAX as u32) as u16).to_be_bytes());
+    b.push(packet.if_chain as u8);
+    b.push(packet.rf_chain as u8);
+    b.extend_from_slice(&0_u16.to_be_bytes()); // tag
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loratap_header() {
+        let packet = Packet {
+            gateway_id: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            count_us: 1000,
+            frequency: 868100000,
+            bandwidth: 125000,
+            spreading_factor: 7,
+            code_rate: "4/5".to_string(),
+            rssi: -60,
+            snr: -2.5,
+            crc_status: chirpstack_api::gw::CrcStatus::CrcOk,
+            if_chain: 2,
+            rf_chain: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec![
+                1, 0, 0, 35, // version, padding, length
+                0x33, 0xbe, 0x27, 0xa0, // frequency
+                1, 7, // bandwidth, sf
+                79, 0, 0, 0xf6, // rssi, snr
+                0x34, // sync word
+                1, 2, 3, 4, 5, 6, 7, 8, // gateway id
+                0, 0, 0x03, 0xe8, // timestamp
+                0x08, 5, // flags, cr
+                0, 0, // datarate
+                2, 1, // if chain, rf chain
+                0, 0, // tag
+            ],
+            get_loratap_header(&packet, 0x34)
+        );
+    }
+
+    #[test]
+    fn test_get_board_file() {
+        assert_eq!("", get_board_file("", 1));
+        assert_eq!(
+            "/tmp/capture_board1.pcap",
+            get_board_file("/tmp/capture.pcap", 1)
+        );
+        assert_eq!("/tmp/capture_board0", get_board_file("/tmp/capture", 0));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "concentratord_capture_test_{}.pcap",
+                std::process::id()
+            ))
+            .display()
+            .to_string();
+
+        let mut f = PcapFile::create(&Configuration {
+            file: path.clone(),
+            max_file_size: 100,
+            max_files: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(PCAP_HEADER_LENGTH, f.size);
+
+        let record = get_pcap_record(&[0; 44], SystemTime::now());
+        for _ in 0..4 {
+            f.write(&record).unwrap();
+        }
+
+        // every file contains the header and one record
+        for p in &[path.clone(), format!("{}.1", path), format!("{}.2", path)] {
+            assert_eq!(
+                PCAP_HEADER_LENGTH + record.len() as u64,
+                fs::metadata(p).unwrap().len()
+            );
+            fs::remove_file(p).unwrap();
+        }
+        assert!(fs::metadata(format!("{}.3", path)).is_err());
+    }
+}
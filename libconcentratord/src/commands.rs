@@ -2,7 +2,10 @@ use std::time::Duration;
 
 use log::info;
 use prost::Message;
+use uuid::Uuid;
 
+use super::curve;
+use super::proto;
 use super::socket::ZMQ_CONTEXT;
 
 pub fn get_socket(bind: &str) -> Result<zmq::Socket, zmq::Error> {
@@ -10,6 +13,7 @@ pub fn get_socket(bind: &str) -> Result<zmq::Socket, zmq::Error> {
 
     let zmq_ctx = ZMQ_CONTEXT.lock().unwrap();
     let sock = zmq_ctx.socket(zmq::REP)?;
+    curve::configure_socket(&sock)?;
     sock.bind(&bind)?;
     return Ok(sock);
 }
@@ -32,6 +36,18 @@ pub enum Command {
 
     // Gateway configuration.
     Configuration(chirpstack_api::gw::GatewayConfiguration),
+
+    // JIT queue request.
+    Queue,
+
+    // Cancel the queued downlink with the given downlink ID.
+    Cancel(Uuid),
+
+    // Start a test transmission.
+    TestTx(proto::TestTx),
+
+    // Stop the test transmission.
+    TestTxStop,
 }
 
 pub struct Reader<'a> {
@@ -87,6 +103,16 @@ fn handle_message(msg: Vec<Vec<u8>>) -> Result<Command, String> {
             Err(err) => Command::Error(err),
         },
         "gateway_id" => Command::GatewayID,
+        "queue" => Command::Queue,
+        "cancel" => match Uuid::from_slice(&msg[1]) {
+            Ok(v) => Command::Cancel(v),
+            Err(err) => Command::Error(format!("decode downlink_id error: {}", err)),
+        },
+        "test_tx" => match proto::TestTx::decode(&msg[1][..]) {
+            Ok(v) => Command::TestTx(v),
+            Err(err) => Command::Error(err.to_string()),
+        },
+        "test_tx_stop" => Command::TestTxStop,
         _ => Command::Unknown(command, msg[1].clone()),
     })
 }
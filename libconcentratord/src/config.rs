@@ -0,0 +1,479 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+
+use serde::de::DeserializeOwned;
+
+/// Configuration error.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The configuration file could not be read.
+    Read { file: String, error: String },
+    /// The configuration could not be parsed. When known, the position
+    /// contains the (1-based) line and column within the file.
+    Parse {
+        file: String,
+        position: Option<(usize, usize)>,
+        error: String,
+    },
+    /// The configuration value at the given location is invalid.
+    Value { location: String, error: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Read { file, error } => write!(f, "{}: read error: {}", file, error),
+            Error::Parse {
+                file,
+                position: Some((line, column)),
+                error,
+            } => write!(f, "{}:{}:{}: {}", file, line, column, error),
+            Error::Parse {
+                file,
+                position: None,
+                error,
+            } => write!(f, "{}: {}", file, error),
+            Error::Value { location, error } => write!(f, "{}: {}", location, error),
+        }
+    }
+}
+
+/// Prefix of the environment variables overriding configuration values.
+///
+/// The remainder of the variable name is the lowercase path of the value, with
+/// a double underscore as separator, e.g. CONCENTRATORD_GATEWAY__MODEL sets
+/// gateway.model.
+pub const ENV_PREFIX: &str = "CONCENTRATORD_";
+
+// Merged configuration, with for every value the layer (file or environment
+// variable) that set it and the value as it was set.
+struct Layers {
+    value: toml::value::Table,
+    origins: BTreeMap<String, (String, String)>,
+    // Environment variable values which were parsed as non-string TOML value,
+    // by key.
+    env_values: BTreeMap<String, String>,
+}
+
+/// Read and parse the given configuration files and directories. The
+/// configuration is merged in layers:
+///
+/// * the given files, in the given order
+/// * for a directory, the *.toml files within it, in lexical order
+/// * the CONCENTRATORD_* environment variables
+///
+/// A table which is defined in multiple layers is merged, any other value is
+/// replaced by the last layer which sets it.
+pub fn parse<T: DeserializeOwned>(filenames: &[String]) -> Result<T, Error> {
+    parse_vars(filenames, env::vars())
+}
+
+fn parse_vars<T, I>(filenames: &[String], vars: I) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    I: Iterator<Item = (String, String)>,
+{
+    let Layers {
+        mut value,
+        origins,
+        mut env_values,
+    } = load(filenames, vars)?;
+
+    loop {
+        let error = match toml::Value::Table(value.clone()).try_into() {
+            Ok(v) => return Ok(v),
+            Err(e) => e.to_string(),
+        };
+
+        // the deserialization error mentions the key of the invalid value,
+        // this is used to report the layer which set it.
+        let key = error
+            .rfind("for key `")
+            .map(|i| error[i + 9..].trim_end_matches('`').to_string());
+
+        // An environment variable value which was parsed as e.g. a number is
+        // retried as string, e.g. a gateway_id only containing digits.
+        if let Some(key) = &key {
+            if let Some(raw) = env_values.remove(key) {
+                set_value(&mut value, key, toml::Value::String(raw));
+                continue;
+            }
+        }
+
+        let file =
+            key.and_then(|key| get_origin(&origins, &key))
+                .unwrap_or(match filenames.is_empty() {
+                    true => "configuration".to_string(),
+                    false => filenames.join(", "),
+                });
+
+        return Err(Error::Parse {
+            file: file,
+            position: None,
+            error: error,
+        });
+    }
+}
+
+/// Print every configuration value together with the layer which set it.
+pub fn print_origins(filenames: &[String]) {
+    match load(filenames, env::vars()) {
+        Ok(layers) => {
+            for (key, (layer, value)) in &layers.origins {
+                eprintln!("{} = {} # {}", key, value, layer);
+            }
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+}
+
+fn load<I>(filenames: &[String], vars: I) -> Result<Layers, Error>
+where
+    I: Iterator<Item = (String, String)>,
+{
+    let mut layers = Layers {
+        value: toml::value::Table::new(),
+        origins: BTreeMap::new(),
+        env_values: BTreeMap::new(),
+    };
+
+    for file_name in expand_filenames(filenames)? {
+        let content = fs::read_to_string(&file_name).map_err(|e| Error::Read {
+            file: file_name.to_string(),
+            error: e.to_string(),
+        })?;
+
+        let table: toml::value::Table = toml::from_str(&content).map_err(|e| {
+            // toml appends the position to the error message, this is
+            // reported separately.
+            let mut error = e.to_string();
+            if let Some(i) = error.rfind(" at line ") {
+                error.truncate(i);
+            }
+
+            Error::Parse {
+                file: file_name.to_string(),
+                position: e.line_col().map(|(line, column)| (line + 1, column + 1)),
+                error: error,
+            }
+        })?;
+
+        merge_table(
+            &mut layers.value,
+            &mut layers.origins,
+            table,
+            "",
+            &file_name,
+        );
+    }
+
+    for (name, value) in vars {
+        if !name.starts_with(ENV_PREFIX) || name.len() == ENV_PREFIX.len() {
+            continue;
+        }
+
+        let path: Vec<String> = name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(|v| v.to_lowercase())
+            .collect();
+        if path.iter().any(|v| v.is_empty()) {
+            return Err(Error::Value {
+                location: name,
+                error: "invalid configuration path".to_string(),
+            });
+        }
+
+        // Walk the tables, the value replaces the last element of the path.
+        let mut table = &mut layers.value;
+        for key in &path[..path.len() - 1] {
+            table = match table
+                .entry(key.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+            {
+                toml::Value::Table(v) => v,
+                _ => {
+                    return Err(Error::Value {
+                        location: name,
+                        error: format!("{} is not a table", key),
+                    })
+                }
+            };
+        }
+
+        let key = &path[path.len() - 1];
+        let value = match table.get(key) {
+            // keep string values as string, e.g. a gateway_id only
+            // containing digits
+            Some(toml::Value::String(_)) => toml::Value::String(value),
+            _ => {
+                let v = parse_value(&value);
+                if !v.is_str() {
+                    layers.env_values.insert(path.join("."), value);
+                }
+                v
+            }
+        };
+
+        let mut overrides = toml::value::Table::new();
+        overrides.insert(key.to_string(), value);
+        let prefix = path[..path.len() - 1].join(".");
+        let layer = format!("${}", name);
+        merge_table(table, &mut layers.origins, overrides, &prefix, &layer);
+    }
+
+    Ok(layers)
+}
+
+// Returns the files for the given file and directory names. The *.toml files
+// within a directory are returned in lexical order.
+fn expand_filenames(filenames: &[String]) -> Result<Vec<String>, Error> {
+    let mut out: Vec<String> = Vec::new();
+
+    for file_name in filenames {
+        let is_dir = fs::metadata(file_name).map(|v| v.is_dir()).unwrap_or(false);
+
+        if !is_dir {
+            out.push(file_name.to_string());
+            continue;
+        }
+
+        let read_error = |e: std::io::Error| Error::Read {
+            file: file_name.to_string(),
+            error: e.to_string(),
+        };
+
+        let mut paths: Vec<String> = fs::read_dir(file_name)
+            .map_err(read_error)?
+            .filter_map(|v| v.ok())
+            .map(|v| v.path())
+            .filter(|v| v.is_file() && v.extension().map_or(false, |ext| ext == "toml"))
+            .map(|v| v.display().to_string())
+            .collect();
+        paths.sort();
+        out.extend(paths);
+    }
+
+    Ok(out)
+}
+
+// Parse the value of an environment variable as TOML value (e.g. a number,
+// boolean or array). Anything else is used as string.
+fn parse_value(value: &str) -> toml::Value {
+    if let Ok(mut table) = toml::from_str::<toml::value::Table>(&format!("v = {}", value)) {
+        if table.len() == 1 {
+            if let Some(v) = table.remove("v") {
+                return v;
+            }
+        }
+    }
+
+    toml::Value::String(value.to_string())
+}
+
+// Set the value for the given (dotted) key, the tables of the key must exist.
+fn set_value(table: &mut toml::value::Table, key: &str, value: toml::Value) {
+    match key.find('.') {
+        Some(i) => {
+            if let Some(toml::Value::Table(t)) = table.get_mut(&key[..i]) {
+                set_value(t, &key[i + 1..], value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+fn merge_table(
+    dst: &mut toml::value::Table,
+    origins: &mut BTreeMap<String, (String, String)>,
+    src: toml::value::Table,
+    prefix: &str,
+    layer: &str,
+) {
+    for (k, v) in src {
+        let key = match prefix.is_empty() {
+            true => k.clone(),
+            false => format!("{}.{}", prefix, k),
+        };
+
+        match (dst.get_mut(&k), v) {
+            (Some(toml::Value::Table(dst)), toml::Value::Table(src)) => {
+                merge_table(dst, origins, src, &key, layer)
+            }
+            (_, v) => {
+                // the previous value (and its origins) is replaced
+                let nested = format!("{}.", key);
+                origins.retain(|o, _| *o != key && !o.starts_with(&nested));
+                set_origins(origins, &v, &key, layer);
+                dst.insert(k, v);
+            }
+        }
+    }
+}
+
+fn set_origins(
+    origins: &mut BTreeMap<String, (String, String)>,
+    value: &toml::Value,
+    key: &str,
+    layer: &str,
+) {
+    match value {
+        toml::Value::Table(table) => {
+            for (k, v) in table {
+                set_origins(origins, v, &format!("{}.{}", key, k), layer);
+            }
+        }
+        _ => {
+            origins.insert(key.to_string(), (layer.to_string(), value.to_string()));
+        }
+    }
+}
+
+// Returns the layer which set the given key, or the closest parent key.
+fn get_origin(origins: &BTreeMap<String, (String, String)>, key: &str) -> Option<String> {
+    let mut key = key;
+    loop {
+        if let Some((layer, _)) = origins.get(key) {
+            return Some(layer.to_string());
+        }
+
+        match key.rfind('.') {
+            Some(i) => key = &key[..i],
+            None => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, Debug)]
+    struct Config {
+        #[allow(dead_code)]
+        gateway: HashMap<String, String>,
+    }
+
+    #[test]
+    fn test_parse() {
+        let dir = std::env::temp_dir();
+        let a = dir.join(format!("concentratord_config_a_{}", std::process::id()));
+        let b = dir.join(format!("concentratord_config_b_{}", std::process::id()));
+        fs::write(&a, "[gateway]\nmodel=\"foo\"\n").unwrap();
+        fs::write(&b, "\n\n  gateway_id=0102\n").unwrap();
+
+        let filenames = vec![
+            a.to_str().unwrap().to_string(),
+            b.to_str().unwrap().to_string(),
+        ];
+        let err = parse::<Config>(&filenames).unwrap_err();
+        match err {
+            Error::Parse { file, position, .. } => {
+                assert_eq!(filenames[1], file);
+                assert_eq!(3, position.unwrap().0);
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        let filenames = vec!["/does/not/exist.toml".to_string()];
+        match parse::<Config>(&filenames).unwrap_err() {
+            Error::Read { file, .. } => assert_eq!(filenames[0], file),
+            err => panic!("unexpected error: {}", err),
+        }
+
+        fs::remove_file(a).unwrap();
+        fs::remove_file(b).unwrap();
+    }
+
+    #[test]
+    fn test_parse_env_string() {
+        // the gateway_id only contains digits and is not set by a file
+        let vars = vec![
+            (
+                "CONCENTRATORD_GATEWAY__MODEL".to_string(),
+                "foo".to_string(),
+            ),
+            (
+                "CONCENTRATORD_GATEWAY__GATEWAY_ID".to_string(),
+                "1122334455667788".to_string(),
+            ),
+        ];
+        let config: Config = parse_vars(&[], vars.into_iter()).unwrap();
+        assert_eq!("1122334455667788", config.gateway["gateway_id"]);
+    }
+
+    #[test]
+    fn test_load_layers() {
+        let dir = std::env::temp_dir().join(format!("concentratord_conf_d_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("base.conf");
+        fs::write(
+            &base,
+            "[gateway]\nmodel=\"foo\"\ngateway_id=\"0102030405060708\"\nantenna_gain=0\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.toml"), "[gateway]\nantenna_gain=2\n").unwrap();
+        fs::write(
+            dir.join("a.toml"),
+            "[gateway]\nantenna_gain=1\nmodel_flags=[\"GNSS\"]\n",
+        )
+        .unwrap();
+
+        let filenames = vec![
+            base.to_str().unwrap().to_string(),
+            dir.to_str().unwrap().to_string(),
+        ];
+        let vars = vec![
+            ("HOME".to_string(), "/root".to_string()),
+            (
+                "CONCENTRATORD_GATEWAY__MODEL".to_string(),
+                "bar".to_string(),
+            ),
+            (
+                "CONCENTRATORD_GATEWAY__GATEWAY_ID".to_string(),
+                "1122334455667788".to_string(),
+            ),
+            (
+                "CONCENTRATORD_GATEWAY__BEACON__FREQUENCIES".to_string(),
+                "[869525000]".to_string(),
+            ),
+        ];
+        let layers = load(&filenames, vars.into_iter()).unwrap();
+
+        let gateway = layers.value["gateway"].as_table().unwrap();
+        assert_eq!("bar", gateway["model"].as_str().unwrap());
+        assert_eq!("1122334455667788", gateway["gateway_id"].as_str().unwrap());
+        assert_eq!(2, gateway["antenna_gain"].as_integer().unwrap());
+        assert_eq!(1, gateway["model_flags"].as_array().unwrap().len());
+        assert_eq!(
+            869525000,
+            gateway["beacon"]["frequencies"][0].as_integer().unwrap()
+        );
+
+        assert_eq!(
+            "$CONCENTRATORD_GATEWAY__MODEL",
+            layers.origins["gateway.model"].0
+        );
+        assert_eq!(
+            dir.join("b.toml").to_str().unwrap(),
+            layers.origins["gateway.antenna_gain"].0
+        );
+        assert_eq!(
+            dir.join("a.toml").to_str().unwrap(),
+            layers.origins["gateway.model_flags"].0
+        );
+
+        let vars = vec![(
+            "CONCENTRATORD_GATEWAY__MODEL__NAME".to_string(),
+            "x".to_string(),
+        )];
+        assert!(load(&filenames, vars.into_iter()).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
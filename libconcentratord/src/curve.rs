@@ -0,0 +1,159 @@
+use std::sync::Mutex;
+use std::thread;
+
+use log::{debug, error, info, warn};
+
+use super::socket::ZMQ_CONTEXT;
+
+// ZAP (ZeroMQ Authentication Protocol) handler endpoint, see
+// https://rfc.zeromq.org/spec/27/.
+const ZAP_ENDPOINT: &str = "inproc://zeromq.zap.01";
+const ZAP_DOMAIN: &str = "concentratord";
+
+struct Curve {
+    secret_key: Vec<u8>,
+    allowed_clients: Vec<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref CURVE: Mutex<Option<Curve>> = Mutex::new(None);
+    static ref ZAP_STARTED: Mutex<bool> = Mutex::new(false);
+}
+
+/// Decode the given Z85 encoded CURVE key.
+pub fn decode_key(key: &str) -> Result<Vec<u8>, String> {
+    if key.len() != 40 {
+        return Err("key must be exactly 40 characters (Z85 encoded)".to_string());
+    }
+
+    zmq::z85_decode(key).map_err(|e| e.to_string())
+}
+
+/// Generate a new CURVE keypair. This returns the Z85 encoded public and
+/// secret key.
+pub fn generate_keypair() -> Result<(String, String), String> {
+    let pair = zmq::CurveKeyPair::new().map_err(|e| e.to_string())?;
+    let public_key = zmq::z85_encode(&pair.public_key).map_err(|e| e.to_string())?;
+    let secret_key = zmq::z85_encode(&pair.secret_key).map_err(|e| e.to_string())?;
+
+    return Ok((public_key, secret_key));
+}
+
+/// Setup CURVE authentication and encryption for the API sockets which are
+/// created after this call. When the secret_key is empty, CURVE is disabled.
+/// When allowed_clients is empty, any client knowing the server public key
+/// is accepted, else only the clients with one of the given public keys.
+pub fn setup(secret_key: &str, allowed_clients: &[String]) -> Result<(), String> {
+    let mut curve = CURVE.lock().unwrap();
+
+    if secret_key.is_empty() {
+        *curve = None;
+        return Ok(());
+    }
+
+    info!(
+        "Enabling CURVE security for API sockets, allowed_clients: {}",
+        allowed_clients.len()
+    );
+
+    *curve = Some(Curve {
+        secret_key: decode_key(secret_key)?,
+        allowed_clients: allowed_clients
+            .iter()
+            .map(|k| decode_key(k))
+            .collect::<Result<Vec<Vec<u8>>, String>>()?,
+    });
+
+    start_zap_handler()
+}
+
+/// Configure the given (server) socket for CURVE, when enabled. This must be
+/// called before binding the socket.
+pub fn configure_socket(sock: &zmq::Socket) -> Result<(), zmq::Error> {
+    let curve = CURVE.lock().unwrap();
+
+    if let Some(curve) = curve.as_ref() {
+        sock.set_curve_server(true)?;
+        sock.set_curve_secretkey(&curve.secret_key)?;
+        sock.set_zap_domain(ZAP_DOMAIN)?;
+    }
+
+    return Ok(());
+}
+
+fn start_zap_handler() -> Result<(), String> {
+    let mut started = ZAP_STARTED.lock().unwrap();
+    if *started {
+        return Ok(());
+    }
+
+    // The socket is bound before returning, so that the handler is available
+    // before the first client connects.
+    let sock = {
+        let zmq_ctx = ZMQ_CONTEXT.lock().unwrap();
+        zmq_ctx.socket(zmq::REP).map_err(|e| e.to_string())?
+    };
+    sock.bind(ZAP_ENDPOINT).map_err(|e| e.to_string())?;
+
+    thread::spawn(move || zap_loop(sock));
+    *started = true;
+
+    return Ok(());
+}
+
+fn zap_loop(sock: zmq::Socket) {
+    debug!("Starting ZAP handler loop");
+
+    loop {
+        let msg = match sock.recv_multipart(0) {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Receive ZAP request error, error: {}", err);
+                continue;
+            }
+        };
+
+        // frames: version, request id, domain, address, identity, mechanism,
+        // credentials (the client public key for CURVE)
+        let (status_code, status_text) = match (msg.get(5), msg.get(6)) {
+            (Some(mechanism), Some(client_key)) if mechanism == b"CURVE" => {
+                match is_allowed(client_key) {
+                    true => ("200", "OK"),
+                    false => {
+                        warn!(
+                            "Rejecting API client, address: {}, public_key: {}",
+                            String::from_utf8_lossy(&msg[3]),
+                            zmq::z85_encode(client_key).unwrap_or_default()
+                        );
+                        ("400", "Client public key is not allowed")
+                    }
+                }
+            }
+            _ => ("400", "CURVE credentials are required"),
+        };
+
+        let resp: Vec<&[u8]> = vec![
+            &b"1.0"[..],
+            msg.get(1).map_or(&b""[..], |v| &v[..]),
+            status_code.as_bytes(),
+            status_text.as_bytes(),
+            &b""[..],
+            &b""[..],
+        ];
+        if let Err(err) = sock.send_multipart(resp, 0) {
+            error!("Send ZAP response error, error: {}", err);
+        }
+    }
+}
+
+fn is_allowed(client_key: &[u8]) -> bool {
+    let curve = CURVE.lock().unwrap();
+
+    match curve.as_ref() {
+        Some(curve) => {
+            curve.allowed_clients.is_empty()
+                || curve.allowed_clients.iter().any(|k| k == client_key)
+        }
+        None => true,
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use log::info;
+
+/// Regulatory sub-band.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Band {
+    pub name: String,
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    /// Max duty-cycle (e.g. 0.01 for 1%).
+    pub duty_cycle: f32,
+}
+
+/// Duty-cycle accountant.
+///
+/// This keeps track of the transmit time per sub-band over a sliding window.
+/// Transmissions are accounted at the time they are scheduled, this means
+/// that packets which are still in the queue are taken into account.
+pub struct DutyCycle {
+    window: Duration,
+    bands: Vec<(Band, VecDeque<(Instant, Duration)>)>,
+}
+
+impl DutyCycle {
+    pub fn new(window: Duration, bands: Vec<Band>) -> DutyCycle {
+        for b in &bands {
+            info!(
+                "Configuring duty-cycle band, name: {}, frequency_min: {}, frequency_max: {}, duty_cycle: {}",
+                b.name, b.frequency_min, b.frequency_max, b.duty_cycle
+            );
+        }
+
+        DutyCycle {
+            window: window,
+            bands: bands.into_iter().map(|b| (b, VecDeque::new())).collect(),
+        }
+    }
+
+    /// Reserve the time on air for a transmission at tx_time. An error is
+    /// returned when this would exceed the duty-cycle of the sub-band.
+    /// Frequencies which are not within a configured sub-band are not
+    /// restricted.
+    pub fn reserve(
+        &mut self,
+        now: Instant,
+        tx_time: Instant,
+        frequency: u32,
+        time_on_air: Duration,
+    ) -> Result<(), String> {
+        self.cleanup(now);
+
+        let window = self.window;
+        let (band, transmissions) = match self
+            .bands
+            .iter_mut()
+            .find(|(b, _)| frequency >= b.frequency_min && frequency <= b.frequency_max)
+        {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let used = get_used(transmissions, window, tx_time);
+        let max = window.mul_f32(band.duty_cycle);
+        if used + time_on_air > max {
+            return Err(format!(
+                "duty-cycle overflow, band: {}, used: {:?}, time_on_air: {:?}, max: {:?}",
+                band.name, used, time_on_air, max
+            ));
+        }
+
+        transmissions.push_back((tx_time, time_on_air));
+
+        return Ok(());
+    }
+
+    /// Release the reservation of the transmission at tx_time, e.g. when the
+    /// transmission has been cancelled.
+    pub fn release(&mut self, tx_time: Instant, frequency: u32, time_on_air: Duration) {
+        if let Some((_, transmissions)) = self
+            .bands
+            .iter_mut()
+            .find(|(b, _)| frequency >= b.frequency_min && frequency <= b.frequency_max)
+        {
+            if let Some(i) = transmissions
+                .iter()
+                .position(|t| *t == (tx_time, time_on_air))
+            {
+                transmissions.remove(i);
+            }
+        }
+    }
+
+    /// Returns the duty-cycle usage per sub-band (e.g. 0.005 for 0.5%) over
+    /// the window ending at now, including the scheduled transmissions.
+    pub fn get_usage(&mut self, now: Instant) -> Vec<(String, f32)> {
+        self.cleanup(now);
+
+        let window = self.window;
+        self.bands
+            .iter()
+            .map(|(b, transmissions)| {
+                (
+                    b.name.clone(),
+                    get_used(transmissions, window, now).as_secs_f32() / window.as_secs_f32(),
+                )
+            })
+            .collect()
+    }
+
+    fn cleanup(&mut self, now: Instant) {
+        let window = self.window;
+
+        for (_, transmissions) in self.bands.iter_mut() {
+            transmissions.retain(|(tx_time, _)| *tx_time + window > now);
+        }
+    }
+}
+
+fn get_used(
+    transmissions: &VecDeque<(Instant, Duration)>,
+    window: Duration,
+    end: Instant,
+) -> Duration {
+    transmissions
+        .iter()
+        .filter(|(tx_time, _)| match end.checked_sub(window) {
+            Some(start) => *tx_time > start,
+            None => true,
+        })
+        .map(|(_, time_on_air)| *time_on_air)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_duty_cycle() -> DutyCycle {
+        DutyCycle::new(
+            Duration::from_secs(100),
+            vec![Band {
+                name: "g1".to_string(),
+                frequency_min: 868000000,
+                frequency_max: 868600000,
+                duty_cycle: 0.01,
+            }],
+        )
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut dc = get_duty_cycle();
+        let now = Instant::now();
+
+        dc.reserve(now, now, 868100000, Duration::from_millis(600))
+            .unwrap();
+        dc.reserve(now, now, 868100000, Duration::from_millis(400))
+            .unwrap();
+
+        // exceeds the 1s budget
+        assert!(dc
+            .reserve(now, now, 868100000, Duration::from_millis(1))
+            .is_err());
+
+        // frequency outside the configured bands
+        dc.reserve(now, now, 869525000, Duration::from_secs(10))
+            .unwrap();
+
+        // first transmissions are out of the window
+        let later = now + Duration::from_secs(100);
+        dc.reserve(later, later, 868100000, Duration::from_millis(1000))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_usage() {
+        let mut dc = get_duty_cycle();
+        let now = Instant::now();
+
+        assert_eq!(vec![("g1".to_string(), 0.0)], dc.get_usage(now));
+
+        dc.reserve(now, now, 868100000, Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(vec![("g1".to_string(), 0.005)], dc.get_usage(now));
+        assert_eq!(
+            vec![("g1".to_string(), 0.0)],
+            dc.get_usage(now + Duration::from_secs(100))
+        );
+    }
+
+    #[test]
+    fn test_release() {
+        let mut dc = get_duty_cycle();
+        let now = Instant::now();
+
+        dc.reserve(now, now, 868100000, Duration::from_millis(1000))
+            .unwrap();
+        dc.release(now, 868100000, Duration::from_millis(1000));
+        assert_eq!(vec![("g1".to_string(), 0.0)], dc.get_usage(now));
+    }
+}
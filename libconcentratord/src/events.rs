@@ -4,6 +4,7 @@ use log::info;
 use prost::Message;
 use uuid::Uuid;
 
+use super::curve;
 use super::socket::ZMQ_CONTEXT;
 
 lazy_static! {
@@ -17,6 +18,7 @@ pub fn bind_socket(bind: &str) -> Result<(), zmq::Error> {
     let mut zmq_pub = ZMQ_PUB.lock().unwrap();
 
     let sock = zmq_ctx.socket(zmq::PUB)?;
+    curve::configure_socket(&sock)?;
     sock.bind(&bind)?;
 
     *zmq_pub = Some(sock);
@@ -36,7 +38,19 @@ pub fn send_uplink(pl: &chirpstack_api::gw::UplinkFrame) -> Result<(), String> {
     return Ok(());
 }
 
-pub fn send_stats(stats: &chirpstack_api::gw::GatewayStats, stats_id: &Uuid) -> Result<(), String> {
+pub fn send_tx_ack(pl: &chirpstack_api::gw::DownlinkTxAck) -> Result<(), String> {
+    let pub_guard = ZMQ_PUB.lock().unwrap();
+    let publisher = pub_guard.as_ref().unwrap();
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).unwrap();
+    publisher.send("txack", zmq::SNDMORE).unwrap();
+    publisher.send(buf, 0).unwrap();
+
+    return Ok(());
+}
+
+pub fn send_stats(stats: &super::proto::GatewayStats, stats_id: &Uuid) -> Result<(), String> {
     let pub_guard = ZMQ_PUB.lock().unwrap();
     let publisher = pub_guard.as_ref().unwrap();
 
@@ -49,3 +63,20 @@ pub fn send_stats(stats: &chirpstack_api::gw::GatewayStats, stats_id: &Uuid) ->
 
     return Ok(());
 }
+
+pub fn send_spectral_scan(pl: &super::proto::SpectralScanResult) -> Result<(), String> {
+    let pub_guard = ZMQ_PUB.lock().unwrap();
+    let publisher = pub_guard.as_ref().unwrap();
+
+    info!(
+        "Publishing spectral scan event, freq: {}, nb_scan: {}",
+        pl.frequency, pl.nb_scan
+    );
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).unwrap();
+    publisher.send("spectral_scan", zmq::SNDMORE).unwrap();
+    publisher.send(buf, 0).unwrap();
+
+    return Ok(());
+}
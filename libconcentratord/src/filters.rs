@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    static ref FILTERS: Mutex<Option<Filters>> = Mutex::new(None);
+}
+
+/// Uplink filter configuration.
+///
+/// NetIDs are given as 6 hex characters (e.g. 000013), DevAddr prefixes as
+/// hex DevAddr and prefix length (e.g. 26000000/7) and JoinEUI prefixes as hex
+/// JoinEUI and prefix length (e.g. 70b3d57ed0000000/36). An empty allow list
+/// allows all values.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Configuration {
+    pub forward_crc_ok: bool,
+    pub forward_crc_invalid: bool,
+    pub forward_crc_missing: bool,
+    pub net_ids: Vec<String>,
+    pub deny_net_ids: Vec<String>,
+    pub dev_addr_prefixes: Vec<String>,
+    pub deny_dev_addr_prefixes: Vec<String>,
+    pub join_eui_prefixes: Vec<String>,
+    pub deny_join_eui_prefixes: Vec<String>,
+    pub rate_limit_max_frames: u32,
+    #[serde(with = "humantime_serde")]
+    pub rate_limit_interval: Duration,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            forward_crc_ok: true,
+            forward_crc_invalid: true,
+            forward_crc_missing: true,
+            net_ids: vec![],
+            deny_net_ids: vec![],
+            dev_addr_prefixes: vec![],
+            deny_dev_addr_prefixes: vec![],
+            join_eui_prefixes: vec![],
+            deny_join_eui_prefixes: vec![],
+            rate_limit_max_frames: 0,
+            rate_limit_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The reason for dropping an uplink.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Reason {
+    CrcStatus,
+    InvalidPayload,
+    NetId,
+    DevAddr,
+    JoinEui,
+    RateLimit,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Reason::CrcStatus => "crc_status",
+            Reason::InvalidPayload => "invalid_payload",
+            Reason::NetId => "net_id",
+            Reason::DevAddr => "dev_addr",
+            Reason::JoinEui => "join_eui",
+            Reason::RateLimit => "rate_limit",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DevAddrPrefix {
+    prefix: u32,
+    size: u32,
+}
+
+impl DevAddrPrefix {
+    /// Returns the DevAddr prefix of the given NetID, see the LoRaWAN Backend
+    /// Interfaces specification.
+    pub fn from_net_id(net_id: u32) -> DevAddrPrefix {
+        let net_type = net_id >> 21;
+        let nwk_id_size = match net_type {
+            0 | 1 => 6,
+            2 => 9,
+            3 => 11,
+            4 => 12,
+            5 => 13,
+            6 => 15,
+            _ => 17,
+        };
+
+        // the type prefix is net_type times 1 followed by a 0
+        let type_prefix = ((1 << net_type) - 1) << 1;
+        let nwk_id = net_id & ((1 << nwk_id_size) - 1);
+        let size = net_type + 1 + nwk_id_size;
+
+        DevAddrPrefix {
+            prefix: ((type_prefix << nwk_id_size) | nwk_id) << (32 - size),
+            size: size,
+        }
+    }
+
+    pub fn matches(&self, dev_addr: u32) -> bool {
+        self.size == 0 || dev_addr >> (32 - self.size) == self.prefix >> (32 - self.size)
+    }
+}
+
+impl FromStr for DevAddrPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, size) = parse_prefix(s, 8, 32)?;
+        Ok(DevAddrPrefix {
+            prefix: prefix as u32,
+            size: size,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EuiPrefix {
+    prefix: u64,
+    size: u32,
+}
+
+impl EuiPrefix {
+    pub fn matches(&self, eui: u64) -> bool {
+        self.size == 0 || eui >> (64 - self.size) == self.prefix >> (64 - self.size)
+    }
+}
+
+impl FromStr for EuiPrefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, size) = parse_prefix(s, 16, 64)?;
+        Ok(EuiPrefix {
+            prefix: prefix,
+            size: size,
+        })
+    }
+}
+
+/// Parse the given NetID (6 hex characters).
+pub fn parse_net_id(s: &str) -> Result<u32, String> {
+    if s.len() != 6 {
+        return Err(format!("invalid net_id: {}, expected 6 hex characters", s));
+    }
+
+    u32::from_str_radix(s, 16).map_err(|_| format!("invalid net_id: {}", s))
+}
+
+fn parse_prefix(s: &str, hex_len: usize, max_size: u32) -> Result<(u64, u32), String> {
+    let err = || {
+        format!(
+            "invalid prefix: {}, expected {} hex characters followed by /<prefix length>",
+            s, hex_len
+        )
+    };
+
+    let mut parts = s.splitn(2, '/');
+    let prefix = parts.next().unwrap_or_default();
+    let size = parts.next().ok_or_else(err)?;
+
+    if prefix.len() != hex_len {
+        return Err(err());
+    }
+    let prefix = u64::from_str_radix(prefix, 16).map_err(|_| err())?;
+    let size: u32 = size.parse().map_err(|_| err())?;
+    if size > max_size {
+        return Err(format!(
+            "invalid prefix: {}, prefix length must be <= {}",
+            s, max_size
+        ));
+    }
+
+    return Ok((prefix, size));
+}
+
+// The fields of the LoRaWAN PHYPayload which are used for filtering.
+#[derive(Debug, PartialEq)]
+enum Frame {
+    JoinRequest { join_eui: u64 },
+    RejoinRequest { net_id: u32 },
+    RejoinRequestJoinEui { join_eui: u64 },
+    Data { dev_addr: u32 },
+    Other,
+}
+
+impl Frame {
+    fn from_slice(b: &[u8]) -> Result<Frame, String> {
+        if b.is_empty() {
+            return Err("empty phy_payload".to_string());
+        }
+
+        // MHDR: MType (3 bits) | RFU (3 bits) | Major (2 bits)
+        match b[0] >> 5 {
+            // join-request: MHDR | JoinEUI | DevEUI | DevNonce | MIC
+            0x00 => {
+                check_len(b, 23)?;
+                Ok(Frame::JoinRequest {
+                    join_eui: read_u64_le(&b[1..9]),
+                })
+            }
+            // unconfirmed / confirmed data-up: MHDR | DevAddr | FCtrl | FCnt | ... | MIC
+            0x02 | 0x04 => {
+                check_len(b, 12)?;
+                Ok(Frame::Data {
+                    dev_addr: read_u32_le(&b[1..5]),
+                })
+            }
+            // rejoin-request: MHDR | Rejoin type | ...
+            0x06 => {
+                check_len(b, 2)?;
+                match b[1] {
+                    // NetID | DevEUI | RJcount0 | MIC
+                    0x00 | 0x02 => {
+                        check_len(b, 19)?;
+                        Ok(Frame::RejoinRequest {
+                            net_id: read_u32_le(&[b[2], b[3], b[4], 0]),
+                        })
+                    }
+                    // JoinEUI | DevEUI | RJcount1 | MIC
+                    0x01 => {
+                        check_len(b, 24)?;
+                        Ok(Frame::RejoinRequestJoinEui {
+                            join_eui: read_u64_le(&b[2..10]),
+                        })
+                    }
+                    _ => Err(format!("invalid rejoin type: {}", b[1])),
+                }
+            }
+            // downlink and proprietary frames
+            _ => Ok(Frame::Other),
+        }
+    }
+}
+
+fn check_len(b: &[u8], len: usize) -> Result<(), String> {
+    if b.len() < len {
+        return Err(format!(
+            "phy_payload too short, expected at least {} bytes, got: {}",
+            len,
+            b.len()
+        ));
+    }
+
+    return Ok(());
+}
+
+fn read_u32_le(b: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(b);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64_le(b: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(b);
+    u64::from_le_bytes(buf)
+}
+
+pub struct Filters {
+    forward_crc_ok: bool,
+    forward_crc_invalid: bool,
+    forward_crc_missing: bool,
+    net_ids: Vec<u32>,
+    deny_net_ids: Vec<u32>,
+    dev_addr_prefixes: Vec<DevAddrPrefix>,
+    deny_dev_addr_prefixes: Vec<DevAddrPrefix>,
+    join_eui_prefixes: Vec<EuiPrefix>,
+    deny_join_eui_prefixes: Vec<EuiPrefix>,
+    rate_limit_max_frames: u32,
+    rate_limit_interval: Duration,
+
+    // DevAddr => (start of the rate-limit window, frames within window)
+    rate_limits: HashMap<u32, (Instant, u32)>,
+    rate_limits_cleaned: Option<Instant>,
+}
+
+impl Filters {
+    /// Create the filters from the given configuration. The DevAddr prefixes
+    /// of the (deny) NetIDs are added to the (deny) DevAddr prefixes.
+    pub fn new(config: &Configuration) -> Result<Filters, String> {
+        let net_ids = parse_list(&config.net_ids, "net_ids", parse_net_id)?;
+        let deny_net_ids = parse_list(&config.deny_net_ids, "deny_net_ids", parse_net_id)?;
+
+        let mut dev_addr_prefixes =
+            parse_list(&config.dev_addr_prefixes, "dev_addr_prefixes", |s| {
+                s.parse()
+            })?;
+        dev_addr_prefixes.extend(net_ids.iter().map(|v| DevAddrPrefix::from_net_id(*v)));
+
+        let mut deny_dev_addr_prefixes = parse_list(
+            &config.deny_dev_addr_prefixes,
+            "deny_dev_addr_prefixes",
+            |s| s.parse(),
+        )?;
+        deny_dev_addr_prefixes.extend(deny_net_ids.iter().map(|v| DevAddrPrefix::from_net_id(*v)));
+
+        if config.rate_limit_max_frames != 0 && config.rate_limit_interval == Duration::from_secs(0)
+        {
+            return Err("rate_limit_interval must be set when rate limiting".to_string());
+        }
+
+        Ok(Filters {
+            forward_crc_ok: config.forward_crc_ok,
+            forward_crc_invalid: config.forward_crc_invalid,
+            forward_crc_missing: config.forward_crc_missing,
+            net_ids: net_ids,
+            deny_net_ids: deny_net_ids,
+            dev_addr_prefixes: dev_addr_prefixes,
+            deny_dev_addr_prefixes: deny_dev_addr_prefixes,
+            join_eui_prefixes: parse_list(&config.join_eui_prefixes, "join_eui_prefixes", |s| {
+                s.parse()
+            })?,
+            deny_join_eui_prefixes: parse_list(
+                &config.deny_join_eui_prefixes,
+                "deny_join_eui_prefixes",
+                |s| s.parse(),
+            )?,
+            rate_limit_max_frames: config.rate_limit_max_frames,
+            rate_limit_interval: config.rate_limit_interval,
+            rate_limits: HashMap::new(),
+            rate_limits_cleaned: None,
+        })
+    }
+
+    /// Check if the given uplink must be forwarded. In case it must be dropped,
+    /// the reason is returned.
+    pub fn check(
+        &mut self,
+        pl: &chirpstack_api::gw::UplinkFrame,
+        now: Instant,
+    ) -> Result<(), Reason> {
+        let crc_status = pl
+            .rx_info
+            .as_ref()
+            .map_or(chirpstack_api::gw::CrcStatus::NoCrc, |v| v.crc_status());
+        let forward = match crc_status {
+            chirpstack_api::gw::CrcStatus::CrcOk => self.forward_crc_ok,
+            chirpstack_api::gw::CrcStatus::BadCrc => self.forward_crc_invalid,
+            chirpstack_api::gw::CrcStatus::NoCrc => self.forward_crc_missing,
+        };
+        if !forward {
+            return Err(Reason::CrcStatus);
+        }
+
+        let frame = match Frame::from_slice(&pl.phy_payload) {
+            Ok(v) => v,
+            Err(_) => {
+                // Only drop frames which can't be parsed when address based
+                // filtering is used.
+                if self.has_address_filters() {
+                    return Err(Reason::InvalidPayload);
+                }
+                return Ok(());
+            }
+        };
+
+        match frame {
+            Frame::Data { dev_addr } => {
+                if !is_allowed(&self.dev_addr_prefixes, &self.deny_dev_addr_prefixes, |p| {
+                    p.matches(dev_addr)
+                }) {
+                    return Err(Reason::DevAddr);
+                }
+
+                if !self.rate_limit(dev_addr, now) {
+                    return Err(Reason::RateLimit);
+                }
+            }
+            Frame::JoinRequest { join_eui } | Frame::RejoinRequestJoinEui { join_eui } => {
+                if !is_allowed(&self.join_eui_prefixes, &self.deny_join_eui_prefixes, |p| {
+                    p.matches(join_eui)
+                }) {
+                    return Err(Reason::JoinEui);
+                }
+            }
+            Frame::RejoinRequest { net_id } => {
+                if !is_allowed(&self.net_ids, &self.deny_net_ids, |v| *v == net_id) {
+                    return Err(Reason::NetId);
+                }
+            }
+            Frame::Other => {}
+        }
+
+        return Ok(());
+    }
+
+    fn has_address_filters(&self) -> bool {
+        !self.dev_addr_prefixes.is_empty()
+            || !self.deny_dev_addr_prefixes.is_empty()
+            || !self.join_eui_prefixes.is_empty()
+            || !self.deny_join_eui_prefixes.is_empty()
+    }
+
+    // Fixed window rate limiting per DevAddr. This returns false when the
+    // max frames within the window have been exceeded.
+    fn rate_limit(&mut self, dev_addr: u32, now: Instant) -> bool {
+        if self.rate_limit_max_frames == 0 {
+            return true;
+        }
+
+        let interval = self.rate_limit_interval;
+
+        // remove the expired windows, at most once per interval
+        if self
+            .rate_limits_cleaned
+            .map_or(true, |v| now.duration_since(v) >= interval)
+        {
+            self.rate_limits
+                .retain(|_, (start, _)| now.duration_since(*start) < interval);
+            self.rate_limits_cleaned = Some(now);
+        }
+
+        let window = self.rate_limits.entry(dev_addr).or_insert((now, 0));
+        if now.duration_since(window.0) >= interval {
+            *window = (now, 0);
+        }
+        window.1 += 1;
+
+        window.1 <= self.rate_limit_max_frames
+    }
+}
+
+fn parse_list<T, F>(values: &[String], key: &str, f: F) -> Result<Vec<T>, String>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| f(v).map_err(|e| format!("{}[{}]: {}", key, i, e)))
+        .collect()
+}
+
+fn is_allowed<T, F>(allow: &[T], deny: &[T], matches: F) -> bool
+where
+    F: Fn(&T) -> bool,
+{
+    if deny.iter().any(|v| matches(v)) {
+        return false;
+    }
+
+    allow.is_empty() || allow.iter().any(|v| matches(v))
+}
+
+/// Setup the uplink filters used by the check function.
+pub fn setup(config: &Configuration) -> Result<(), String> {
+    let filters = Filters::new(config)?;
+    *FILTERS.lock().unwrap() = Some(filters);
+    return Ok(());
+}
+
+/// Check if the given uplink must be forwarded, using the filters configured
+/// by setup. In case it must be dropped, the reason is returned.
+pub fn check(pl: &chirpstack_api::gw::UplinkFrame) -> Result<(), Reason> {
+    let mut filters = FILTERS.lock().unwrap();
+
+    match filters.as_mut() {
+        Some(v) => v.check(pl, Instant::now()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uplink(
+        crc_status: chirpstack_api::gw::CrcStatus,
+        phy_payload: &[u8],
+    ) -> chirpstack_api::gw::UplinkFrame {
+        let mut rx_info: chirpstack_api::gw::UplinkRxInfo = Default::default();
+        rx_info.set_crc_status(crc_status);
+
+        chirpstack_api::gw::UplinkFrame {
+            phy_payload: phy_payload.to_vec(),
+            rx_info: Some(rx_info),
+            ..Default::default()
+        }
+    }
+
+    fn data_up(dev_addr: u32) -> Vec<u8> {
+        let mut b = vec![0x40];
+        b.extend_from_slice(&dev_addr.to_le_bytes());
+        b.extend_from_slice(&[0x00, 0x01, 0x00, 0x01, 0x02, 0x03, 0x04]);
+        b
+    }
+
+    fn join_request(join_eui: u64) -> Vec<u8> {
+        let mut b = vec![0x00];
+        b.extend_from_slice(&join_eui.to_le_bytes());
+        b.extend_from_slice(&[0x01; 14]);
+        b
+    }
+
+    #[test]
+    fn test_dev_addr_prefix() {
+        // TTN NetID
+        let p = DevAddrPrefix::from_net_id(0x000013);
+        assert_eq!(
+            DevAddrPrefix {
+                prefix: 0x26000000,
+                size: 7
+            },
+            p
+        );
+        assert!(p.matches(0x26011234));
+        assert!(p.matches(0x27ffffff));
+        assert!(!p.matches(0x28000000));
+
+        // type 3 NetID, NwkID 0x0007 (11 bits)
+        assert_eq!(
+            DevAddrPrefix {
+                prefix: 0xe00e0000,
+                size: 15
+            },
+            DevAddrPrefix::from_net_id(0x600007)
+        );
+
+        assert_eq!(
+            Ok(DevAddrPrefix {
+                prefix: 0x26000000,
+                size: 7
+            }),
+            "26000000/7".parse()
+        );
+        assert!("26000000".parse::<DevAddrPrefix>().is_err());
+        assert!("26000000/33".parse::<DevAddrPrefix>().is_err());
+        assert!("0000000000000000/0"
+            .parse::<EuiPrefix>()
+            .unwrap()
+            .matches(0x0102030405060708));
+    }
+
+    #[test]
+    fn test_frame_from_slice() {
+        assert_eq!(
+            Frame::Data {
+                dev_addr: 0x26011234
+            },
+            Frame::from_slice(&data_up(0x26011234)).unwrap()
+        );
+        assert_eq!(
+            Frame::JoinRequest {
+                join_eui: 0x70b3d57ed0000001
+            },
+            Frame::from_slice(&join_request(0x70b3d57ed0000001)).unwrap()
+        );
+        assert_eq!(
+            Frame::RejoinRequest { net_id: 0x000013 },
+            Frame::from_slice(&[
+                0xc0, 0x00, 0x13, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x00,
+                0x00, 0x01, 0x02, 0x03, 0x04
+            ])
+            .unwrap()
+        );
+        assert_eq!(Frame::Other, Frame::from_slice(&[0xe0, 0x01]).unwrap());
+        assert!(Frame::from_slice(&[0x40, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let now = Instant::now();
+        let mut filters = Filters::new(&Configuration {
+            forward_crc_invalid: false,
+            net_ids: vec!["000013".to_string()],
+            deny_dev_addr_prefixes: vec!["26010000/16".to_string()],
+            deny_join_eui_prefixes: vec!["70b3d57ed0000000/40".to_string()],
+            rate_limit_max_frames: 2,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let tests = vec![
+            (
+                "bad crc",
+                uplink(chirpstack_api::gw::CrcStatus::BadCrc, &data_up(0x26000001)),
+                Err(Reason::CrcStatus),
+            ),
+            (
+                "allowed net_id",
+                uplink(chirpstack_api::gw::CrcStatus::CrcOk, &data_up(0x26000001)),
+                Ok(()),
+            ),
+            (
+                "foreign net_id",
+                uplink(chirpstack_api::gw::CrcStatus::CrcOk, &data_up(0x01000001)),
+                Err(Reason::DevAddr),
+            ),
+            (
+                "denied dev_addr prefix",
+                uplink(chirpstack_api::gw::CrcStatus::CrcOk, &data_up(0x26010001)),
+                Err(Reason::DevAddr),
+            ),
+            (
+                "allowed join_eui",
+                uplink(
+                    chirpstack_api::gw::CrcStatus::CrcOk,
+                    &join_request(0x70b3d57ed1000001),
+                ),
+                Ok(()),
+            ),
+            (
+                "denied join_eui",
+                uplink(
+                    chirpstack_api::gw::CrcStatus::CrcOk,
+                    &join_request(0x70b3d57ed0000001),
+                ),
+                Err(Reason::JoinEui),
+            ),
+            (
+                "invalid payload",
+                uplink(chirpstack_api::gw::CrcStatus::CrcOk, &[0x40, 0x01]),
+                Err(Reason::InvalidPayload),
+            ),
+            (
+                "proprietary",
+                uplink(chirpstack_api::gw::CrcStatus::CrcOk, &[0xe0, 0x01]),
+                Ok(()),
+            ),
+        ];
+
+        for (name, pl, expected) in tests {
+            assert_eq!(expected, filters.check(&pl, now), "{}", name);
+        }
+
+        // rate limit (the first frame was received in the test above)
+        let pl = uplink(chirpstack_api::gw::CrcStatus::CrcOk, &data_up(0x26000001));
+        assert_eq!(Ok(()), filters.check(&pl, now));
+        assert_eq!(Err(Reason::RateLimit), filters.check(&pl, now));
+        assert_eq!(Ok(()), filters.check(&pl, now + Duration::from_secs(60)));
+    }
+}
@@ -1,6 +1,99 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::dutycycle::DutyCycle;
+use super::metrics;
+use super::proto;
+
+// Default number of packets which can be queued.
+const DEFAULT_CAPACITY: usize = 32;
+
+/// JIT queue configuration. Values which are not set fall back to the value
+/// of the gateway model, or else to the default value.
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Configuration {
+    /// Time between the scheduled transmission time and the start of the
+    /// emission.
+    #[serde(with = "humantime_serde")]
+    pub tx_start_delay: Option<Duration>,
+    /// Minimum time between two transmissions.
+    #[serde(with = "humantime_serde")]
+    pub tx_margin_delay: Option<Duration>,
+    /// Time in advance of the transmission at which a packet is sent to the
+    /// concentrator. This must cover the hal::send latency.
+    #[serde(with = "humantime_serde")]
+    pub tx_jit_delay: Option<Duration>,
+    /// Maximum time in advance for which a packet can be enqueued.
+    #[serde(with = "humantime_serde")]
+    pub tx_max_advance_delay: Option<Duration>,
+    /// Maximum number of queued packets.
+    pub capacity: Option<usize>,
+    /// Measure the latency between sending a packet to the concentrator and
+    /// the start of the emission, see Calibration.
+    pub calibrate: bool,
+}
+
+impl Configuration {
+    /// Returns the configuration in which the values set in other override
+    /// the values of self.
+    pub fn merge(&self, other: &Configuration) -> Configuration {
+        Configuration {
+            tx_start_delay: other.tx_start_delay.or(self.tx_start_delay),
+            tx_margin_delay: other.tx_margin_delay.or(self.tx_margin_delay),
+            tx_jit_delay: other.tx_jit_delay.or(self.tx_jit_delay),
+            tx_max_advance_delay: other.tx_max_advance_delay.or(self.tx_max_advance_delay),
+            capacity: other.capacity.or(self.capacity),
+            calibrate: self.calibrate || other.calibrate,
+        }
+    }
+}
+
+/// TX latency measurements, when calibration is enabled.
+///
+/// For every transmitted packet, the time needed by hal::send and the offset
+/// between the detected start of the emission and the scheduled start is
+/// measured. As the concentrator TX status is polled, the offset is only as
+/// accurate as the polling interval (and latency).
+#[derive(Default)]
+pub struct Calibration {
+    samples: u64,
+    max_send_duration: Duration,
+    min_tx_start_offset: i64,
+    max_tx_start_offset: i64,
+}
+
+impl Calibration {
+    /// Add a measurement. The tx_start_offset is in microseconds, a positive
+    /// value means that the emission started after the scheduled time.
+    pub fn add(&mut self, downlink_id: &str, send_duration: Duration, tx_start_offset: i64) {
+        if self.samples == 0 {
+            self.min_tx_start_offset = tx_start_offset;
+            self.max_tx_start_offset = tx_start_offset;
+        }
+
+        self.samples += 1;
+        self.max_send_duration = self.max_send_duration.max(send_duration);
+        self.min_tx_start_offset = self.min_tx_start_offset.min(tx_start_offset);
+        self.max_tx_start_offset = self.max_tx_start_offset.max(tx_start_offset);
+
+        info!(
+            "JIT calibration, downlink_id: {}, send_duration: {:?}, tx_start_offset_us: {}, max_send_duration: {:?}, min_tx_start_offset_us: {}, max_tx_start_offset_us: {}, samples: {}",
+            downlink_id,
+            send_duration,
+            tx_start_offset,
+            self.max_send_duration,
+            self.min_tx_start_offset,
+            self.max_tx_start_offset,
+            self.samples
+        );
 
-use log::{debug, error, info};
+        metrics::set_jit_calibration(send_duration, tx_start_offset);
+    }
+}
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum TxMode {
@@ -9,28 +102,85 @@ pub enum TxMode {
     OnGPS,
 }
 
+/// Transmission priority. A packet evicts the colliding packets with a lower
+/// priority from the queue.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Priority {
+    /// Class-C and multicast downlinks (immediately).
+    ClassC,
+    /// Class-B ping-slot downlinks (GPS epoch).
+    ClassB,
+    /// Class-A downlinks (RX1 / RX2 delay).
+    ClassA,
+    /// Class-B beacons.
+    Beacon,
+}
+
+impl Priority {
+    /// Returns the priority of the given downlink, based on its timing.
+    pub fn from_downlink(item: &chirpstack_api::gw::DownlinkFrameItem) -> Priority {
+        match item.tx_info.as_ref().map(|v| v.timing()) {
+            Some(chirpstack_api::gw::DownlinkTiming::Delay) => Priority::ClassA,
+            Some(chirpstack_api::gw::DownlinkTiming::GpsEpoch) => Priority::ClassB,
+            _ => Priority::ClassC,
+        }
+    }
+}
+
 pub trait TxPacket {
     fn get_time_on_air(&self) -> Result<Duration, String>;
     fn get_tx_mode(&self) -> TxMode;
     fn get_id(&self) -> String;
+    fn get_priority(&self) -> Priority;
     fn set_tx_mode(&mut self, tx_mode: TxMode);
     fn get_count_us(&self) -> u32;
     fn set_count_us(&mut self, count_us: u32);
+    fn get_frequency(&self) -> u32;
 }
 
 pub struct Item<T> {
     pre_delay: Duration,
     post_delay: Duration,
+    // Estimated (wall-clock) time of the transmission.
+    tx_time: Instant,
     packet: T,
 }
 
+impl<T: TxPacket> Item<T> {
+    fn to_proto(&self, concentrator_count: u32) -> proto::JitQueueItem {
+        let tx_in = self.packet.get_count_us().wrapping_sub(concentrator_count) as i32;
+
+        proto::JitQueueItem {
+            downlink_id: Uuid::parse_str(&self.packet.get_id())
+                .map(|v| v.as_bytes().to_vec())
+                .unwrap_or_default(),
+            count_us: self.packet.get_count_us(),
+            tx_in: Some(prost_types::Duration {
+                seconds: (tx_in / 1_000_000) as i64,
+                nanos: (tx_in % 1_000_000) * 1000,
+            }),
+            frequency: self.packet.get_frequency(),
+            time_on_air: Some(prost_types::Duration {
+                seconds: self.post_delay.as_secs() as i64,
+                nanos: self.post_delay.subsec_nanos() as i32,
+            }),
+        }
+    }
+}
+
 pub struct Queue<T> {
     items: Vec<Item<T>>,
+    evicted: Vec<(T, proto::TxAckStatus)>,
 
     tx_start_delay: Duration,
     tx_margin_delay: Duration,
     tx_jit_delay: Duration,
     tx_max_advance_delay: Duration,
+
+    duty_cycle: Option<DutyCycle>,
+    // Downlink ID, transmission time and time on air of the last popped
+    // packet, used to release its duty-cycle reservation.
+    popped: Option<(String, Instant, Duration)>,
 }
 
 impl<T: TxPacket + Copy> Queue<T> {
@@ -39,11 +189,58 @@ impl<T: TxPacket + Copy> Queue<T> {
 
         Queue {
             items: Vec::with_capacity(capacity),
+            evicted: Vec::new(),
 
             tx_start_delay: Duration::from_micros(1500),
             tx_margin_delay: Duration::from_micros(1000),
             tx_jit_delay: Duration::from_micros(30000),
             tx_max_advance_delay: Duration::from_secs((3 + 1) * 128),
+
+            duty_cycle: None,
+            popped: None,
+        }
+    }
+
+    /// Create a new queue using the given configuration. Values which are
+    /// not set fall back to the default value.
+    pub fn from_config(conf: &Configuration) -> Queue<T> {
+        let mut queue = Queue::new(conf.capacity.unwrap_or(DEFAULT_CAPACITY));
+
+        if let Some(v) = conf.tx_start_delay {
+            queue.tx_start_delay = v;
+        }
+        if let Some(v) = conf.tx_margin_delay {
+            queue.tx_margin_delay = v;
+        }
+        if let Some(v) = conf.tx_jit_delay {
+            queue.tx_jit_delay = v;
+        }
+        if let Some(v) = conf.tx_max_advance_delay {
+            queue.tx_max_advance_delay = v;
+        }
+
+        info!(
+            "JIT queue timing configured, tx_start_delay: {:?}, tx_margin_delay: {:?}, tx_jit_delay: {:?}, tx_max_advance_delay: {:?}",
+            queue.tx_start_delay,
+            queue.tx_margin_delay,
+            queue.tx_jit_delay,
+            queue.tx_max_advance_delay
+        );
+
+        queue
+    }
+
+    /// Enable duty-cycle enforcement.
+    pub fn set_duty_cycle(&mut self, duty_cycle: DutyCycle) {
+        self.duty_cycle = Some(duty_cycle);
+    }
+
+    /// Returns the duty-cycle usage per sub-band, this is empty when
+    /// duty-cycle enforcement is disabled.
+    pub fn get_duty_cycle_usage(&mut self) -> Vec<(String, f32)> {
+        match self.duty_cycle.as_mut() {
+            Some(v) => v.get_usage(Instant::now()),
+            None => Vec::new(),
         }
     }
 
@@ -59,6 +256,65 @@ impl<T: TxPacket + Copy> Queue<T> {
         self.items.len() == self.size()
     }
 
+    /// Returns true when a queued packet is (being) transmitted within the
+    /// given duration, starting at the given concentrator count.
+    pub fn has_pending_tx(&self, concentrator_count: u32, duration: Duration) -> bool {
+        self.collision_test(concentrator_count, Duration::from_secs(0), duration)
+    }
+
+    /// Returns the queued items, in order of transmission.
+    pub fn get_items(&self, concentrator_count: u32) -> Vec<proto::JitQueueItem> {
+        self.items
+            .iter()
+            .map(|i| i.to_proto(concentrator_count))
+            .collect()
+    }
+
+    /// Remove the packets with the given downlink ID from the queue. This
+    /// returns the removed items.
+    pub fn cancel(&mut self, concentrator_count: u32, id: &str) -> Vec<proto::JitQueueItem> {
+        let removed = self.remove(|i| i.packet.get_id() == id);
+
+        info!(
+            "Packets cancelled, downlink_id: {}, count: {}",
+            id,
+            removed.len()
+        );
+
+        removed
+            .iter()
+            .map(|i| i.to_proto(concentrator_count))
+            .collect()
+    }
+
+    /// Remove the packets of which the transmission time has passed (e.g.
+    /// because the JIT loop has been stalled), these can no longer be
+    /// transmitted. This returns the removed packets. This must be called
+    /// before pop, as an expired packet would block the queue.
+    pub fn pop_expired(&mut self, concentrator_count: u32) -> Vec<T> {
+        let tx_start_delay = self.tx_start_delay.as_micros() as i32;
+        let removed = self.remove(|i| {
+            (i.packet.get_count_us().wrapping_sub(concentrator_count) as i32) < tx_start_delay
+        });
+
+        for item in &removed {
+            warn!(
+                "Packet expired, downlink_id: {}, counter_us: {}, current_counter_us: {}",
+                item.packet.get_id(),
+                item.packet.get_count_us(),
+                concentrator_count
+            );
+        }
+
+        removed.into_iter().map(|i| i.packet).collect()
+    }
+
+    /// Returns the packets which have been evicted by a higher priority
+    /// packet since the previous call, with the status to report.
+    pub fn pop_evicted(&mut self) -> Vec<(T, proto::TxAckStatus)> {
+        self.evicted.drain(..).collect()
+    }
+
     pub fn pop(&mut self, concentrator_count: u32) -> Option<T> {
         match self.items.first() {
             None => {
@@ -76,59 +332,81 @@ impl<T: TxPacket + Copy> Queue<T> {
         };
 
         let item = self.items.remove(0);
+        metrics::set_jit_queue_size(self.items.len());
+
+        self.popped = Some((item.packet.get_id(), item.tx_time, item.post_delay));
 
         return Some(item.packet);
     }
 
+    /// Release the duty-cycle reservation of the given packet, returned by
+    /// the last pop call. This must be called when the packet could not be
+    /// sent, as it would otherwise count against the duty-cycle.
+    pub fn release(&mut self, packet: &T) {
+        if let Some((id, tx_time, time_on_air)) = self.popped.take() {
+            if id != packet.get_id() {
+                return;
+            }
+
+            if let Some(duty_cycle) = self.duty_cycle.as_mut() {
+                duty_cycle.release(tx_time, packet.get_frequency(), time_on_air);
+            }
+        }
+    }
+
     pub fn enqueue(
         &mut self,
         concentrator_count: u32,
         packet: T,
-    ) -> Result<(), chirpstack_api::gw::TxAckStatus> {
+    ) -> Result<(), proto::TxAckStatus> {
         match packet.get_tx_mode() {
             TxMode::Timestamped => {
                 info!(
-                    "Enqueueing timestamped packet, downlink_id: {}, counter_us: {}, current_counter_us: {}",
+                    "Enqueueing timestamped packet, downlink_id: {}, counter_us: {}, current_counter_us: {}, priority: {:?}",
                     packet.get_id(),
                     packet.get_count_us(),
                     concentrator_count,
+                    packet.get_priority(),
                 );
             }
             TxMode::Immediate => {
                 info!(
-                    "Enqueueing immediate packet, downlink_id: {}, current_counter_us: {}",
+                    "Enqueueing immediate packet, downlink_id: {}, current_counter_us: {}, priority: {:?}",
                     packet.get_id(),
                     concentrator_count,
+                    packet.get_priority(),
                 );
             }
             TxMode::OnGPS => {
                 info!(
-                    "Enqueueing packet on pps, downlink_id: {}, counter_us: {}, current_counter_us: {}",
+                    "Enqueueing packet on pps, downlink_id: {}, counter_us: {}, current_counter_us: {}, priority: {:?}",
                     packet.get_id(),
                     packet.get_count_us(),
                     concentrator_count,
+                    packet.get_priority(),
                 );
             }
         }
 
-        if self.full() {
-            return Err(chirpstack_api::gw::TxAckStatus::QueueFull);
-        }
-
         let time_on_air = match packet.get_time_on_air() {
             Ok(v) => v,
             Err(err) => {
                 error!("Get time on air for tx packet error, error: {}", err);
-                return Err(chirpstack_api::gw::TxAckStatus::InternalError);
+                return Err(proto::TxAckStatus::InternalError);
             }
         };
 
+        let now = Instant::now();
         let mut item = Item {
             pre_delay: self.tx_start_delay + self.tx_jit_delay,
             post_delay: time_on_air,
+            tx_time: now,
             packet: packet,
         };
 
+        // Number of lower priority packets which will be evicted.
+        let mut evict_count = 0;
+
         // An immediate downlink becomes a timestamped downlink "ASAP".
         // Set the packet count_us to the first available slot.
         if item.packet.get_tx_mode() == TxMode::Immediate {
@@ -154,13 +432,26 @@ impl<T: TxPacket + Copy> Queue<T> {
             }
 
             item.packet.set_count_us(asap_count_us);
-        } else if item.packet.get_tx_mode() == TxMode::Timestamped {
-            if self.collision_test(item.packet.get_count_us(), item.pre_delay, item.post_delay) {
-                return Err(chirpstack_api::gw::TxAckStatus::CollisionPacket);
-            }
-        } else if item.packet.get_tx_mode() == TxMode::OnGPS {
-            if self.collision_test(item.packet.get_count_us(), item.pre_delay, item.post_delay) {
-                return Err(chirpstack_api::gw::TxAckStatus::CollisionPacket);
+        } else {
+            // Timestamped and OnGPS packets can only evict packets with a
+            // lower priority.
+            for p in self.items.iter().filter(|p| {
+                collides(
+                    p,
+                    item.packet.get_count_us(),
+                    item.pre_delay,
+                    item.post_delay,
+                    self.tx_margin_delay,
+                )
+            }) {
+                if p.packet.get_priority() >= item.packet.get_priority() {
+                    return Err(match p.packet.get_priority() {
+                        Priority::Beacon => proto::TxAckStatus::CollisionBeacon,
+                        _ => proto::TxAckStatus::CollisionPacket,
+                    });
+                }
+
+                evict_count += 1;
             }
         }
 
@@ -168,14 +459,63 @@ impl<T: TxPacket + Copy> Queue<T> {
         if item.packet.get_count_us().wrapping_sub(concentrator_count)
             < (self.tx_start_delay + self.tx_margin_delay + self.tx_jit_delay).as_micros() as u32
         {
-            return Err(chirpstack_api::gw::TxAckStatus::TooLate);
+            return Err(proto::TxAckStatus::TooLate);
         }
 
         // Is it too early to send this packet?
         if item.packet.get_count_us().wrapping_sub(concentrator_count)
             > self.tx_max_advance_delay.as_micros() as u32
         {
-            return Err(chirpstack_api::gw::TxAckStatus::TooEarly);
+            return Err(proto::TxAckStatus::TooEarly);
+        }
+
+        if self.items.len() - evict_count >= self.size() {
+            return Err(proto::TxAckStatus::QueueFull);
+        }
+
+        // Evict the colliding lower priority packets. This must be done
+        // before the duty-cycle check, as this releases the duty-cycle
+        // reservations of these packets.
+        let evicted = if evict_count > 0 {
+            let count_us = item.packet.get_count_us();
+            let pre_delay = item.pre_delay;
+            let post_delay = item.post_delay;
+            let margin = self.tx_margin_delay;
+            self.remove(|p| collides(p, count_us, pre_delay, post_delay, margin))
+        } else {
+            Vec::new()
+        };
+
+        item.tx_time = now
+            + Duration::from_micros(
+                item.packet.get_count_us().wrapping_sub(concentrator_count) as u64
+            );
+
+        // Does this packet exceed the duty-cycle?
+        if let Some(duty_cycle) = self.duty_cycle.as_mut() {
+            if let Err(err) = duty_cycle.reserve(
+                now,
+                item.tx_time,
+                item.packet.get_frequency(),
+                item.post_delay,
+            ) {
+                warn!(
+                    "Rejecting packet, downlink_id: {}, error: {}",
+                    item.packet.get_id(),
+                    err
+                );
+
+                // restore the evicted packets
+                for p in evicted {
+                    let _ =
+                        duty_cycle.reserve(now, p.tx_time, p.packet.get_frequency(), p.post_delay);
+                    self.items.push(p);
+                }
+                self.sort(concentrator_count);
+                metrics::set_jit_queue_size(self.items.len());
+
+                return Err(proto::TxAckStatus::DutyCycleOverflow);
+            }
         }
 
         debug!(
@@ -184,12 +524,60 @@ impl<T: TxPacket + Copy> Queue<T> {
             item.packet.get_count_us()
         );
 
+        for p in evicted {
+            warn!(
+                "Packet evicted by higher priority packet, downlink_id: {}, priority: {:?}, evicted_by: {}, evicted_by_priority: {:?}",
+                p.packet.get_id(),
+                p.packet.get_priority(),
+                item.packet.get_id(),
+                item.packet.get_priority()
+            );
+
+            self.evicted.push((
+                p.packet,
+                match item.packet.get_priority() {
+                    Priority::Beacon => proto::TxAckStatus::CollisionBeacon,
+                    _ => proto::TxAckStatus::CollisionPacket,
+                },
+            ));
+        }
+
         self.items.push(item);
         self.sort(concentrator_count);
+        metrics::set_jit_queue_size(self.items.len());
 
         return Ok(());
     }
 
+    // Remove the items matching the given function. The duty-cycle
+    // reservations of the removed items are released.
+    fn remove<F>(&mut self, f: F) -> Vec<Item<T>>
+    where
+        F: Fn(&Item<T>) -> bool,
+    {
+        let mut removed: Vec<Item<T>> = Vec::new();
+        let mut i = 0;
+        while i < self.items.len() {
+            if f(&self.items[i]) {
+                removed.push(self.items.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+
+        if let Some(duty_cycle) = self.duty_cycle.as_mut() {
+            for item in &removed {
+                duty_cycle.release(item.tx_time, item.packet.get_frequency(), item.post_delay);
+            }
+        }
+
+        if !removed.is_empty() {
+            metrics::set_jit_queue_size(self.items.len());
+        }
+
+        removed
+    }
+
     fn sort(&mut self, count_us: u32) {
         self.items.sort_by(|a, b| {
             let a_diff = a.packet.get_count_us().wrapping_sub(count_us);
@@ -200,26 +588,33 @@ impl<T: TxPacket + Copy> Queue<T> {
     }
 
     fn collision_test(&self, count_us: u32, pre_delay: Duration, post_delay: Duration) -> bool {
-        let pre_delay = pre_delay.as_micros() as u32;
-        let post_delay = post_delay.as_micros() as u32;
-
-        for p2 in self.items.iter() {
-            let p2_pre_delay = p2.pre_delay.as_micros() as u32;
-            let p2_post_delay = p2.post_delay.as_micros() as u32;
-
-            if ((count_us.wrapping_sub(p2.packet.get_count_us()))
-                <= (pre_delay + p2_post_delay + self.tx_margin_delay.as_micros() as u32))
-                || ((p2.packet.get_count_us().wrapping_sub(count_us))
-                    <= (p2_pre_delay + post_delay + self.tx_margin_delay.as_micros() as u32))
-            {
-                return true;
-            }
-        }
-
-        return false;
+        self.items
+            .iter()
+            .any(|p2| collides(p2, count_us, pre_delay, post_delay, self.tx_margin_delay))
     }
 }
 
+// Returns true when the given item collides with a packet at count_us with
+// the given pre and post delay.
+fn collides<T: TxPacket>(
+    p2: &Item<T>,
+    count_us: u32,
+    pre_delay: Duration,
+    post_delay: Duration,
+    margin_delay: Duration,
+) -> bool {
+    let pre_delay = pre_delay.as_micros() as u32;
+    let post_delay = post_delay.as_micros() as u32;
+    let margin_delay = margin_delay.as_micros() as u32;
+    let p2_pre_delay = p2.pre_delay.as_micros() as u32;
+    let p2_post_delay = p2.post_delay.as_micros() as u32;
+
+    ((count_us.wrapping_sub(p2.packet.get_count_us()))
+        <= (pre_delay + p2_post_delay + margin_delay))
+        || ((p2.packet.get_count_us().wrapping_sub(count_us))
+            <= (p2_pre_delay + post_delay + margin_delay))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,6 +624,8 @@ mod tests {
         time_on_air: Duration,
         tx_mode: TxMode,
         count_us: u32,
+        frequency: u32,
+        priority: Priority,
     }
 
     impl TxPacket for TxPacketMock {
@@ -244,6 +641,10 @@ mod tests {
             return "".to_string();
         }
 
+        fn get_priority(&self) -> Priority {
+            return self.priority;
+        }
+
         fn set_tx_mode(&mut self, tx_mode: TxMode) {
             self.tx_mode = tx_mode;
         }
@@ -255,6 +656,10 @@ mod tests {
         fn set_count_us(&mut self, count_us: u32) {
             self.count_us = count_us;
         }
+
+        fn get_frequency(&self) -> u32 {
+            return self.frequency;
+        }
     }
 
     #[test]
@@ -263,6 +668,44 @@ mod tests {
         assert_eq!(10, q.size());
     }
 
+    #[test]
+    fn test_from_config() {
+        let model = Configuration {
+            tx_jit_delay: Some(Duration::from_millis(50)),
+            capacity: Some(16),
+            ..Default::default()
+        };
+        let gateway = Configuration {
+            tx_start_delay: Some(Duration::from_micros(2000)),
+            capacity: Some(8),
+            calibrate: true,
+            ..Default::default()
+        };
+
+        let conf = model.merge(&gateway);
+        assert_eq!(
+            Configuration {
+                tx_start_delay: Some(Duration::from_micros(2000)),
+                tx_margin_delay: None,
+                tx_jit_delay: Some(Duration::from_millis(50)),
+                tx_max_advance_delay: None,
+                capacity: Some(8),
+                calibrate: true,
+            },
+            conf
+        );
+
+        let q: Queue<TxPacketMock> = Queue::from_config(&conf);
+        assert_eq!(8, q.size());
+        assert_eq!(Duration::from_micros(2000), q.tx_start_delay);
+        assert_eq!(Duration::from_micros(1000), q.tx_margin_delay);
+        assert_eq!(Duration::from_millis(50), q.tx_jit_delay);
+
+        let q: Queue<TxPacketMock> = Queue::from_config(&Default::default());
+        assert_eq!(DEFAULT_CAPACITY, q.size());
+        assert_eq!(Duration::from_micros(30000), q.tx_jit_delay);
+    }
+
     #[test]
     fn test_enqueue_full() {
         let mut q: Queue<TxPacketMock> = Queue::new(2);
@@ -273,6 +716,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -283,6 +728,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -294,6 +741,8 @@ mod tests {
                     time_on_air: Duration::from_millis(100),
                     tx_mode: TxMode::Immediate,
                     count_us: 0,
+                    frequency: 868100000,
+                    priority: Priority::ClassA,
                 },
             )
             .is_err(),
@@ -312,6 +761,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -322,6 +773,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -365,6 +818,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -375,6 +830,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -386,6 +843,45 @@ mod tests {
         assert_eq!(1000, item.packet.get_count_us());
     }
 
+    #[test]
+    fn test_has_pending_tx() {
+        let mut q: Queue<TxPacketMock> = Queue::new(2);
+        let concentrator_count = 100;
+
+        assert_eq!(
+            false,
+            q.has_pending_tx(concentrator_count, Duration::from_secs(2))
+        );
+
+        q.enqueue(
+            concentrator_count,
+            TxPacketMock {
+                time_on_air: Duration::from_millis(100),
+                tx_mode: TxMode::Timestamped,
+                count_us: concentrator_count + Duration::from_secs(5).as_micros() as u32,
+                frequency: 868100000,
+                priority: Priority::ClassA,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            false,
+            q.has_pending_tx(concentrator_count, Duration::from_secs(2))
+        );
+        assert_eq!(
+            true,
+            q.has_pending_tx(concentrator_count, Duration::from_secs(5))
+        );
+        assert_eq!(
+            true,
+            q.has_pending_tx(
+                concentrator_count + Duration::from_secs(5).as_micros() as u32,
+                Duration::from_secs(2)
+            )
+        );
+    }
+
     #[test]
     fn test_pop_empty() {
         let mut q: Queue<TxPacketMock> = Queue::new(2);
@@ -405,6 +901,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Timestamped,
                 count_us: Duration::from_secs(2).as_micros() as u32,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -424,6 +922,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Timestamped,
                 count_us: Duration::from_secs(2).as_micros() as u32,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -443,6 +943,8 @@ mod tests {
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Timestamped,
                 count_us: 1,
+                frequency: 868100000,
+                priority: Priority::ClassA,
             },
         )
         .unwrap();
@@ -450,4 +952,209 @@ mod tests {
         let item = q.pop(0_u32.wrapping_sub(100));
         assert_eq!(true, item.is_some());
     }
+
+    #[test]
+    fn test_pop_expired() {
+        let mut q: Queue<TxPacketMock> = Queue::new(2);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+
+        q.enqueue(
+            concentrator_count,
+            TxPacketMock {
+                time_on_air: Duration::from_millis(100),
+                tx_mode: TxMode::Timestamped,
+                count_us: Duration::from_secs(2).as_micros() as u32,
+                frequency: 868100000,
+                priority: Priority::ClassA,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(0, q.pop_expired(concentrator_count).len());
+
+        // the jit loop stalled, the packet can't be popped anymore
+        let concentrator_count = Duration::from_secs(3).as_micros() as u32;
+        assert_eq!(true, q.pop(concentrator_count).is_none());
+        assert_eq!(1, q.pop_expired(concentrator_count).len());
+        assert_eq!(true, q.empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut q: Queue<TxPacketMock> = Queue::new(2);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+
+        q.enqueue(
+            concentrator_count,
+            TxPacketMock {
+                time_on_air: Duration::from_millis(100),
+                tx_mode: TxMode::Timestamped,
+                count_us: Duration::from_secs(2).as_micros() as u32,
+                frequency: 868100000,
+                priority: Priority::ClassA,
+            },
+        )
+        .unwrap();
+
+        let items = q.get_items(concentrator_count);
+        assert_eq!(1, items.len());
+        assert_eq!(Duration::from_secs(2).as_micros() as u32, items[0].count_us);
+        assert_eq!(
+            Some(prost_types::Duration {
+                seconds: 1,
+                nanos: 0
+            }),
+            items[0].tx_in
+        );
+
+        assert_eq!(0, q.cancel(concentrator_count, "unknown").len());
+        assert_eq!(1, q.cancel(concentrator_count, "").len());
+        assert_eq!(true, q.empty());
+    }
+
+    #[test]
+    fn test_enqueue_priority() {
+        let mut q: Queue<TxPacketMock> = Queue::new(2);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+        let count_us = Duration::from_secs(2).as_micros() as u32;
+
+        q.enqueue(
+            concentrator_count,
+            TxPacketMock {
+                time_on_air: Duration::from_millis(100),
+                tx_mode: TxMode::Timestamped,
+                count_us: count_us,
+                frequency: 868100000,
+                priority: Priority::ClassA,
+            },
+        )
+        .unwrap();
+
+        // lower priority packet is rejected
+        assert_eq!(
+            Err(proto::TxAckStatus::CollisionPacket),
+            q.enqueue(
+                concentrator_count,
+                TxPacketMock {
+                    time_on_air: Duration::from_millis(100),
+                    tx_mode: TxMode::Timestamped,
+                    count_us: count_us + 50000,
+                    frequency: 868100000,
+                    priority: Priority::ClassB,
+                },
+            )
+        );
+
+        // beacon evicts the class-a packet
+        q.enqueue(
+            concentrator_count,
+            TxPacketMock {
+                time_on_air: Duration::from_millis(150),
+                tx_mode: TxMode::OnGPS,
+                count_us: count_us + 50000,
+                frequency: 869525000,
+                priority: Priority::Beacon,
+            },
+        )
+        .unwrap();
+
+        let evicted = q.pop_evicted();
+        assert_eq!(1, evicted.len());
+        assert_eq!(Priority::ClassA, evicted[0].0.priority);
+        assert_eq!(proto::TxAckStatus::CollisionBeacon, evicted[0].1);
+        assert_eq!(0, q.pop_evicted().len());
+
+        // the class-a packet can't evict the beacon
+        assert_eq!(
+            Err(proto::TxAckStatus::CollisionBeacon),
+            q.enqueue(
+                concentrator_count,
+                TxPacketMock {
+                    time_on_air: Duration::from_millis(100),
+                    tx_mode: TxMode::Timestamped,
+                    count_us: count_us,
+                    frequency: 868100000,
+                    priority: Priority::ClassA,
+                },
+            )
+        );
+        assert_eq!(1, q.get_items(concentrator_count).len());
+    }
+
+    #[test]
+    fn test_enqueue_duty_cycle() {
+        let mut q: Queue<TxPacketMock> = Queue::new(2);
+        q.set_duty_cycle(DutyCycle::new(
+            Duration::from_secs(3600),
+            vec![crate::dutycycle::Band {
+                name: "g1".to_string(),
+                frequency_min: 868000000,
+                frequency_max: 868600000,
+                duty_cycle: 0.0001,
+            }],
+        ));
+
+        q.enqueue(
+            100,
+            TxPacketMock {
+                time_on_air: Duration::from_millis(300),
+                tx_mode: TxMode::Immediate,
+                count_us: 0,
+                frequency: 868100000,
+                priority: Priority::ClassA,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(proto::TxAckStatus::DutyCycleOverflow),
+            q.enqueue(
+                100,
+                TxPacketMock {
+                    time_on_air: Duration::from_millis(100),
+                    tx_mode: TxMode::Immediate,
+                    count_us: 0,
+                    frequency: 868100000,
+                    priority: Priority::ClassA,
+                },
+            )
+        );
+
+        let usage = q.get_duty_cycle_usage();
+        assert_eq!(1, usage.len());
+        assert_eq!("g1", usage[0].0);
+    }
+
+    #[test]
+    fn test_release_duty_cycle() {
+        let mut q: Queue<TxPacketMock> = Queue::new(2);
+        q.set_duty_cycle(DutyCycle::new(
+            Duration::from_secs(3600),
+            vec![crate::dutycycle::Band {
+                name: "g1".to_string(),
+                frequency_min: 868000000,
+                frequency_max: 868600000,
+                duty_cycle: 0.0001,
+            }],
+        ));
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+        let packet = TxPacketMock {
+            time_on_air: Duration::from_millis(300),
+            tx_mode: TxMode::Timestamped,
+            count_us: Duration::from_secs(2).as_micros() as u32,
+            frequency: 868100000,
+            priority: Priority::ClassA,
+        };
+
+        q.enqueue(concentrator_count, packet).unwrap();
+        let packet = q.pop(Duration::from_secs(2).as_micros() as u32).unwrap();
+        assert_eq!(
+            Err(proto::TxAckStatus::DutyCycleOverflow),
+            q.enqueue(concentrator_count, packet)
+        );
+
+        // the packet could not be sent
+        q.release(&packet);
+        q.enqueue(concentrator_count, packet).unwrap();
+    }
 }
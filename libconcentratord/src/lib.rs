@@ -1,10 +1,23 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod beacon;
+pub mod boards;
+pub mod capture;
 pub mod commands;
+pub mod config;
+pub mod curve;
+pub mod dutycycle;
 pub mod events;
+pub mod filters;
 pub mod jitqueue;
+pub mod metrics;
+pub mod proto;
 pub mod reset;
 pub mod signals;
+pub mod simulator;
 mod socket;
+pub mod state;
 pub mod stats;
+pub mod testtx;
+pub mod vendor;
@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info};
+
+lazy_static! {
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Default::default());
+}
+
+// Unlike the stats, these metrics are never reset.
+#[derive(Default)]
+struct Metrics {
+    rx_packets_received: u64,
+    rx_packets_received_ok: u64,
+    rx_packets_dropped: BTreeMap<String, u64>,
+    tx_packets_received: u64,
+    tx_packets_emitted: u64,
+    tx_packets_per_status: BTreeMap<String, u64>,
+    jit_queue_size: Option<usize>,
+    jit_send_duration: Option<Duration>,
+    jit_tx_start_offset: Option<i64>,
+    concentrator_temperature: Option<f32>,
+    gps_locked: Option<bool>,
+    xtal_correct: Option<f64>,
+}
+
+/// Start the HTTP server serving the Prometheus metrics.
+pub fn start(bind: &str) -> Result<(), String> {
+    info!("Starting Prometheus metrics server, bind: {}", bind);
+
+    let listener = TcpListener::bind(bind).map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(v) => {
+                    if let Err(err) = handle_request(v) {
+                        error!("Handle metrics request error, error: {}", err);
+                    }
+                }
+                Err(err) => error!("Accept metrics connection error, error: {}", err),
+            }
+        }
+    });
+
+    return Ok(());
+}
+
+pub fn inc_rx_packets_received() {
+    METRICS.lock().unwrap().rx_packets_received += 1;
+}
+
+pub fn inc_rx_packets_received_ok() {
+    METRICS.lock().unwrap().rx_packets_received_ok += 1;
+}
+
+pub fn inc_rx_packets_dropped(reason: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics
+        .rx_packets_dropped
+        .entry(reason.to_string())
+        .or_insert(0) += 1;
+}
+
+pub fn inc_tx_packets_received() {
+    METRICS.lock().unwrap().tx_packets_received += 1;
+}
+
+pub fn inc_tx_packets_emitted() {
+    METRICS.lock().unwrap().tx_packets_emitted += 1;
+}
+
+pub fn inc_tx_status(status: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics
+        .tx_packets_per_status
+        .entry(status.to_string())
+        .or_insert(0) += 1;
+}
+
+pub fn set_jit_queue_size(size: usize) {
+    METRICS.lock().unwrap().jit_queue_size = Some(size);
+}
+
+/// Set the latest JIT calibration measurement, see jitqueue::Calibration.
+pub fn set_jit_calibration(send_duration: Duration, tx_start_offset: i64) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.jit_send_duration = Some(send_duration);
+    metrics.jit_tx_start_offset = Some(tx_start_offset);
+}
+
+pub fn set_concentrator_temperature(temp: f32) {
+    METRICS.lock().unwrap().concentrator_temperature = Some(temp);
+}
+
+pub fn set_gps_locked(locked: bool) {
+    METRICS.lock().unwrap().gps_locked = Some(locked);
+}
+
+pub fn set_xtal_correct(xtal_correct: f64) {
+    METRICS.lock().unwrap().xtal_correct = Some(xtal_correct);
+}
+
+fn handle_request(mut stream: TcpStream) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .map_err(|e| e.to_string())?;
+
+    // The request itself is not inspected, every request returns the metrics.
+    let mut buf = [0; 1024];
+    let _ = stream.read(&mut buf).map_err(|e| e.to_string())?;
+
+    let body = encode(&METRICS.lock().unwrap());
+    let resp = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+
+    stream
+        .write_all(resp.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    return Ok(());
+}
+
+fn encode(metrics: &Metrics) -> String {
+    let mut out = String::new();
+
+    write_metric(
+        &mut out,
+        "concentratord_rx_packets_received_total",
+        "counter",
+        "Number of radio packets received.",
+        &[("", metrics.rx_packets_received as f64)],
+    );
+    write_metric(
+        &mut out,
+        "concentratord_rx_packets_received_ok_total",
+        "counter",
+        "Number of radio packets received with valid PHY CRC.",
+        &[("", metrics.rx_packets_received_ok as f64)],
+    );
+
+    let per_reason: Vec<(String, f64)> = metrics
+        .rx_packets_dropped
+        .iter()
+        .map(|(k, v)| (format!("reason=\"{}\"", k), *v as f64))
+        .collect();
+    let per_reason: Vec<(&str, f64)> = per_reason.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    write_metric(
+        &mut out,
+        "concentratord_rx_packets_dropped_total",
+        "counter",
+        "Number of radio packets dropped by the uplink filters, per reason.",
+        &per_reason,
+    );
+
+    write_metric(
+        &mut out,
+        "concentratord_tx_packets_received_total",
+        "counter",
+        "Number of downlink packets received for transmission.",
+        &[("", metrics.tx_packets_received as f64)],
+    );
+    write_metric(
+        &mut out,
+        "concentratord_tx_packets_emitted_total",
+        "counter",
+        "Number of downlink packets emitted.",
+        &[("", metrics.tx_packets_emitted as f64)],
+    );
+
+    let per_status: Vec<(String, f64)> = metrics
+        .tx_packets_per_status
+        .iter()
+        .map(|(k, v)| (format!("status=\"{}\"", k), *v as f64))
+        .collect();
+    let per_status: Vec<(&str, f64)> = per_status.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    write_metric(
+        &mut out,
+        "concentratord_tx_packets_status_total",
+        "counter",
+        "Number of downlink packets per tx ack status.",
+        &per_status,
+    );
+
+    if let Some(v) = metrics.jit_queue_size {
+        write_metric(
+            &mut out,
+            "concentratord_jit_queue_size",
+            "gauge",
+            "Number of packets in the JIT queue.",
+            &[("", v as f64)],
+        );
+    }
+
+    if let Some(v) = metrics.jit_send_duration {
+        write_metric(
+            &mut out,
+            "concentratord_jit_send_duration_seconds",
+            "gauge",
+            "Duration of sending the latest packet to the concentrator (calibration).",
+            &[("", v.as_secs_f64())],
+        );
+    }
+
+    if let Some(v) = metrics.jit_tx_start_offset {
+        write_metric(
+            &mut out,
+            "concentratord_jit_tx_start_offset_seconds",
+            "gauge",
+            "Offset between the detected and scheduled start of the latest emission (calibration).",
+            &[("", v as f64 / 1_000_000.0)],
+        );
+    }
+
+    if let Some(v) = metrics.concentrator_temperature {
+        write_metric(
+            &mut out,
+            "concentratord_concentrator_temperature_celsius",
+            "gauge",
+            "Concentrator temperature.",
+            &[("", v as f64)],
+        );
+    }
+
+    if let Some(v) = metrics.gps_locked {
+        write_metric(
+            &mut out,
+            "concentratord_gps_locked",
+            "gauge",
+            "GPS time reference is valid (1) or not (0).",
+            &[("", if v { 1.0 } else { 0.0 })],
+        );
+    }
+
+    if let Some(v) = metrics.xtal_correct {
+        write_metric(
+            &mut out,
+            "concentratord_xtal_correct",
+            "gauge",
+            "Concentrator xtal correction.",
+            &[("", v)],
+        );
+    }
+
+    out
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, values: &[(&str, f64)]) {
+    if values.is_empty() {
+        return;
+    }
+
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+    for (labels, value) in values {
+        if labels.is_empty() {
+            writeln!(out, "{} {}", name, value).unwrap();
+        } else {
+            writeln!(out, "{}{{{}}} {}", name, labels, value).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let mut metrics: Metrics = Default::default();
+        metrics.rx_packets_received = 10;
+        metrics
+            .tx_packets_per_status
+            .insert("TOO_LATE".to_string(), 2);
+        metrics.gps_locked = Some(true);
+
+        let out = encode(&metrics);
+        assert!(out.contains(
+            "# TYPE concentratord_rx_packets_received_total counter\nconcentratord_rx_packets_received_total 10\n"
+        ));
+        assert!(out.contains("concentratord_tx_packets_status_total{status=\"TOO_LATE\"} 2\n"));
+        assert!(out.contains("concentratord_gps_locked 1\n"));
+        assert!(!out.contains("concentratord_jit_queue_size"));
+    }
+}
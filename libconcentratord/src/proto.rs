@@ -0,0 +1,270 @@
+//! Concentratord specific Protobuf messages.
+//!
+//! These messages are not (yet) part of the ChirpStack API and are only
+//! published / handled by the Concentratord.
+
+use std::collections::HashMap;
+
+/// Spectral scan result for a single frequency.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpectralScanResult {
+    /// Gateway ID.
+    #[prost(bytes, tag = "1")]
+    pub gateway_id: Vec<u8>,
+    /// Time of the scan.
+    #[prost(message, optional, tag = "2")]
+    pub time: Option<prost_types::Timestamp>,
+    /// Scanned (center) frequency (Hz).
+    #[prost(uint32, tag = "3")]
+    pub frequency: u32,
+    /// Number of RSSI measurements.
+    #[prost(uint32, tag = "4")]
+    pub nb_scan: u32,
+    /// Histogram of the measured RSSI levels.
+    #[prost(message, repeated, tag = "5")]
+    pub levels: Vec<SpectralScanLevel>,
+}
+
+/// Number of measurements for a single RSSI level.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpectralScanLevel {
+    /// RSSI level (dBm).
+    #[prost(sint32, tag = "1")]
+    pub rssi: i32,
+    /// Number of measurements at this level.
+    #[prost(uint32, tag = "2")]
+    pub count: u32,
+}
+
+/// JIT queue content.
+///
+/// This is the response of the queue command (the queued items) and of the
+/// cancel command (the cancelled items).
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JitQueue {
+    /// Gateway ID.
+    #[prost(bytes, tag = "1")]
+    pub gateway_id: Vec<u8>,
+    /// Items, in order of transmission.
+    #[prost(message, repeated, tag = "2")]
+    pub items: Vec<JitQueueItem>,
+}
+
+/// Downlink scheduled for transmission.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct JitQueueItem {
+    /// Downlink ID (UUID).
+    #[prost(bytes, tag = "1")]
+    pub downlink_id: Vec<u8>,
+    /// Concentrator counter (us) at which the transmission starts.
+    #[prost(uint32, tag = "2")]
+    pub count_us: u32,
+    /// Time until the transmission starts.
+    #[prost(message, optional, tag = "3")]
+    pub tx_in: Option<prost_types::Duration>,
+    /// Frequency (Hz).
+    #[prost(uint32, tag = "4")]
+    pub frequency: u32,
+    /// Time on air.
+    #[prost(message, optional, tag = "5")]
+    pub time_on_air: Option<prost_types::Duration>,
+}
+
+/// Test transmission (test_tx command).
+///
+/// This emits a continuous wave or repeated LoRa test packets, bypassing the
+/// JIT queue, until the duration has passed or the test_tx_stop command is
+/// received. The JIT queue is paused during the test transmission.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TestTx {
+    /// Frequency (Hz).
+    #[prost(uint32, tag = "1")]
+    pub frequency: u32,
+    /// TX power (dBm). The antenna gain and TX limits are not applied.
+    #[prost(sint32, tag = "2")]
+    pub power: i32,
+    /// Duration of the test transmission.
+    #[prost(message, optional, tag = "3")]
+    pub duration: Option<prost_types::Duration>,
+    /// Emit a continuous wave instead of LoRa test packets.
+    #[prost(bool, tag = "4")]
+    pub cw: bool,
+    /// Bandwidth (Hz) of the LoRa test packets.
+    #[prost(uint32, tag = "5")]
+    pub bandwidth: u32,
+    /// Spreading factor of the LoRa test packets.
+    #[prost(uint32, tag = "6")]
+    pub spreading_factor: u32,
+    /// Payload size (bytes) of the LoRa test packets.
+    #[prost(uint32, tag = "7")]
+    pub payload_size: u32,
+    /// Time between the end of a LoRa test packet and the start of the next.
+    #[prost(message, optional, tag = "8")]
+    pub interval: Option<prost_types::Duration>,
+    /// Board (in case of multiple concentrator boards).
+    #[prost(uint32, tag = "9")]
+    pub board: u32,
+}
+
+/// Response of the test_tx and test_tx_stop commands.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TestTxResponse {
+    /// Gateway ID.
+    #[prost(bytes, tag = "1")]
+    pub gateway_id: Vec<u8>,
+    /// Error, this is empty when the command was successful.
+    #[prost(string, tag = "2")]
+    pub error: String,
+}
+
+/// Gateway statistics.
+///
+/// This message is wire compatible with the GatewayStats message of the
+/// ChirpStack API (v3.11+), which adds the per frequency, modulation and
+/// status counters.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayStats {
+    /// Gateway ID.
+    #[prost(bytes, tag = "1")]
+    pub gateway_id: Vec<u8>,
+    /// Gateway IP.
+    #[prost(string, tag = "9")]
+    pub ip: String,
+    /// Gateway time.
+    #[prost(message, optional, tag = "2")]
+    pub time: Option<prost_types::Timestamp>,
+    /// Gateway location.
+    #[prost(message, optional, tag = "3")]
+    pub location: Option<chirpstack_api::common::Location>,
+    /// Gateway configuration version.
+    #[prost(string, tag = "4")]
+    pub config_version: String,
+    /// Number of radio packets received.
+    #[prost(uint32, tag = "5")]
+    pub rx_packets_received: u32,
+    /// Number of radio packets received with valid PHY CRC.
+    #[prost(uint32, tag = "6")]
+    pub rx_packets_received_ok: u32,
+    /// Number of downlink packets received for transmission.
+    #[prost(uint32, tag = "7")]
+    pub tx_packets_received: u32,
+    /// Number of downlink packets emitted.
+    #[prost(uint32, tag = "8")]
+    pub tx_packets_emitted: u32,
+    /// Additional gateway meta-data.
+    #[prost(map = "string, string", tag = "10")]
+    pub meta_data: HashMap<String, String>,
+    /// Stats ID (UUID).
+    #[prost(bytes, tag = "11")]
+    pub stats_id: Vec<u8>,
+    /// Tx packets per frequency.
+    #[prost(map = "uint32, uint32", tag = "12")]
+    pub tx_packets_per_frequency: HashMap<u32, u32>,
+    /// Rx packets per frequency.
+    #[prost(map = "uint32, uint32", tag = "13")]
+    pub rx_packets_per_frequency: HashMap<u32, u32>,
+    /// Tx packets per modulation parameters.
+    #[prost(message, repeated, tag = "14")]
+    pub tx_packets_per_modulation: Vec<PerModulationCount>,
+    /// Rx packets per modulation parameters.
+    #[prost(message, repeated, tag = "15")]
+    pub rx_packets_per_modulation: Vec<PerModulationCount>,
+    /// Tx packets per status.
+    #[prost(map = "string, uint32", tag = "16")]
+    pub tx_packets_per_status: HashMap<String, u32>,
+    /// Rx packets per CRC status (Concentratord specific).
+    #[prost(map = "string, uint32", tag = "100")]
+    pub rx_packets_per_crc_status: HashMap<String, u32>,
+}
+
+/// Number of packets for the given modulation parameters.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PerModulationCount {
+    /// Modulation.
+    #[prost(message, optional, tag = "1")]
+    pub modulation: Option<Modulation>,
+    /// Count.
+    #[prost(uint32, tag = "2")]
+    pub count: u32,
+}
+
+/// Modulation parameters.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Modulation {
+    #[prost(oneof = "modulation::Parameters", tags = "3, 4")]
+    pub parameters: Option<modulation::Parameters>,
+}
+
+pub mod modulation {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Parameters {
+        /// LoRa modulation information.
+        #[prost(message, tag = "3")]
+        Lora(chirpstack_api::gw::LoRaModulationInfo),
+        /// FSK modulation information.
+        #[prost(message, tag = "4")]
+        Fsk(chirpstack_api::gw::FskModulationInfo),
+    }
+}
+
+/// TX acknowledgement status.
+///
+/// This enum is wire compatible with the TxAckStatus enum of the ChirpStack
+/// API. DUTY_CYCLE_OVERFLOW is part of later ChirpStack API versions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TxAckStatus {
+    /// Ignored (when a previous item was already emitted).
+    Ignored = 0,
+    /// Packet has been programmed for downlink.
+    Ok = 1,
+    /// Rejected because it was already too late to program this packet for
+    /// downlink.
+    TooLate = 2,
+    /// Rejected because downlink packet timestamp is too much in advance.
+    TooEarly = 3,
+    /// Rejected because there was already a packet programmed in requested
+    /// timeframe.
+    CollisionPacket = 4,
+    /// Rejected because there was already a beacon planned in requested
+    /// timeframe.
+    CollisionBeacon = 5,
+    /// Rejected because requested frequency is not supported by TX RF chain.
+    TxFreq = 6,
+    /// Rejected because requested power is not supported by gateway.
+    TxPower = 7,
+    /// Rejected because GPS is unlocked, so GPS timestamp cannot be used.
+    GpsUnlocked = 8,
+    /// Downlink queue is full.
+    QueueFull = 9,
+    /// Internal error.
+    InternalError = 10,
+    /// Rejected because the packet would exceed the duty-cycle.
+    DutyCycleOverflow = 11,
+}
+
+impl TxAckStatus {
+    /// Returns the status name, as used by the ChirpStack API and the stats.
+    pub fn name(self) -> &'static str {
+        match self {
+            TxAckStatus::Ignored => "IGNORED",
+            TxAckStatus::Ok => "OK",
+            TxAckStatus::TooLate => "TOO_LATE",
+            TxAckStatus::TooEarly => "TOO_EARLY",
+            TxAckStatus::CollisionPacket => "COLLISION_PACKET",
+            TxAckStatus::CollisionBeacon => "COLLISION_BEACON",
+            TxAckStatus::TxFreq => "TX_FREQ",
+            TxAckStatus::TxPower => "TX_POWER",
+            TxAckStatus::GpsUnlocked => "GPS_UNLOCKED",
+            TxAckStatus::QueueFull => "QUEUE_FULL",
+            TxAckStatus::InternalError => "INTERNAL_ERROR",
+            TxAckStatus::DutyCycleOverflow => "DUTY_CYCLE_OVERFLOW",
+        }
+    }
+
+    /// Returns the status of the given TX acknowledgement item. Unknown values
+    /// are returned as InternalError.
+    pub fn from_item(item: &chirpstack_api::gw::DownlinkTxAckItem) -> TxAckStatus {
+        TxAckStatus::from_i32(item.status).unwrap_or(TxAckStatus::InternalError)
+    }
+}
@@ -11,7 +11,11 @@ lazy_static! {
     static ref POWER_EN: Mutex<Option<LineHandle>> = Mutex::new(None);
 }
 
+/// Configure the reset and (optional) power enable pins. The previously
+/// configured pins are released, e.g. on a configuration reload.
 pub fn setup_pins(reset: u32, power_en: Option<u32>) -> Result<(), Error> {
+    clear_pins();
+
     let mut chip = Chip::new("/dev/gpiochip0")?;
 
     info!("Configuring reset pin, pin: {}", reset);
@@ -30,6 +34,12 @@ pub fn setup_pins(reset: u32, power_en: Option<u32>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Release the configured reset and power enable pins.
+pub fn clear_pins() {
+    *RESET.lock().unwrap() = None;
+    *POWER_EN.lock().unwrap() = None;
+}
+
 pub fn reset() -> Result<(), Error> {
     let reset = RESET.lock().unwrap();
     if reset.is_some() {
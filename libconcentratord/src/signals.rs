@@ -4,6 +4,7 @@ use std::sync::mpsc::{channel, Receiver, Sender};
 #[derive(Clone)]
 pub enum Signal {
     Stop,
+    Reload,
     Configuration(chirpstack_api::gw::GatewayConfiguration),
 }
 
@@ -11,6 +12,7 @@ impl fmt::Display for Signal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Signal::Stop => write!(f, "Stop"),
+            Signal::Reload => write!(f, "Reload"),
             Signal::Configuration(_) => write!(f, "Configuration"),
         }
     }
@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::info;
+
+lazy_static! {
+    static ref SIMULATOR: Mutex<Option<Simulator>> = Mutex::new(None);
+}
+
+// GPS epoch (1980-01-06T00:00:00Z) as UNIX timestamp.
+const GPS_EPOCH_UNIX_SECS: u64 = 315964800;
+
+// Difference between GPS time and UTC.
+const GPS_LEAP_SECONDS: u64 = 18;
+
+struct Simulator {
+    start: Instant,
+    uplinks: VecDeque<(Duration, Uplink)>,
+    // Virtual concentrator counter of the latest TX.
+    tx_count_us: Option<u32>,
+}
+
+/// Simulated uplink.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uplink {
+    /// Virtual concentrator counter at which the uplink was received.
+    pub count_us: u32,
+    pub frequency: u32,
+    pub bandwidth: u32,
+    pub spreading_factor: u32,
+    pub rssi: i16,
+    pub snr: f32,
+    pub phy_payload: Vec<u8>,
+}
+
+/// Start the simulator. This resets the virtual concentrator counter.
+///
+/// The (optional) uplink script contains one uplink per line, in the
+/// following format:
+///
+/// `<time_ms> <frequency> <bandwidth> <spreading_factor> <rssi> <snr> <phy_payload_hex>`
+///
+/// where time_ms is the time (in milliseconds) after start at which the
+/// uplink is received. Empty lines and lines starting with # are ignored.
+pub fn start(uplink_script: &str) -> Result<(), String> {
+    let uplinks = match uplink_script {
+        "" => VecDeque::new(),
+        _ => {
+            let content = fs::read_to_string(uplink_script)
+                .map_err(|e| format!("read uplink script error: {}", e))?;
+            parse_script(&content)?
+        }
+    };
+
+    info!(
+        "Starting concentrator simulator, uplink_script: {}, uplinks: {}",
+        uplink_script,
+        uplinks.len()
+    );
+
+    let mut simulator = SIMULATOR.lock().unwrap();
+    *simulator = Some(Simulator {
+        start: Instant::now(),
+        uplinks: uplinks,
+        tx_count_us: None,
+    });
+
+    return Ok(());
+}
+
+/// Returns true when the simulator has been started.
+pub fn enabled() -> bool {
+    SIMULATOR.lock().unwrap().is_some()
+}
+
+/// Returns the virtual concentrator counter.
+pub fn get_count_us() -> Result<u32, String> {
+    let simulator = SIMULATOR.lock().unwrap();
+    let simulator = simulator.as_ref().ok_or("simulator is not started")?;
+
+    return Ok(simulator.start.elapsed().as_micros() as u32);
+}
+
+/// Returns the uplinks which are due.
+pub fn receive() -> Result<Vec<Uplink>, String> {
+    let mut simulator = SIMULATOR.lock().unwrap();
+    let simulator = simulator.as_mut().ok_or("simulator is not started")?;
+    let elapsed = simulator.start.elapsed();
+
+    let mut out: Vec<Uplink> = Vec::new();
+    while let Some((time, _)) = simulator.uplinks.front() {
+        if *time > elapsed {
+            break;
+        }
+
+        let (time, mut uplink) = simulator.uplinks.pop_front().unwrap();
+        uplink.count_us = time.as_micros() as u32;
+        out.push(uplink);
+    }
+
+    return Ok(out);
+}
+
+/// Simulate the transmission of a downlink.
+pub fn send(count_us: u32, frequency: u32, size: u16) -> Result<(), String> {
+    let mut simulator = SIMULATOR.lock().unwrap();
+    let simulator = simulator.as_mut().ok_or("simulator is not started")?;
+    let current_count_us = simulator.start.elapsed().as_micros() as u32;
+    simulator.tx_count_us = Some(count_us);
+
+    info!(
+        "Simulated TX, count_us: {}, current_count_us: {}, freq: {}, size: {}",
+        count_us, current_count_us, frequency, size
+    );
+
+    return Ok(());
+}
+
+/// Returns true when the virtual concentrator counter has reached the
+/// counter of the latest TX. As the simulator does not know the duration of
+/// the TX, it is emitting until the next TX.
+pub fn is_emitting() -> Result<bool, String> {
+    let simulator = SIMULATOR.lock().unwrap();
+    let simulator = simulator.as_ref().ok_or("simulator is not started")?;
+    let current_count_us = simulator.start.elapsed().as_micros() as u32;
+
+    return Ok(match simulator.tx_count_us {
+        Some(v) => (current_count_us.wrapping_sub(v) as i32) >= 0,
+        None => false,
+    });
+}
+
+/// Returns the virtual concentrator counter, the current system time and
+/// the corresponding time since GPS epoch. This can be used as fake GPS time
+/// reference.
+pub fn get_gps_time() -> Result<(u32, SystemTime, Duration), String> {
+    let count_us = get_count_us()?;
+    let system_time = SystemTime::now();
+    let gps_epoch = system_time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        - Duration::from_secs(GPS_EPOCH_UNIX_SECS)
+        + Duration::from_secs(GPS_LEAP_SECONDS);
+
+    return Ok((count_us, system_time, gps_epoch));
+}
+
+fn parse_script(content: &str) -> Result<VecDeque<(Duration, Uplink)>, String> {
+    let mut uplinks: Vec<(Duration, Uplink)> = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 7 {
+            return Err(format!(
+                "uplink script line {}: expected 7 fields, got {}",
+                i + 1,
+                fields.len()
+            ));
+        }
+
+        let err = |field: &str| format!("uplink script line {}: invalid {}", i + 1, field);
+
+        let time = Duration::from_millis(fields[0].parse().map_err(|_| err("time_ms"))?);
+        uplinks.push((
+            time,
+            Uplink {
+                count_us: 0,
+                frequency: fields[1].parse().map_err(|_| err("frequency"))?,
+                bandwidth: fields[2].parse().map_err(|_| err("bandwidth"))?,
+                spreading_factor: fields[3].parse().map_err(|_| err("spreading_factor"))?,
+                rssi: fields[4].parse().map_err(|_| err("rssi"))?,
+                snr: fields[5].parse().map_err(|_| err("snr"))?,
+                phy_payload: decode_hex(fields[6]).map_err(|_| err("phy_payload"))?,
+            },
+        ));
+    }
+
+    uplinks.sort_by(|a, b| a.0.cmp(&b.0));
+
+    return Ok(uplinks.into_iter().collect());
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 {
+        return Err("odd number of hex characters".to_string());
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let content = r#"
+# time_ms frequency bandwidth spreading_factor rssi snr phy_payload
+2000 868300000 125000 9 -80 5.5 40aabbccdd
+1000 868100000 125000 7 -60 7.0 0102
+"#;
+
+        let uplinks = parse_script(content).unwrap();
+        assert_eq!(2, uplinks.len());
+
+        assert_eq!(Duration::from_secs(1), uplinks[0].0);
+        assert_eq!(
+            Uplink {
+                count_us: 0,
+                frequency: 868100000,
+                bandwidth: 125000,
+                spreading_factor: 7,
+                rssi: -60,
+                snr: 7.0,
+                phy_payload: vec![0x01, 0x02],
+            },
+            uplinks[0].1
+        );
+
+        assert_eq!(Duration::from_secs(2), uplinks[1].0);
+        assert_eq!(vec![0x40, 0xaa, 0xbb, 0xcc, 0xdd], uplinks[1].1.phy_payload);
+    }
+
+    #[test]
+    fn test_parse_script_invalid() {
+        assert!(parse_script("1000 868100000 125000 7 -60 7.0").is_err());
+        assert!(parse_script("1000 868100000 125000 7 -60 7.0 0").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::{ErrorKind, Write};
+
+use log::info;
+use prost::Message;
+
+/// Save the gateway configuration to the state file.
+///
+/// The configuration is first written to a temporary file, which is then
+/// renamed to the state file. This makes sure that the state file is never
+/// partially written.
+pub fn save_configuration(
+    path: &str,
+    pl: &chirpstack_api::gw::GatewayConfiguration,
+) -> Result<(), String> {
+    info!(
+        "Saving gateway configuration to state file, state_file: {}, version: {}",
+        path, pl.version
+    );
+
+    let mut buf = Vec::new();
+    pl.encode(&mut buf).map_err(|e| e.to_string())?;
+
+    let tmp_path = format!("{}.tmp", path);
+    let mut f = fs::File::create(&tmp_path).map_err(|e| e.to_string())?;
+    f.write_all(&buf).map_err(|e| e.to_string())?;
+    f.sync_all().map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|e| e.to_string())?;
+
+    return Ok(());
+}
+
+/// Load the gateway configuration from the state file. This returns None in
+/// case the state file does not exist.
+pub fn load_configuration(
+    path: &str,
+) -> Result<Option<chirpstack_api::gw::GatewayConfiguration>, String> {
+    let b = match fs::read(path) {
+        Ok(v) => v,
+        Err(err) => {
+            if err.kind() == ErrorKind::NotFound {
+                return Ok(None);
+            }
+            return Err(err.to_string());
+        }
+    };
+
+    let pl = chirpstack_api::gw::GatewayConfiguration::decode(&b[..]).map_err(|e| e.to_string())?;
+
+    info!(
+        "Gateway configuration loaded from state file, state_file: {}, version: {}",
+        path, pl.version
+    );
+
+    return Ok(Some(pl));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_configuration() {
+        let path =
+            std::env::temp_dir().join(format!("concentratord_state_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        assert_eq!(None, load_configuration(path).unwrap());
+
+        let pl = chirpstack_api::gw::GatewayConfiguration {
+            version: "1.2.3".to_string(),
+            channels: vec![chirpstack_api::gw::ChannelConfiguration {
+                frequency: 868100000,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        save_configuration(path, &pl).unwrap();
+        assert_eq!(Some(pl), load_configuration(path).unwrap());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,33 +1,127 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use uuid::Uuid;
 
 use super::events;
+use super::metrics;
+use super::proto;
 
 lazy_static! {
-    static ref STATS: Mutex<chirpstack_api::gw::GatewayStats> = Mutex::new(Default::default());
+    static ref STATS: Mutex<proto::GatewayStats> = Mutex::new(Default::default());
+
+    // There is no GatewayStats field for these, they are reported as
+    // rx_packets_dropped_<reason> meta-data.
+    static ref RX_PACKETS_DROPPED: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
 }
 
 pub fn inc_rx_packets_received() {
     let mut stats = STATS.lock().unwrap();
     stats.rx_packets_received += 1;
+    metrics::inc_rx_packets_received();
 }
 
 pub fn inc_rx_packets_received_ok() {
     let mut stats = STATS.lock().unwrap();
     stats.rx_packets_received_ok += 1;
+    metrics::inc_rx_packets_received_ok();
 }
 
 pub fn inc_tx_packets_received() {
     let mut stats = STATS.lock().unwrap();
     stats.tx_packets_received += 1;
+    metrics::inc_tx_packets_received();
 }
 
 pub fn inc_tx_packets_emitted() {
     let mut stats = STATS.lock().unwrap();
     stats.tx_packets_emitted += 1;
+    metrics::inc_tx_packets_emitted();
+}
+
+/// Increment the dropped counter for the given filter reason.
+pub fn inc_rx_packets_dropped(reason: &str) {
+    let mut dropped = RX_PACKETS_DROPPED.lock().unwrap();
+    *dropped.entry(reason.to_string()).or_insert(0) += 1;
+    metrics::inc_rx_packets_dropped(reason);
+}
+
+/// Increment the per frequency, modulation and CRC status counters for the
+/// given uplink.
+pub fn inc_rx_counts(pl: &chirpstack_api::gw::UplinkFrame) {
+    let mut stats = STATS.lock().unwrap();
+
+    if let Some(tx_info) = &pl.tx_info {
+        *stats
+            .rx_packets_per_frequency
+            .entry(tx_info.frequency)
+            .or_insert(0) += 1;
+
+        let modulation = match &tx_info.modulation_info {
+            Some(chirpstack_api::gw::uplink_tx_info::ModulationInfo::LoraModulationInfo(v)) => {
+                Some(get_lora_modulation(v))
+            }
+            Some(chirpstack_api::gw::uplink_tx_info::ModulationInfo::FskModulationInfo(v)) => {
+                Some(get_fsk_modulation(v))
+            }
+            None => None,
+        };
+
+        if let Some(modulation) = modulation {
+            inc_per_modulation_count(&mut stats.rx_packets_per_modulation, modulation);
+        }
+    }
+
+    if let Some(rx_info) = &pl.rx_info {
+        let crc_status = match rx_info.crc_status() {
+            chirpstack_api::gw::CrcStatus::NoCrc => "NO_CRC",
+            chirpstack_api::gw::CrcStatus::BadCrc => "BAD_CRC",
+            chirpstack_api::gw::CrcStatus::CrcOk => "CRC_OK",
+        };
+
+        *stats
+            .rx_packets_per_crc_status
+            .entry(crc_status.to_string())
+            .or_insert(0) += 1;
+    }
+}
+
+/// Increment the per frequency and modulation counters for the given
+/// downlink.
+pub fn inc_tx_counts(tx_info: &chirpstack_api::gw::DownlinkTxInfo) {
+    let mut stats = STATS.lock().unwrap();
+
+    *stats
+        .tx_packets_per_frequency
+        .entry(tx_info.frequency)
+        .or_insert(0) += 1;
+
+    let modulation = match &tx_info.modulation_info {
+        Some(chirpstack_api::gw::downlink_tx_info::ModulationInfo::LoraModulationInfo(v)) => {
+            Some(get_lora_modulation(v))
+        }
+        Some(chirpstack_api::gw::downlink_tx_info::ModulationInfo::FskModulationInfo(v)) => {
+            Some(get_fsk_modulation(v))
+        }
+        None => None,
+    };
+
+    if let Some(modulation) = modulation {
+        inc_per_modulation_count(&mut stats.tx_packets_per_modulation, modulation);
+    }
+}
+
+/// Increment the per status counter.
+pub fn inc_tx_status(status: proto::TxAckStatus) {
+    let mut stats = STATS.lock().unwrap();
+    let status = status.name();
+
+    *stats
+        .tx_packets_per_status
+        .entry(status.to_string())
+        .or_insert(0) += 1;
+    metrics::inc_tx_status(status);
 }
 
 pub fn send_and_reset(
@@ -49,10 +143,181 @@ pub fn send_and_reset(
     stats.location = location;
     stats.meta_data = metadata.clone();
 
+    let mut dropped = RX_PACKETS_DROPPED.lock().unwrap();
+    for (reason, count) in dropped.iter() {
+        stats
+            .meta_data
+            .insert(format!("rx_packets_dropped_{}", reason), count.to_string());
+    }
+
     events::send_stats(&stats, &stats_id).unwrap();
 
     // reset stats
     *stats = Default::default();
+    dropped.clear();
 
     return Ok(());
 }
+
+/// Add the counters of src to dst, e.g. to aggregate the stats of multiple
+/// boards. The gateway ID, location and meta-data of dst are kept, except for
+/// the rx_packets_dropped_<reason> meta-data which is added up.
+pub fn merge(dst: &mut proto::GatewayStats, src: proto::GatewayStats) {
+    let time_key = |t: &Option<prost_types::Timestamp>| t.as_ref().map(|t| (t.seconds, t.nanos));
+    if time_key(&src.time) > time_key(&dst.time) {
+        dst.time = src.time;
+    }
+
+    dst.rx_packets_received += src.rx_packets_received;
+    dst.rx_packets_received_ok += src.rx_packets_received_ok;
+    dst.tx_packets_received += src.tx_packets_received;
+    dst.tx_packets_emitted += src.tx_packets_emitted;
+
+    for (k, v) in src.tx_packets_per_frequency {
+        *dst.tx_packets_per_frequency.entry(k).or_insert(0) += v;
+    }
+    for (k, v) in src.rx_packets_per_frequency {
+        *dst.rx_packets_per_frequency.entry(k).or_insert(0) += v;
+    }
+    for (k, v) in src.tx_packets_per_status {
+        *dst.tx_packets_per_status.entry(k).or_insert(0) += v;
+    }
+    for (k, v) in src.rx_packets_per_crc_status {
+        *dst.rx_packets_per_crc_status.entry(k).or_insert(0) += v;
+    }
+
+    for c in src.tx_packets_per_modulation {
+        add_per_modulation_count(&mut dst.tx_packets_per_modulation, c);
+    }
+    for c in src.rx_packets_per_modulation {
+        add_per_modulation_count(&mut dst.rx_packets_per_modulation, c);
+    }
+
+    for (k, v) in src.meta_data {
+        if !k.starts_with("rx_packets_dropped_") {
+            dst.meta_data.entry(k).or_insert(v);
+            continue;
+        }
+
+        let count: u32 = v.parse().unwrap_or(0);
+        let dst_count: u32 = dst
+            .meta_data
+            .get(&k)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        dst.meta_data.insert(k, (dst_count + count).to_string());
+    }
+}
+
+// Only the bandwidth and spreading-factor are taken into account.
+fn get_lora_modulation(mod_info: &chirpstack_api::gw::LoRaModulationInfo) -> proto::Modulation {
+    proto::Modulation {
+        parameters: Some(proto::modulation::Parameters::Lora(
+            chirpstack_api::gw::LoRaModulationInfo {
+                bandwidth: mod_info.bandwidth,
+                spreading_factor: mod_info.spreading_factor,
+                ..Default::default()
+            },
+        )),
+    }
+}
+
+// Only the datarate is taken into account.
+fn get_fsk_modulation(mod_info: &chirpstack_api::gw::FskModulationInfo) -> proto::Modulation {
+    proto::Modulation {
+        parameters: Some(proto::modulation::Parameters::Fsk(
+            chirpstack_api::gw::FskModulationInfo {
+                datarate: mod_info.datarate,
+                ..Default::default()
+            },
+        )),
+    }
+}
+
+fn inc_per_modulation_count(
+    counts: &mut Vec<proto::PerModulationCount>,
+    modulation: proto::Modulation,
+) {
+    match counts
+        .iter_mut()
+        .find(|c| c.modulation.as_ref() == Some(&modulation))
+    {
+        Some(v) => v.count += 1,
+        None => counts.push(proto::PerModulationCount {
+            modulation: Some(modulation),
+            count: 1,
+        }),
+    }
+}
+
+fn add_per_modulation_count(
+    counts: &mut Vec<proto::PerModulationCount>,
+    count: proto::PerModulationCount,
+) {
+    match counts.iter_mut().find(|c| c.modulation == count.modulation) {
+        Some(v) => v.count += count.count,
+        None => counts.push(count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let modulation = proto::Modulation {
+            parameters: Some(proto::modulation::Parameters::Lora(
+                chirpstack_api::gw::LoRaModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 7,
+                    ..Default::default()
+                },
+            )),
+        };
+
+        let mut dst = proto::GatewayStats {
+            gateway_id: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            rx_packets_received: 2,
+            rx_packets_per_frequency: [(868100000, 2)].iter().cloned().collect(),
+            rx_packets_per_modulation: vec![proto::PerModulationCount {
+                modulation: Some(modulation.clone()),
+                count: 2,
+            }],
+            meta_data: [("rx_packets_dropped_crc".to_string(), "1".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        merge(
+            &mut dst,
+            proto::GatewayStats {
+                gateway_id: vec![8, 7, 6, 5, 4, 3, 2, 1],
+                rx_packets_received: 3,
+                rx_packets_per_frequency: [(868100000, 1), (868300000, 2)]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                rx_packets_per_modulation: vec![proto::PerModulationCount {
+                    modulation: Some(modulation.clone()),
+                    count: 3,
+                }],
+                meta_data: [("rx_packets_dropped_crc".to_string(), "2".to_string())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], dst.gateway_id);
+        assert_eq!(5, dst.rx_packets_received);
+        assert_eq!(3, dst.rx_packets_per_frequency[&868100000]);
+        assert_eq!(2, dst.rx_packets_per_frequency[&868300000]);
+        assert_eq!(1, dst.rx_packets_per_modulation.len());
+        assert_eq!(5, dst.rx_packets_per_modulation[0].count);
+        assert_eq!("3", dst.meta_data["rx_packets_dropped_crc"]);
+    }
+}
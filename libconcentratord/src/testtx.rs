@@ -0,0 +1,327 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+
+use super::proto;
+
+// Maximum duration of a test transmission.
+const MAX_DURATION: Duration = Duration::from_secs(3600);
+
+// Interval at which the test transmission checks if it must stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State::Idle);
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum State {
+    Idle,
+    Running,
+    Stopping,
+}
+
+/// Concentrator specific part of the test transmission.
+pub trait Transmitter {
+    /// Start emitting a continuous wave.
+    fn cw_on(&mut self) -> Result<(), String>;
+    /// Stop emitting the continuous wave.
+    fn cw_off(&mut self) -> Result<(), String>;
+    /// Send a single (immediate) LoRa test packet. This returns the time on
+    /// air of the packet.
+    fn send_packet(&mut self) -> Result<Duration, String>;
+}
+
+/// Test transmission parameters.
+#[derive(Debug, PartialEq)]
+pub struct Parameters {
+    pub cw: bool,
+    pub duration: Duration,
+    pub interval: Duration,
+}
+
+impl Parameters {
+    pub fn from_proto(pl: &proto::TestTx) -> Result<Parameters, String> {
+        let duration = match &pl.duration {
+            Some(v) => duration_from_proto(v)?,
+            None => return Err("duration must be set".to_string()),
+        };
+        if duration == Duration::from_secs(0) || duration > MAX_DURATION {
+            return Err(format!(
+                "duration must be greater than 0 and at most {:?}",
+                MAX_DURATION
+            ));
+        }
+
+        Ok(Parameters {
+            cw: pl.cw,
+            duration: duration,
+            interval: match &pl.interval {
+                Some(v) => duration_from_proto(v)?,
+                None => Duration::from_secs(0),
+            },
+        })
+    }
+}
+
+/// Returns the downlink item for the LoRa test packets of the given test
+/// transmission. This can be converted to the HAL packet like any other
+/// downlink. The payload contains an incrementing byte pattern.
+pub fn get_downlink_item(pl: &proto::TestTx) -> chirpstack_api::gw::DownlinkFrameItem {
+    let mut tx_info = chirpstack_api::gw::DownlinkTxInfo {
+        frequency: pl.frequency,
+        power: pl.power,
+        board: pl.board,
+        modulation_info: Some(
+            chirpstack_api::gw::downlink_tx_info::ModulationInfo::LoraModulationInfo(
+                chirpstack_api::gw::LoRaModulationInfo {
+                    bandwidth: pl.bandwidth,
+                    spreading_factor: pl.spreading_factor,
+                    code_rate: "4/5".to_string(),
+                    polarization_inversion: false,
+                },
+            ),
+        ),
+        timing_info: Some(
+            chirpstack_api::gw::downlink_tx_info::TimingInfo::ImmediatelyTimingInfo(
+                chirpstack_api::gw::ImmediatelyTimingInfo {},
+            ),
+        ),
+        ..Default::default()
+    };
+    tx_info.set_modulation(chirpstack_api::common::Modulation::Lora);
+    tx_info.set_timing(chirpstack_api::gw::DownlinkTiming::Immediately);
+
+    chirpstack_api::gw::DownlinkFrameItem {
+        phy_payload: (0..pl.payload_size.min(255)).map(|v| v as u8).collect(),
+        tx_info: Some(tx_info),
+    }
+}
+
+/// Start the test transmission in a new thread.
+///
+/// The test transmission runs until the duration has passed or until stop
+/// is called. Only one test transmission can be active at a time. While a
+/// test transmission is active, the JIT queue must not be processed (see
+/// is_active).
+pub fn start<T>(mut transmitter: T, params: Parameters) -> Result<(), String>
+where
+    T: Transmitter + Send + 'static,
+{
+    {
+        let mut state = STATE.lock().unwrap();
+        if *state != State::Idle {
+            return Err("test transmission is already active".to_string());
+        }
+        *state = State::Running;
+    }
+
+    info!(
+        "Starting test transmission, cw: {}, duration: {:?}, interval: {:?}",
+        params.cw, params.duration, params.interval
+    );
+
+    // The continuous wave is started before returning, so that an error can
+    // be returned to the caller.
+    if params.cw {
+        if let Err(err) = transmitter.cw_on() {
+            *STATE.lock().unwrap() = State::Idle;
+            return Err(err);
+        }
+    }
+
+    thread::spawn(move || {
+        if let Err(err) = run(&mut transmitter, &params) {
+            error!("Test transmission error, error: {}", err);
+        }
+
+        *STATE.lock().unwrap() = State::Idle;
+        info!("Test transmission stopped");
+    });
+
+    return Ok(());
+}
+
+/// Stop the active test transmission. This blocks until the test
+/// transmission has stopped.
+pub fn stop() -> Result<(), String> {
+    {
+        let mut state = STATE.lock().unwrap();
+        if *state == State::Idle {
+            return Err("no test transmission is active".to_string());
+        }
+        *state = State::Stopping;
+    }
+
+    info!("Stopping test transmission");
+
+    while is_active() {
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    return Ok(());
+}
+
+/// Returns true when a test transmission is active.
+pub fn is_active() -> bool {
+    *STATE.lock().unwrap() != State::Idle
+}
+
+fn run<T: Transmitter>(transmitter: &mut T, params: &Parameters) -> Result<(), String> {
+    let end = Instant::now() + params.duration;
+
+    if params.cw {
+        wait(end);
+        return transmitter.cw_off();
+    }
+
+    let mut count: u32 = 0;
+    while !stopping() && Instant::now() < end {
+        let time_on_air = transmitter.send_packet()?;
+        count += 1;
+
+        // wait until the packet has been sent
+        wait(Instant::now() + time_on_air + params.interval);
+    }
+
+    info!("Test packets sent, count: {}", count);
+
+    return Ok(());
+}
+
+// Wait until the given time, or until the test transmission must stop.
+fn wait(until: Instant) {
+    while !stopping() && Instant::now() < until {
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn stopping() -> bool {
+    *STATE.lock().unwrap() == State::Stopping
+}
+
+fn duration_from_proto(d: &prost_types::Duration) -> Result<Duration, String> {
+    if d.seconds < 0 || d.nanos < 0 {
+        return Err("duration must not be negative".to_string());
+    }
+
+    Ok(Duration::new(d.seconds as u64, d.nanos as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    struct TransmitterMock {
+        packets: Arc<Mutex<u32>>,
+    }
+
+    impl Transmitter for TransmitterMock {
+        fn cw_on(&mut self) -> Result<(), String> {
+            return Err("not supported".to_string());
+        }
+
+        fn cw_off(&mut self) -> Result<(), String> {
+            return Ok(());
+        }
+
+        fn send_packet(&mut self) -> Result<Duration, String> {
+            *self.packets.lock().unwrap() += 1;
+            return Ok(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_parameters_from_proto() {
+        let mut pl = proto::TestTx {
+            cw: true,
+            ..Default::default()
+        };
+        assert!(Parameters::from_proto(&pl).is_err());
+
+        pl.duration = Some(prost_types::Duration {
+            seconds: 7200,
+            nanos: 0,
+        });
+        assert!(Parameters::from_proto(&pl).is_err());
+
+        pl.duration = Some(prost_types::Duration {
+            seconds: 10,
+            nanos: 0,
+        });
+        assert_eq!(
+            Parameters {
+                cw: true,
+                duration: Duration::from_secs(10),
+                interval: Duration::from_secs(0),
+            },
+            Parameters::from_proto(&pl).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_start_stop() {
+        let packets = Arc::new(Mutex::new(0));
+
+        // cw is not supported by the mock
+        assert!(start(
+            TransmitterMock {
+                packets: packets.clone(),
+            },
+            Parameters {
+                cw: true,
+                duration: Duration::from_secs(1),
+                interval: Duration::from_secs(0),
+            },
+        )
+        .is_err());
+        assert_eq!(false, is_active());
+
+        // the test transmission ends after the duration
+        start(
+            TransmitterMock {
+                packets: packets.clone(),
+            },
+            Parameters {
+                cw: false,
+                duration: Duration::from_millis(50),
+                interval: Duration::from_millis(0),
+            },
+        )
+        .unwrap();
+        assert_eq!(true, is_active());
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(false, is_active());
+        assert!(*packets.lock().unwrap() >= 2);
+
+        // the test transmission is stopped
+        start(
+            TransmitterMock {
+                packets: packets.clone(),
+            },
+            Parameters {
+                cw: false,
+                duration: Duration::from_secs(60),
+                interval: Duration::from_millis(0),
+            },
+        )
+        .unwrap();
+        assert!(start(
+            TransmitterMock {
+                packets: packets.clone(),
+            },
+            Parameters {
+                cw: false,
+                duration: Duration::from_secs(60),
+                interval: Duration::from_millis(0),
+            },
+        )
+        .is_err());
+        stop().unwrap();
+        assert_eq!(false, is_active());
+        assert!(stop().is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+
+/// Gateway model definition.
+///
+/// A model definition is a TOML document containing the model name and the
+/// (daemon specific) model configuration. Values which depend on the
+/// model_flags are defined in a [flag.<FLAG>] table, values which depend on
+/// the antenna gain in an [antenna_gain.<GAIN>] table. These values override
+/// the base configuration of the model.
+#[derive(Clone, Debug)]
+pub struct Model {
+    pub name: String,
+    /// Accepted model_flags.
+    pub flags: Vec<String>,
+    /// File from which the model was loaded, None in case of a built-in model.
+    pub file: Option<String>,
+    table: toml::value::Table,
+}
+
+impl Model {
+    pub fn from_str(s: &str) -> Result<Model, String> {
+        let mut table: toml::value::Table = toml::from_str(s).map_err(|e| e.to_string())?;
+
+        let name = match table.remove("name") {
+            Some(toml::Value::String(v)) => v,
+            _ => return Err("name must be set".to_string()),
+        };
+
+        let flags = match table.get("flag") {
+            Some(toml::Value::Table(v)) => v.keys().cloned().collect(),
+            Some(_) => return Err("flag must be a table".to_string()),
+            None => vec![],
+        };
+
+        Ok(Model {
+            name: name,
+            flags: flags,
+            file: None,
+            table: table,
+        })
+    }
+
+    /// Returns the model configuration for the given model_flags and antenna
+    /// gain.
+    pub fn get_config<T: DeserializeOwned>(
+        &self,
+        flags: &[String],
+        antenna_gain: i8,
+    ) -> Result<T, String> {
+        let mut table = self.table.clone();
+
+        if let Some(toml::Value::Table(overrides)) = table.remove("flag") {
+            for flag in flags {
+                if let Some(toml::Value::Table(v)) = overrides.get(flag) {
+                    table.extend(v.clone());
+                }
+            }
+        }
+
+        if let Some(v) = table.remove("antenna_gain") {
+            let overrides = match v {
+                toml::Value::Table(v) => v,
+                _ => return Err("antenna_gain must be a table".to_string()),
+            };
+
+            match overrides.get(&antenna_gain.to_string()) {
+                Some(toml::Value::Table(v)) => table.extend(v.clone()),
+                _ => {
+                    return Err(format!(
+                        "invalid antenna_gain: {}, must be one of: [{}]",
+                        antenna_gain,
+                        overrides
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ))
+                }
+            }
+        }
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("model: {}, error: {}", self.name, e))
+    }
+}
+
+/// Returns the given built-in models, extended by the models defined in the
+/// model_dir. A model from the model_dir overrides the built-in model with the
+/// same name.
+pub fn get_models(builtin: &[&str], model_dir: &str) -> Result<Vec<Model>, String> {
+    let mut models: Vec<Model> = Vec::new();
+
+    for s in builtin {
+        models.push(Model::from_str(s).map_err(|e| format!("parse built-in model error: {}", e))?);
+    }
+
+    if model_dir.is_empty() {
+        return Ok(models);
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(model_dir)
+        .map_err(|e| {
+            format!(
+                "read model_dir error, model_dir: {}, error: {}",
+                model_dir, e
+            )
+        })?
+        .filter_map(|v| v.ok())
+        .map(|v| v.path())
+        .filter(|v| v.extension().map_or(false, |ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let file = path.display().to_string();
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("read model error, file: {}, error: {}", file, e))?;
+        let mut model = Model::from_str(&content)
+            .map_err(|e| format!("parse model error, file: {}, error: {}", file, e))?;
+        model.file = Some(file);
+
+        match models.iter_mut().find(|m| m.name == model.name) {
+            Some(v) => *v = model,
+            None => models.push(model),
+        }
+    }
+
+    return Ok(models);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        spidev_path: String,
+        gps_tty_path: Option<String>,
+        tx_gain_table: Vec<i8>,
+    }
+
+    const MODEL: &str = r#"
+        name = "test_model"
+        spidev_path = "/dev/spidev0.0"
+        tx_gain_table = [0, 1, 2]
+
+        [flag.GNSS]
+        gps_tty_path = "/dev/ttyAMA0"
+
+        [flag.AP2]
+        spidev_path = "/dev/spidev1.0"
+
+        [antenna_gain.0]
+
+        [antenna_gain.4]
+        tx_gain_table = [4, 5, 6]
+    "#;
+
+    #[test]
+    fn test_get_config() {
+        let model = Model::from_str(MODEL).unwrap();
+        assert_eq!("test_model", model.name);
+        assert_eq!(vec!["AP2".to_string(), "GNSS".to_string()], model.flags);
+
+        let config: Config = model.get_config(&[], 0).unwrap();
+        assert_eq!(
+            Config {
+                spidev_path: "/dev/spidev0.0".to_string(),
+                gps_tty_path: None,
+                tx_gain_table: vec![0, 1, 2],
+            },
+            config
+        );
+
+        let config: Config = model
+            .get_config(&["GNSS".to_string(), "AP2".to_string()], 4)
+            .unwrap();
+        assert_eq!(
+            Config {
+                spidev_path: "/dev/spidev1.0".to_string(),
+                gps_tty_path: Some("/dev/ttyAMA0".to_string()),
+                tx_gain_table: vec![4, 5, 6],
+            },
+            config
+        );
+
+        assert!(model.get_config::<Config>(&[], 2).is_err());
+    }
+}
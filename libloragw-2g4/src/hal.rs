@@ -30,10 +30,12 @@ impl ConvertBandwidth for Bandwidth {
     }
 
     fn to_hal(&self) -> u32 {
+        // The exact bandwidths (e.g. 812500 for the BW812 datarate) are
+        // accepted too.
         match self {
-            203000 => wrapper::e_bandwidth_BW_200KHZ,
-            406000 => wrapper::e_bandwidth_BW_400KHZ,
-            812000 => wrapper::e_bandwidth_BW_800KHZ,
+            203000 | 203125 => wrapper::e_bandwidth_BW_200KHZ,
+            406000 | 406250 => wrapper::e_bandwidth_BW_400KHZ,
+            812000 | 812500 => wrapper::e_bandwidth_BW_800KHZ,
             1625000 => wrapper::e_bandwidth_BW_1600KHZ,
             _ => 0,
         }
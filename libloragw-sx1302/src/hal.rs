@@ -1,5 +1,6 @@
 use std::convert::TryInto;
 use std::ffi::{CStr, CString};
+use std::fmt;
 use std::mem::transmute;
 use std::os::raw::c_char;
 use std::time::Duration;
@@ -84,6 +85,8 @@ pub enum Modulation {
     Undefined,
     LoRa,
     FSK,
+    /// Continuous wave (TX only).
+    CW,
 }
 
 impl Modulation {
@@ -92,6 +95,7 @@ impl Modulation {
             Modulation::Undefined => wrapper::MOD_UNDEFINED,
             Modulation::LoRa => wrapper::MOD_LORA,
             Modulation::FSK => wrapper::MOD_FSK,
+            Modulation::CW => wrapper::MOD_CW,
         } as u8;
     }
 
@@ -99,6 +103,7 @@ impl Modulation {
         match modulation as u32 {
             wrapper::MOD_LORA => Modulation::LoRa,
             wrapper::MOD_FSK => Modulation::FSK,
+            wrapper::MOD_CW => Modulation::CW,
             _ => Modulation::Undefined,
         }
     }
@@ -753,6 +758,36 @@ impl SX1261Config {
     }
 }
 
+/// Spectral scan status.
+#[derive(Debug, PartialEq)]
+pub enum SpectralScanStatus {
+    None,
+    OnGoing,
+    Aborted,
+    Completed,
+    Unknown,
+}
+
+impl SpectralScanStatus {
+    fn from_hal(status: wrapper::lgw_spectral_scan_status_t) -> Self {
+        match status {
+            wrapper::lgw_spectral_scan_status_t_LGW_SPECTRAL_SCAN_STATUS_NONE => {
+                SpectralScanStatus::None
+            }
+            wrapper::lgw_spectral_scan_status_t_LGW_SPECTRAL_SCAN_STATUS_ON_GOING => {
+                SpectralScanStatus::OnGoing
+            }
+            wrapper::lgw_spectral_scan_status_t_LGW_SPECTRAL_SCAN_STATUS_ABORTED => {
+                SpectralScanStatus::Aborted
+            }
+            wrapper::lgw_spectral_scan_status_t_LGW_SPECTRAL_SCAN_STATUS_COMPLETED => {
+                SpectralScanStatus::Completed
+            }
+            _ => SpectralScanStatus::Unknown,
+        }
+    }
+}
+
 /// Spectral scan result.
 pub struct SpectralScanResult {
     /// dBm level.
@@ -764,6 +799,24 @@ pub struct SpectralScanResult {
 
 const MAX_PKT: usize = 8;
 
+/// Send error.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendError {
+    /// TX was not allowed by listen-before-talk (the channel is busy).
+    LbtNotAllowed,
+    /// Any other send error.
+    Failed(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::LbtNotAllowed => write!(f, "lgw_send failed, tx not allowed by lbt"),
+            SendError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
 /// Configure the gateway board.
 pub fn board_setconf(conf: &BoardConfig) -> Result<(), String> {
     let mut conf = conf.to_hal()?;
@@ -922,12 +975,17 @@ pub fn receive() -> Result<Vec<RxPacket>, String> {
 /// trigger signal. Because there is no way to anticipate the triggering event and
 /// start the analog circuitry beforehand, that delay must be taken into account in
 /// the protocol.
-pub fn send(pkt: &TxPacket) -> Result<(), String> {
+/// When listen-before-talk is enabled and the channel is busy,
+/// SendError::LbtNotAllowed is returned.
+pub fn send(pkt: &TxPacket) -> Result<(), SendError> {
     let _guard = mutex::CONCENTATOR.lock().unwrap();
     let mut pkt = pkt.to_hal();
     let ret = unsafe { wrapper::lgw_send(&mut pkt) };
+    if ret == wrapper::LGW_LBT_NOT_ALLOWED as i32 {
+        return Err(SendError::LbtNotAllowed);
+    }
     if ret != 0 {
-        return Err("lgw_send failed".to_string());
+        return Err(SendError::Failed("lgw_send failed".to_string()));
     }
     return Ok(());
 }
@@ -1027,6 +1085,27 @@ pub fn spectral_scan_start(freq_hz: u32, nb_scan: u16) -> Result<(), String> {
     return Ok(());
 }
 
+/// Get the status of a spectral scan.
+pub fn spectral_scan_get_status() -> Result<SpectralScanStatus, String> {
+    let _guard = mutex::CONCENTATOR.lock().unwrap();
+    let mut status: wrapper::lgw_spectral_scan_status_t = 0;
+    let ret = unsafe { wrapper::lgw_spectral_scan_get_status(&mut status) };
+    if ret != 0 {
+        return Err("lgw_spectral_scan_get_status failed".to_string());
+    }
+    return Ok(SpectralScanStatus::from_hal(status));
+}
+
+/// Abort the current spectral scan.
+pub fn spectral_scan_abort() -> Result<(), String> {
+    let _guard = mutex::CONCENTATOR.lock().unwrap();
+    let ret = unsafe { wrapper::lgw_spectral_scan_abort() };
+    if ret != 0 {
+        return Err("lgw_spectral_scan_abort failed".to_string());
+    }
+    return Ok(());
+}
+
 /// Get the channel scan results.
 pub fn spectral_scan_get_results() -> Result<Vec<SpectralScanResult>, String> {
     let _guard = mutex::CONCENTATOR.lock().unwrap();